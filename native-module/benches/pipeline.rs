@@ -0,0 +1,20 @@
+// Per-stage cost of the capture pipeline over one second of speech-like
+// audio. Throughput is reported per sample, so runs stay comparable if the
+// input length changes.
+//
+//   cargo bench --features test-utils --bench pipeline
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use smarterli_audio::benchmarks::pipeline_cases;
+
+fn pipeline(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pipeline");
+    for mut case in pipeline_cases() {
+        group.throughput(Throughput::Elements(case.samples() as u64));
+        group.bench_function(case.name(), |b| b.iter(|| case.run()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, pipeline);
+criterion_main!(benches);
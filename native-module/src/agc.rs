@@ -11,7 +11,16 @@
 //   - Slow release (~500 ms): gain rises slowly after the loud signal
 //     ends, preventing pumping on short pauses.
 //   - Gain is computed from the peak envelope, not RMS, for faster
-//     transient response on bursty VoIP audio.
+//     transient response on bursty VoIP audio. `DetectorMode::Rms` trades
+//     that back for stability on steady speech with isolated spikes, and
+//     `Hybrid` follows whichever of the two reads louder.
+
+use anyhow::{ensure, Result};
+
+use crate::compressor::GainLink;
+use crate::envelope::EnvelopeFollower;
+use crate::levels::{NoiseFloor, SlidingRms};
+use crate::stage::AudioStage;
 
 /// Target peak level for normalised output.
 /// 0.25 keeps headroom for the i16 conversion while being loud enough for STT.
@@ -34,63 +43,380 @@ const ENVELOPE_RELEASE: f32 = 0.99993;
 /// 0.02 gives ~500 ms time constant — slow rise prevents pumping.
 const GAIN_RELEASE_COEFF: f32 = 0.02;
 
-/// Minimum peak envelope to act on. Below this, hold gain (silence).
+/// Longest `time_to_converge` will run before giving up: 30s at 48kHz
+const CONVERGENCE_LIMIT_SAMPLES: usize = 48_000 * 30;
+
+/// Default minimum peak envelope to act on. Below this, hold gain (silence).
 const SILENCE_FLOOR: f32 = 0.0001;
 
+/// Capture rate the per-sample coefficients assume.
+const SAMPLE_RATE: f32 = 48000.0;
+
+/// RMS detector window: 100ms at 48kHz, long enough that a lone spike
+/// barely moves it
+const RMS_DETECTOR_WINDOW: usize = 4800;
+
+/// Scales an RMS reading to a peak-equivalent level (a sine's crest
+/// factor), so a steady tone gets the same gain in every detector mode.
+const RMS_TO_PEAK: f32 = std::f32::consts::SQRT_2;
+
+/// Default peak-hold fall time (seconds to drop 20 dB).
+const PEAK_HOLD_DECAY_S: f32 = 1.5;
+
+/// Per-sample multiplier that drops a level by 20 dB over `decay_s`.
+fn peak_hold_coeff(decay_s: f32) -> f32 {
+    0.1f32.powf(1.0 / (SAMPLE_RATE * decay_s.max(1e-3)))
+}
+
+/// Level the gain is computed from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DetectorMode {
+    /// Instant-attack peak envelope: best transient protection, but an
+    /// isolated spike ducks the gain for the whole release
+    #[default]
+    Peak,
+    /// 100ms sliding RMS (scaled to a peak-equivalent): stable on steady
+    /// speech, slower to catch onsets
+    Rms,
+    /// The louder of the two
+    Hybrid,
+}
+
+/// Tunable AGC behaviour. `AgcConfig::default()` matches `AutoGainControl::new()`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AgcConfig {
+    /// Gain attack time in ms. 0.0 (default) drops the gain instantly for
+    /// the whole batch, so nothing clips but every loud onset gets a hard
+    /// "suck". A few ms (e.g. 2.0) ramps the gain down per sample instead:
+    /// smoother, at the cost of a few clipped samples at the onset. Must be
+    /// finite and ≥ 0.
+    pub attack_ms: f32,
+    /// Detected level below which the gain is held instead of adapting
+    /// (default 0.0001 ≈ -80 dBFS). Raise it in noisy rooms so HVAC hum in
+    /// pauses isn't pumped up. Must be finite and positive.
+    pub silence_floor: f32,
+    /// Soft-start time in ms after construction or `reset()`. The gain
+    /// starts at unity and its ceiling ramps (in dB) up to `MAX_GAIN` over
+    /// this period, so speech arriving the moment capture starts isn't hit
+    /// with full gain before the envelope has adapted. 0.0 (default)
+    /// starts at `MAX_GAIN` immediately.
+    pub startup_ms: f32,
+    /// Level detector the gain follows (default `Peak`)
+    pub detector: DetectorMode,
+    /// Per-batch gain release coefficient (default 0.02, ~500ms with 10ms
+    /// batches). Higher values recover gain faster after a loud passage,
+    /// at the cost of more pumping. Must be in (0, 1].
+    pub gain_release: f32,
+}
+
+impl Default for AgcConfig {
+    fn default() -> Self {
+        Self {
+            attack_ms: 0.0,
+            silence_floor: SILENCE_FLOOR,
+            startup_ms: 0.0,
+            detector: DetectorMode::Peak,
+            gain_release: GAIN_RELEASE_COEFF,
+        }
+    }
+}
+
+impl AgcConfig {
+    pub fn validate(&self) -> Result<()> {
+        ensure!(self.attack_ms.is_finite() && self.attack_ms >= 0.0,
+            "attack_ms must be >= 0, got {}", self.attack_ms);
+        ensure!(self.silence_floor.is_finite() && self.silence_floor > 0.0,
+            "silence_floor must be > 0, got {}", self.silence_floor);
+        ensure!(self.startup_ms >= 0.0, "startup_ms must be >= 0, got {}", self.startup_ms);
+        ensure!(self.gain_release > 0.0 && self.gain_release <= 1.0,
+            "gain_release must be in (0, 1], got {}", self.gain_release);
+        Ok(())
+    }
+
+    /// Per-sample one-pole coefficient for `attack_ms` (1.0 = instant).
+    fn attack_coeff(&self) -> f32 {
+        attack_coeff(self.attack_ms)
+    }
+}
+
+/// Per-sample one-pole coefficient for an attack time in ms (1.0 = instant).
+fn attack_coeff(attack_ms: f32) -> f32 {
+    if attack_ms <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-1000.0 / (SAMPLE_RATE * attack_ms)).exp()
+    }
+}
+
+/// Apply `gain` (≥ 1) and hard-clip to ±1.0. The input is clipped first:
+/// with gain ≥ 1 that gives the same result for any in-range product, but
+/// a huge finite sample can no longer overflow to ±inf in the multiply.
+#[inline]
+fn apply_gain(sample: f32, gain: f32) -> f32 {
+    (sample.clamp(-1.0, 1.0) * gain).clamp(-1.0, 1.0)
+}
+
 pub struct AutoGainControl {
     current_gain: f32,
-    peak_envelope: f32,
+    /// Gain at construction and after `reset()`
+    initial_gain: f32,
+    /// Instant-attack peak follower releasing by `ENVELOPE_RELEASE`
+    peak_envelope: EnvelopeFollower,
+    /// Sliding RMS for the `Rms`/`Hybrid` detectors
+    rms_detector: SlidingRms,
+    detector: DetectorMode,
+    /// Per-batch gain release coefficient from `AgcConfig::gain_release`
+    gain_release: f32,
+    /// Per-sample gain attack coefficient from `AgcConfig::attack_ms`
+    attack_coeff: f32,
+    /// Detected level below which the gain is held
+    silence_floor: f32,
+    /// Output peak meter (post-gain), decaying by `peak_hold_decay` per sample
+    peak_hold: f32,
+    peak_hold_decay: f32,
+    /// Soft-start length and samples processed since construction/reset
+    startup_samples: usize,
+    elapsed_samples: usize,
+    /// Where the gain is published after every batch, once linked
+    gain_link: Option<GainLink>,
 }
 
 impl AutoGainControl {
     pub fn new() -> Self {
+        Self::from_config(AgcConfig::default())
+    }
+
+    pub fn with_config(config: AgcConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(Self::from_config(config))
+    }
+
+    fn from_config(config: AgcConfig) -> Self {
+        let startup_samples = (config.startup_ms * SAMPLE_RATE / 1000.0) as usize;
+        let initial_gain = Self::initial_gain(startup_samples);
         Self {
-            current_gain: MAX_GAIN, // start high so first speech is audible
-            peak_envelope: 0.0,
+            current_gain: initial_gain,
+            initial_gain,
+            peak_envelope: EnvelopeFollower::new(1.0, 1.0 - ENVELOPE_RELEASE),
+            rms_detector: SlidingRms::new(RMS_DETECTOR_WINDOW),
+            detector: config.detector,
+            gain_release: config.gain_release,
+            attack_coeff: config.attack_coeff(),
+            silence_floor: config.silence_floor,
+            peak_hold: 0.0,
+            peak_hold_decay: peak_hold_coeff(PEAK_HOLD_DECAY_S),
+            startup_samples,
+            elapsed_samples: 0,
+            gain_link: None,
+        }
+    }
+
+    /// Warm start for continuous streams: begin at `gain` (e.g. 1.0, or a
+    /// calibrated value from a previous session) instead of `MAX_GAIN`,
+    /// so the first frames aren't blasted while the envelope adapts.
+    /// Clamped to the AGC's gain range; `reset()` returns to it.
+    pub fn new_with_initial_gain(gain: f32) -> Self {
+        let mut agc = Self::new();
+        agc.initial_gain = gain.clamp(MIN_GAIN, MAX_GAIN);
+        agc.current_gain = agc.initial_gain;
+        agc
+    }
+
+    /// Start high so first speech is audible, unless soft-starting.
+    fn initial_gain(startup_samples: usize) -> f32 {
+        if startup_samples > 0 { MIN_GAIN } else { MAX_GAIN }
+    }
+
+    /// Gain ceiling: `MAX_GAIN`, or a dB-linear ramp up to it while soft-starting.
+    fn max_gain(&self) -> f32 {
+        if self.elapsed_samples >= self.startup_samples {
+            MAX_GAIN
+        } else {
+            MAX_GAIN.powf(self.elapsed_samples as f32 / self.startup_samples as f32)
         }
     }
 
+    /// Output peak with a slow fall, for a UI peak meter. Unlike the
+    /// internal control envelope this is measured after gain and clipping.
+    pub fn peak_hold(&self) -> f32 {
+        self.peak_hold
+    }
+
+    pub fn reset_peak_hold(&mut self) {
+        self.peak_hold = 0.0;
+    }
+
+    /// Hold the gain at and below a calibrated noise floor (shared with
+    /// the processor's stages), instead of `AgcConfig::silence_floor`. The
+    /// floor is an RMS level, scaled here to the detector's peak-equivalent.
+    pub fn set_noise_floor(&mut self, floor: NoiseFloor) {
+        self.silence_floor = floor.rms() * RMS_TO_PEAK;
+    }
+
+    /// Change the gain attack time while running (see `AgcConfig::attack_ms`).
+    /// Takes effect from the next batch. Must be finite and ≥ 0.
+    pub fn set_attack_ms(&mut self, attack_ms: f32) -> Result<()> {
+        ensure!(attack_ms.is_finite() && attack_ms >= 0.0, "attack_ms must be >= 0, got {}", attack_ms);
+        self.attack_coeff = attack_coeff(attack_ms);
+        Ok(())
+    }
+
+    /// Change the gain release coefficient while running (see
+    /// `AgcConfig::gain_release`). Must be in (0, 1].
+    pub fn set_gain_release(&mut self, coeff: f32) -> Result<()> {
+        ensure!(coeff > 0.0 && coeff <= 1.0, "gain_release must be in (0, 1], got {}", coeff);
+        self.gain_release = coeff;
+        Ok(())
+    }
+
+    /// Handle publishing this AGC's gain after every batch, for a
+    /// downstream normalizer to account for (see
+    /// `SystemAudioProcessor::set_upstream_gain`). Repeated calls return
+    /// clones of the same link.
+    pub fn gain_link(&mut self) -> GainLink {
+        let current_gain = self.current_gain;
+        self.gain_link.get_or_insert_with(|| GainLink::new(current_gain)).clone()
+    }
+
+    /// Time for the held peak to fall by 20 dB (default 1.5 s).
+    pub fn set_peak_hold_decay(&mut self, seconds: f32) {
+        self.peak_hold_decay = peak_hold_coeff(seconds);
+    }
+
     /// Apply AGC to a batch of f32 samples **in-place**.
     /// Call this on raw CoreAudioTap samples before resampling.
     pub fn process(&mut self, samples: &mut [f32]) {
+        self.process_frame(samples);
+    }
+
+    /// Same as `process`, returning the mean gain applied to the batch
+    /// (constant across the batch unless a finite attack is ramping; 1.0
+    /// for an empty batch).
+    pub fn process_frame(&mut self, samples: &mut [f32]) -> f32 {
+        let gain = self.apply_frame(samples);
+        self.publish_gain();
+        gain
+    }
+
+    /// Share the current gain with a linked downstream stage.
+    fn publish_gain(&self) {
+        if let Some(link) = self.gain_link.as_ref() {
+            link.set(self.current_gain);
+        }
+    }
+
+    /// `process_frame` without publishing the gain.
+    fn apply_frame(&mut self, samples: &mut [f32]) -> f32 {
         if samples.is_empty() {
-            return;
+            return 1.0;
         }
+        self.elapsed_samples = self.elapsed_samples.saturating_add(samples.len());
+        let max_gain = self.max_gain();
 
-        // 1. Update peak envelope from this batch
+        // 1. Update detectors from this batch. Peak envelope: instant
+        //    attack, slow release towards zero. Both always run so the
+        //    level is settled whichever mode is in use.
+        let mut peak_envelope = self.peak_envelope.value();
+        let mut rms = 0.0;
+        //    Detectors see full scale at most, so an out-of-range sample
+        //    can't overflow the RMS sum or pin the envelope for seconds.
         for &s in samples.iter() {
-            let abs = s.abs();
-            if abs > self.peak_envelope {
-                // Instant attack: envelope jumps to peak immediately
-                self.peak_envelope = abs;
-            } else {
-                // Slow release: envelope decays toward zero
-                self.peak_envelope *= ENVELOPE_RELEASE;
-            }
+            let s = s.clamp(-1.0, 1.0);
+            peak_envelope = self.peak_envelope.process_sample(s.abs());
+            rms = self.rms_detector.push(s);
         }
+        let level = match self.detector {
+            DetectorMode::Peak => peak_envelope,
+            DetectorMode::Rms => rms * RMS_TO_PEAK,
+            DetectorMode::Hybrid => peak_envelope.max(rms * RMS_TO_PEAK),
+        };
 
-        // 2. Compute desired gain from peak envelope
-        if self.peak_envelope > SILENCE_FLOOR {
-            let desired_gain = (TARGET_PEAK / self.peak_envelope).clamp(MIN_GAIN, MAX_GAIN);
+        // 2. Compute desired gain from the detected level
+        let mut attack_target = None;
+        if level > self.silence_floor {
+            let desired_gain = (TARGET_PEAK / level).clamp(MIN_GAIN, max_gain);
 
             if desired_gain < self.current_gain {
-                // Instant attack: gain drops immediately when signal is loud.
-                // This prevents clipping at the start of speech bursts.
-                self.current_gain = desired_gain;
+                if self.attack_coeff >= 1.0 {
+                    // Instant attack: gain drops immediately when signal is loud.
+                    // This prevents clipping at the start of speech bursts.
+                    self.current_gain = desired_gain;
+                } else {
+                    // Finite attack: ramp down per sample while applying
+                    attack_target = Some(desired_gain);
+                }
             } else {
                 // Slow release: gain rises slowly after signal gets quieter.
                 // Prevents pumping between words/pauses.
-                self.current_gain += GAIN_RELEASE_COEFF * (desired_gain - self.current_gain);
-                self.current_gain = self.current_gain.clamp(MIN_GAIN, MAX_GAIN);
+                self.current_gain += self.gain_release * (desired_gain - self.current_gain);
+                self.current_gain = self.current_gain.clamp(MIN_GAIN, max_gain);
             }
         }
         // If below silence floor: hold current gain (don't adapt).
 
         // 3. Apply gain with hard clip (soft clip was distorting speech)
-        let gain = self.current_gain;
+        let Some(target) = attack_target else {
+            let gain = self.current_gain;
+            for sample in samples.iter_mut() {
+                *sample = apply_gain(*sample, gain);
+                self.peak_hold = sample.abs().max(self.peak_hold * self.peak_hold_decay);
+            }
+            return gain;
+        };
+
+        let mut gain_sum = 0.0;
         for sample in samples.iter_mut() {
-            *sample = (*sample * gain).clamp(-1.0, 1.0);
+            self.current_gain += self.attack_coeff * (target - self.current_gain);
+            *sample = apply_gain(*sample, self.current_gain);
+            self.peak_hold = sample.abs().max(self.peak_hold * self.peak_hold_decay);
+            gain_sum += self.current_gain;
         }
+        gain_sum / samples.len() as f32
+    }
+
+    /// Return to the start-up gain with a cleared envelope and peak meter,
+    /// keeping settings.
+    pub fn reset(&mut self) {
+        self.current_gain = self.initial_gain;
+        self.peak_envelope.reset();
+        self.rms_detector.reset();
+        self.peak_hold = 0.0;
+        self.elapsed_samples = 0;
+        self.publish_gain();
+    }
+}
+
+/// Samples `agc` takes, from its current state, to bring its gain within
+/// `tolerance` (a fraction, e.g. 0.05 for 5%) of the steady-state gain for
+/// a tone whose peak is `target_level`. Feeds the tone in 10ms batches, so
+/// the result is a multiple of 480. For automated tuning of the release:
+/// drive the AGC to a known state (e.g. with a loud passage), then measure
+/// the recovery. Returns `CONVERGENCE_LIMIT_SAMPLES` (30s) if the gain
+/// hasn't converged by then.
+pub fn time_to_converge(agc: &mut AutoGainControl, target_level: f32, tolerance: f32) -> usize {
+    const BATCH: usize = 480;
+    let steady_gain = (TARGET_PEAK / target_level).clamp(MIN_GAIN, MAX_GAIN);
+    let step = 2.0 * std::f32::consts::PI * 1000.0 / SAMPLE_RATE;
+    let mut elapsed = 0;
+    while elapsed < CONVERGENCE_LIMIT_SAMPLES {
+        if (agc.current_gain - steady_gain).abs() <= tolerance * steady_gain {
+            return elapsed;
+        }
+        let mut batch: Vec<f32> =
+            (elapsed..elapsed + BATCH).map(|i| target_level * (step * i as f32).sin()).collect();
+        agc.process(&mut batch);
+        elapsed += BATCH;
+    }
+    CONVERGENCE_LIMIT_SAMPLES
+}
+
+impl AudioStage for AutoGainControl {
+    fn process(&mut self, samples: &mut [f32]) {
+        AutoGainControl::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        AutoGainControl::reset(self);
     }
 }
 
@@ -181,6 +507,280 @@ mod tests {
             "Gain should hold during silence: before={}, after={}", gain_before, gain_after);
     }
 
+    #[test]
+    fn test_process_frame_reports_gain() {
+        let mut agc = AutoGainControl::new();
+        let input: Vec<f32> = (0..480).map(|i| {
+            0.002 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin()
+        }).collect();
+        let mut frame = input.clone();
+        let gain = agc.process_frame(&mut frame);
+        assert_eq!(gain, agc.current_gain);
+        assert!((frame[10] - input[10] * gain).abs() < 1e-6);
+        assert_eq!(agc.process_frame(&mut []), 1.0);
+    }
+
+    #[test]
+    fn test_warm_start_at_unity() {
+        let input: Vec<f32> = (0..480).map(|i| {
+            0.2 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin()
+        }).collect();
+
+        let mut agc = AutoGainControl::new_with_initial_gain(1.0);
+        let mut frame = input;
+        let gain = agc.process_frame(&mut frame);
+        assert!((gain - 1.0).abs() < 0.01, "First frame gain {}", gain);
+        let peak = frame.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!(peak < 0.21, "Normal-level input shouldn't be amplified: peak={}", peak);
+
+        agc.process(&mut vec![0.001; 4800]);
+        agc.reset();
+        assert_eq!(agc.current_gain, 1.0);
+    }
+
+    #[test]
+    fn test_huge_sample_at_max_gain_stays_finite() {
+        let mut agc = AutoGainControl::new();
+        assert_eq!(agc.current_gain, MAX_GAIN);
+        let mut frame = vec![0.0f32; 480];
+        frame[0] = f32::MAX / 2.0;
+        frame[1] = -f32::MAX / 2.0;
+        agc.process(&mut frame);
+        assert_eq!((frame[0], frame[1]), (1.0, -1.0), "Clipped with sign kept");
+        assert!(frame.iter().all(|s| s.is_finite()));
+
+        // The next frame is processed normally, with a finite gain
+        let input: Vec<f32> = (0..480).map(|i| {
+            0.1 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin()
+        }).collect();
+        let mut next = input.clone();
+        let gain = agc.process_frame(&mut next);
+        assert!(gain.is_finite() && (MIN_GAIN..=MAX_GAIN).contains(&gain));
+        for (out, inp) in next.iter().zip(&input) {
+            assert!((out - inp * gain).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_finite_attack_is_smoother() {
+        let burst: Vec<f32> = (0..480).map(|i| {
+            0.15 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin()
+        }).collect();
+
+        // Per-sample gain trajectory and clip count for one loud burst
+        // arriving at full gain
+        let run = |config: AgcConfig| {
+            let mut agc = AutoGainControl::with_config(config).unwrap();
+            let mut clipped = 0;
+            let mut max_step_db = 0.0f32;
+            let mut prev_gain = agc.current_gain;
+            for &s in &burst {
+                let mut one = [s];
+                agc.process(&mut one);
+                if one[0].abs() >= 1.0 {
+                    clipped += 1;
+                }
+                max_step_db = max_step_db.max(20.0 * (prev_gain / agc.current_gain).log10().abs());
+                prev_gain = agc.current_gain;
+            }
+            (clipped, max_step_db)
+        };
+
+        let (instant_clipped, instant_step) = run(AgcConfig::default());
+        let (finite_clipped, finite_step) = run(AgcConfig { attack_ms: 2.0, ..Default::default() });
+
+        assert_eq!(instant_clipped, 0);
+        assert!(finite_clipped > instant_clipped, "Finite attack should trade a few clipped samples");
+        assert!(finite_step < 1.0 && finite_step * 4.0 < instant_step,
+            "Finite attack should ramp the gain: {:.2} vs {:.2}dB/sample", finite_step, instant_step);
+    }
+
+    #[test]
+    fn test_attack_spreads_onset_within_frame() {
+        // Quiet lead-in settles the gain high, then an onset 10 dB louder
+        // arrives (still unclipped at the old gain, so output / input is
+        // the per-sample applied gain)
+        let quiet: Vec<f32> = (0..4800).map(|i| {
+            0.01 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin()
+        }).collect();
+        let onset: Vec<f32> = (0..480).map(|i| {
+            0.03 * (2.0 * std::f32::consts::PI * 440.0 * (i as f32 + 0.5) / 48000.0).sin()
+        }).collect();
+
+        let applied_gains = |attack_ms: f32| {
+            let mut agc = AutoGainControl::new();
+            agc.set_attack_ms(attack_ms).unwrap();
+            agc.process(&mut quiet.clone());
+            let mut frame = onset.clone();
+            agc.process(&mut frame);
+            frame.iter().zip(&onset).map(|(o, i)| o / i).collect::<Vec<f32>>()
+        };
+
+        // Instant: one gain for the whole frame
+        let instant = applied_gains(0.0);
+        assert!(instant.iter().all(|g| (g - instant[0]).abs() < 1e-3));
+
+        // 2ms: the reduction ramps in over the first ~100 samples
+        let ramped = applied_gains(2.0);
+        assert!(ramped[0] > 2.0 * ramped[200], "Gain should start high: {:.2} vs {:.2}", ramped[0], ramped[200]);
+        assert!(ramped[..100].windows(2).all(|w| w[1] <= w[0] + 1e-3), "Gain should fall monotonically");
+        assert!((ramped[400] - instant[400]).abs() < 0.05 * instant[400], "Ramp should settle at the same gain");
+
+        assert!(AutoGainControl::new().set_attack_ms(-1.0).is_err());
+        assert!(AutoGainControl::new().set_attack_ms(f32::INFINITY).is_err());
+        assert!(AutoGainControl::new().set_attack_ms(f32::NAN).is_err());
+    }
+
+    #[test]
+    fn test_silence_floor_config() {
+        assert!(AutoGainControl::with_config(AgcConfig { silence_floor: 0.0, ..Default::default() }).is_err());
+        assert!(AutoGainControl::with_config(AgcConfig { attack_ms: -1.0, ..Default::default() }).is_err());
+        assert!(AutoGainControl::with_config(AgcConfig { attack_ms: f32::INFINITY, ..Default::default() }).is_err());
+        assert!(AutoGainControl::with_config(AgcConfig { silence_floor: f32::INFINITY, ..Default::default() }).is_err());
+
+        let config = AgcConfig { silence_floor: 0.01, ..Default::default() };
+        let mut agc = AutoGainControl::with_config(config).unwrap();
+        let mut quiet: Vec<f32> = (0..480).map(|i| {
+            0.005 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin()
+        }).collect();
+        agc.process(&mut quiet);
+        assert_eq!(agc.current_gain, MAX_GAIN, "Below the raised floor the gain should hold");
+    }
+
+    #[test]
+    fn test_faster_release_converges_sooner() {
+        let recovery = |gain_release: f32| {
+            let mut agc = AutoGainControl::with_config(AgcConfig { gain_release, ..Default::default() }).unwrap();
+            // A loud passage pulls the gain to unity...
+            let mut loud: Vec<f32> = (0..4800).map(|i| {
+                0.5 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin()
+            }).collect();
+            agc.process(&mut loud);
+            assert_eq!(agc.current_gain, MIN_GAIN);
+            // ...then a quiet one needs it to climb to 25
+            time_to_converge(&mut agc, 0.01, 0.05)
+        };
+        let default = recovery(GAIN_RELEASE_COEFF);
+        let faster = recovery(0.05);
+        assert!(default < CONVERGENCE_LIMIT_SAMPLES, "Default release should converge");
+        assert!(faster < default, "Faster release should converge sooner: {} vs {} samples", faster, default);
+
+        assert!(AutoGainControl::with_config(AgcConfig { gain_release: 0.0, ..Default::default() }).is_err());
+        assert!(AutoGainControl::new().set_gain_release(1.5).is_err());
+    }
+
+    #[test]
+    fn test_rms_detector_ignores_rare_spikes() {
+        // Steady tone with a single-sample spike every 2s
+        let signal: Vec<f32> = (0..48000 * 8).map(|i| {
+            let tone = 0.02 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin();
+            if i % 96000 == 48000 { 0.8 } else { tone }
+        }).collect();
+
+        let gain_spread = |detector: DetectorMode| {
+            let mut agc = AutoGainControl::with_config(AgcConfig { detector, ..Default::default() }).unwrap();
+            let gains: Vec<f32> = signal
+                .chunks(480)
+                .map(|frame| {
+                    agc.process(&mut frame.to_vec());
+                    agc.current_gain
+                })
+                .skip(100)
+                .collect();
+            let max = gains.iter().cloned().fold(f32::MIN, f32::max);
+            let min = gains.iter().cloned().fold(f32::MAX, f32::min);
+            20.0 * (max / min).log10()
+        };
+
+        let peak = gain_spread(DetectorMode::Peak);
+        let rms = gain_spread(DetectorMode::Rms);
+        let hybrid = gain_spread(DetectorMode::Hybrid);
+        assert!(peak > 10.0, "Spikes should duck the peak-mode gain: {:.1} dB swing", peak);
+        assert!(rms < 3.0, "RMS-mode gain should stay stable: {:.1} dB swing", rms);
+        assert!(hybrid >= rms, "Hybrid follows the louder detector");
+    }
+
+    #[test]
+    fn test_soft_start_prevents_startup_blast() {
+        assert!(AutoGainControl::with_config(AgcConfig { startup_ms: -1.0, ..Default::default() }).is_err());
+
+        let frame: Vec<f32> = (0..480).map(|i| {
+            0.05 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin()
+        }).collect();
+        let peak = |s: &[f32]| s.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+
+        // Moderate speech arriving the instant capture starts; returns the
+        // first frame's peak and the steady-state peak
+        let run = |config: AgcConfig| {
+            let mut agc = AutoGainControl::with_config(config).unwrap();
+            let mut first = frame.clone();
+            agc.process(&mut first);
+            let mut last = frame.clone();
+            for _ in 0..300 {
+                last = frame.clone();
+                agc.process(&mut last);
+            }
+            (peak(&first), peak(&last))
+        };
+
+        let (blast, _) = run(AgcConfig { attack_ms: 2.0, ..Default::default() });
+        assert_eq!(blast, 1.0, "Without soft-start the first frame should clip");
+
+        let (first, steady) = run(AgcConfig { attack_ms: 2.0, startup_ms: 200.0, ..Default::default() });
+        assert!(first <= steady, "First frame over-amplified: {:.3} > steady {:.3}", first, steady);
+        assert!((steady - TARGET_PEAK).abs() < 0.02, "Should still converge to target: {:.3}", steady);
+
+        // reset() restarts the ramp
+        let mut agc = AutoGainControl::with_config(AgcConfig { startup_ms: 200.0, ..Default::default() }).unwrap();
+        agc.process(&mut vec![0.0f32; 48000]);
+        agc.reset();
+        assert_eq!(agc.current_gain, MIN_GAIN);
+    }
+
+    #[test]
+    fn test_peak_hold_decays() {
+        let mut agc = AutoGainControl::new();
+        let mut frame = vec![0.0f32; 480];
+        frame[0] = 0.5;
+        agc.process(&mut frame);
+        let start = agc.peak_hold();
+        // Unity gain at this level, so the held peak ≈ 0.5 (minus 10ms of fall)
+        assert!(start > 0.45, "Peak hold should capture the output peak, got {}", start);
+
+        let mut last = start;
+        for _ in 0..300 {
+            agc.process(&mut vec![0.0f32; 480]);
+            let held = agc.peak_hold();
+            assert!(held < last, "Peak hold should fall monotonically: {} -> {}", last, held);
+            last = held;
+        }
+        // 3 s at the default 1.5 s / 20 dB fall ≈ -40 dB
+        assert!(last < start * 0.02, "Peak hold should decay toward zero, got {}", last);
+
+        agc.reset_peak_hold();
+        assert_eq!(agc.peak_hold(), 0.0);
+    }
+
+    #[test]
+    fn test_envelope_follower_matches_inline_peak_logic() {
+        let signal: Vec<f32> = (0..48000).map(|i| {
+            let burst = if (i / 4800) % 2 == 0 { 0.3 } else { 0.002 };
+            burst * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin()
+        }).collect();
+
+        let mut follower = EnvelopeFollower::new(1.0, 1.0 - ENVELOPE_RELEASE);
+        let mut inline = 0.0f32;
+        for &s in &signal {
+            let abs = s.abs();
+            if abs > inline {
+                inline = abs;
+            } else {
+                inline *= ENVELOPE_RELEASE;
+            }
+            assert_eq!(follower.process_sample(abs), inline);
+        }
+    }
+
     #[test]
     fn test_output_never_exceeds_one() {
         let mut agc = AutoGainControl::new();
@@ -0,0 +1,171 @@
+// Benchmark bodies for `benches/pipeline.rs`
+//
+// Each case owns a processor and one second of input and, per run, copies
+// the input into a reused scratch buffer and processes it in capture-sized
+// blocks. Copying first means every run sees the same audio instead of
+// re-processing its own output. The copy is included in the timing: small
+// next to most stages, but a sizeable share of cheap ones like
+// pre-emphasis, so compare those against their own baseline only.
+// Processors keep their state between runs, as in a live stream.
+//
+// The bodies live in the crate (behind `test-utils`, like `test_signals`)
+// so a unit test can run each once without Criterion.
+
+use std::hint::black_box;
+
+use crate::audio_config::{FRAME_SAMPLES, SAMPLE_RATE};
+use crate::compressor::{NoiseGate, RmsNormalizer, SpeechCompressor, SystemAudioProcessor};
+use crate::convert::f32_to_i16;
+use crate::echo_cancel::{self, EchoCanceller};
+use crate::pre_emphasis::PreEmphasis;
+use crate::stage::AudioStage;
+use crate::test_signals::{make_pink_noise, make_white_noise};
+
+/// One second at the 48kHz pipeline rate
+const PIPELINE_SAMPLES: usize = 48_000;
+/// Capture block: 10ms at 48kHz
+const BLOCK_SAMPLES: usize = 480;
+/// Syllable rate of the speech-like envelope, in Hz
+const SYLLABLE_RATE: f32 = 3.0;
+/// Echo path gain from the reference into the mic signal
+const ECHO_GAIN: f32 = 0.4;
+
+/// One benchmarked stage: a processor bound to its input.
+pub struct BenchCase {
+    name: &'static str,
+    samples: usize,
+    run: Box<dyn FnMut()>,
+}
+
+impl BenchCase {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Samples processed per run, for per-sample throughput.
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Process the case's input once.
+    pub fn run(&mut self) {
+        (self.run)();
+    }
+}
+
+/// Pink noise under a half-wave syllable envelope over a faint noise bed:
+/// bursts with pauses between them and a speech-like crest factor, so the
+/// compressor, normalizer and gate all change state during a run.
+pub fn speech_like(sample_rate: f32, seed: u64, num_samples: usize) -> Vec<f32> {
+    let voice = make_pink_noise(0.5, seed, num_samples);
+    let bed = make_white_noise(0.002, seed + 1, num_samples);
+    voice.iter().zip(&bed).enumerate()
+        .map(|(i, (&v, &n))| {
+            let phase = 2.0 * std::f32::consts::PI * SYLLABLE_RATE * i as f32 / sample_rate;
+            v * phase.sin().max(0.0) + n
+        })
+        .collect()
+}
+
+/// A case for any in-place stage, fed in 10ms blocks.
+fn stage_case(name: &'static str, mut stage: impl AudioStage + 'static) -> BenchCase {
+    let input = speech_like(48_000.0, 7, PIPELINE_SAMPLES);
+    let mut scratch = input.clone();
+    BenchCase {
+        name,
+        samples: input.len(),
+        run: Box::new(move || {
+            scratch.copy_from_slice(&input);
+            for block in scratch.chunks_mut(BLOCK_SAMPLES) {
+                stage.process(block);
+            }
+            black_box(&scratch);
+        }),
+    }
+}
+
+/// Pre-emphasis then the full processor, as the capture loop runs them:
+/// two passes over each block, or fused into the compressor's pass.
+fn pre_emphasized_case(name: &'static str, fused: bool) -> BenchCase {
+    let (mut pre_emphasis, mut processor) = (PreEmphasis::new(), SystemAudioProcessor::new());
+    let input = speech_like(48_000.0, 7, PIPELINE_SAMPLES);
+    let mut scratch = input.clone();
+    BenchCase {
+        name,
+        samples: input.len(),
+        run: Box::new(move || {
+            scratch.copy_from_slice(&input);
+            for block in scratch.chunks_mut(BLOCK_SAMPLES) {
+                if fused {
+                    processor.process_pre_emphasized(&mut pre_emphasis, block);
+                } else {
+                    pre_emphasis.process(block);
+                    processor.process(block);
+                }
+            }
+            black_box(&scratch);
+        }),
+    }
+}
+
+/// The AEC at its own 16kHz rate over one second: each 20ms frame pushes
+/// its reference and cancels the matching mic frame (echo plus noise).
+/// Uses the process-global reference buffer, which it leaves empty.
+/// `None` if the canceller can't be created.
+fn aec_case() -> Option<BenchCase> {
+    let mut canceller = EchoCanceller::new()?;
+    let num_samples = SAMPLE_RATE as usize;
+    let far = speech_like(SAMPLE_RATE as f32, 11, num_samples);
+    let near = make_white_noise(0.01, 12, num_samples);
+    let mic: Vec<f32> = far.iter().zip(&near).map(|(&f, &n)| f * ECHO_GAIN + n).collect();
+    let (reference, mic) = (f32_to_i16(&far), f32_to_i16(&mic));
+    Some(BenchCase {
+        name: "echo_canceller",
+        samples: num_samples,
+        run: Box::new(move || {
+            echo_cancel::clear_reference();
+            for (ref_frame, mic_frame) in reference.chunks(FRAME_SAMPLES).zip(mic.chunks(FRAME_SAMPLES)) {
+                // The buffer is drained every frame, so this can't overflow
+                let _ = echo_cancel::push_reference(ref_frame);
+                black_box(canceller.process(mic_frame));
+            }
+        }),
+    })
+}
+
+/// Every benchmarked stage, each with its own processor and input. The
+/// AEC case is left out if the canceller fails to initialize.
+pub fn pipeline_cases() -> Vec<BenchCase> {
+    let mut cases = vec![
+        stage_case("speech_compressor", SpeechCompressor::new()),
+        stage_case("rms_normalizer", RmsNormalizer::new()),
+        stage_case("noise_gate", NoiseGate::new()),
+        stage_case("system_audio_processor", SystemAudioProcessor::new()),
+        stage_case("pre_emphasis", PreEmphasis::new()),
+        pre_emphasized_case("pre_emphasis_then_processor", false),
+        pre_emphasized_case("pre_emphasis_fused_processor", true),
+    ];
+    cases.extend(aec_case());
+    cases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::crest_factor;
+
+    #[test]
+    fn test_bench_bodies_run_once() {
+        let _guard = echo_cancel::lock_reference();
+        let mut cases = pipeline_cases();
+        assert!(cases.len() >= 7);
+        for case in cases.iter_mut() {
+            assert!(case.samples() > 0, "{} has no input", case.name());
+            case.run();
+        }
+
+        // Bursty enough to exercise level-dependent stages
+        let input = speech_like(48_000.0, 7, PIPELINE_SAMPLES);
+        assert!(crest_factor(&input) > 4.0, "crest {:.1}", crest_factor(&input));
+    }
+}
@@ -0,0 +1,142 @@
+// Second-order IIR (biquad) filter
+//
+// Transposed direct form II, coefficients from the RBJ Audio EQ Cookbook.
+// Used for crossovers and fixed tone-shaping; all coefficients are computed
+// once at construction, so the per-sample cost is 5 multiplies.
+
+use std::f32::consts::PI;
+
+use crate::stage::AudioStage;
+
+/// Q of a 2nd-order Butterworth section
+pub const BUTTERWORTH_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+#[derive(Clone, Debug)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    /// Filter state (TDF-II)
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    /// Build from normalised coefficients (a0 already divided out).
+    pub fn from_coefficients(b0: f32, b1: f32, b2: f32, a1: f32, a2: f32) -> Self {
+        Self { b0, b1, b2, a1, a2, z1: 0.0, z2: 0.0 }
+    }
+
+    pub fn lowpass(cutoff_hz: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prewarp(cutoff_hz, q, sample_rate);
+        let a0 = 1.0 + alpha;
+        Self::from_coefficients(
+            (1.0 - cos_w) / 2.0 / a0,
+            (1.0 - cos_w) / a0,
+            (1.0 - cos_w) / 2.0 / a0,
+            -2.0 * cos_w / a0,
+            (1.0 - alpha) / a0,
+        )
+    }
+
+    pub fn highpass(cutoff_hz: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prewarp(cutoff_hz, q, sample_rate);
+        let a0 = 1.0 + alpha;
+        Self::from_coefficients(
+            (1.0 + cos_w) / 2.0 / a0,
+            -(1.0 + cos_w) / a0,
+            (1.0 + cos_w) / 2.0 / a0,
+            -2.0 * cos_w / a0,
+            (1.0 - alpha) / a0,
+        )
+    }
+
+    /// High shelf with slope S = 1: flat at DC, `gain_db` above the corner,
+    /// half the gain (in dB) at `corner_hz`.
+    pub fn high_shelf(corner_hz: f32, gain_db: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prewarp(corner_hz, BUTTERWORTH_Q, sample_rate);
+        let a = 10.0f32.powf(gain_db / 40.0);
+        let two_sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+        let a0 = (a + 1.0) - (a - 1.0) * cos_w + two_sqrt_a_alpha;
+        Self::from_coefficients(
+            a * ((a + 1.0) + (a - 1.0) * cos_w + two_sqrt_a_alpha) / a0,
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_w) / a0,
+            a * ((a + 1.0) + (a - 1.0) * cos_w - two_sqrt_a_alpha) / a0,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos_w) / a0,
+            ((a + 1.0) - (a - 1.0) * cos_w - two_sqrt_a_alpha) / a0,
+        )
+    }
+
+    fn prewarp(cutoff_hz: f32, q: f32, sample_rate: f32) -> (f32, f32) {
+        let w0 = 2.0 * PI * cutoff_hz.clamp(1.0, sample_rate * 0.49) / sample_rate;
+        (w0.cos(), w0.sin() / (2.0 * q))
+    }
+
+    #[inline]
+    pub fn process_sample(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            *sample = self.process_sample(*sample);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+impl AudioStage for Biquad {
+    fn process(&mut self, samples: &mut [f32]) {
+        Biquad::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        Biquad::reset(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{make_sine, rms};
+
+    fn gain_at(mut filter: Biquad, freq: f32) -> f32 {
+        let mut signal = make_sine(freq, 0.5, 48000.0, 9600);
+        let input_rms = rms(&signal[4800..]);
+        filter.process(&mut signal);
+        rms(&signal[4800..]) / input_rms
+    }
+
+    #[test]
+    fn test_lowpass_response() {
+        assert!((gain_at(Biquad::lowpass(1000.0, BUTTERWORTH_Q, 48000.0), 100.0) - 1.0).abs() < 0.02);
+        // -3dB at cutoff
+        assert!((gain_at(Biquad::lowpass(1000.0, BUTTERWORTH_Q, 48000.0), 1000.0) - 0.707).abs() < 0.02);
+        assert!(gain_at(Biquad::lowpass(1000.0, BUTTERWORTH_Q, 48000.0), 10000.0) < 0.02);
+    }
+
+    #[test]
+    fn test_highpass_response() {
+        assert!(gain_at(Biquad::highpass(1000.0, BUTTERWORTH_Q, 48000.0), 100.0) < 0.02);
+        assert!((gain_at(Biquad::highpass(1000.0, BUTTERWORTH_Q, 48000.0), 10000.0) - 1.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_high_shelf_response() {
+        let db = |filter: Biquad, freq: f32| 20.0 * gain_at(filter, freq).log10();
+        assert!(db(Biquad::high_shelf(1000.0, 6.0, 48000.0), 50.0).abs() < 0.2);
+        // Half the shelf gain at the corner
+        assert!((db(Biquad::high_shelf(1000.0, 6.0, 48000.0), 1000.0) - 3.0).abs() < 0.2);
+        assert!((db(Biquad::high_shelf(1000.0, 6.0, 48000.0), 12000.0) - 6.0).abs() < 0.2);
+        assert!((db(Biquad::high_shelf(1000.0, -6.0, 48000.0), 12000.0) + 6.0).abs() < 0.2);
+    }
+}
@@ -5,97 +5,917 @@
 //   - Low average volume (mean -31.9 dB, Parakeet expects ~-16 dB)
 //   - High crest factor (24.4 — peaks 24x above average)
 //
-// Pipeline: SpeechCompressor → RmsNormalizer → NoiseGate
+// Pipeline: SpeechCompressor → [Saturator] → RmsNormalizer → NoiseGate
+// (default order; see `SystemAudioProcessor::set_order`)
 // All sample-by-sample or per-batch. Zero added latency.
 
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "timing")]
+use std::time::{Duration, Instant};
+
+use anyhow::{ensure, Result};
+
+use crate::biquad::{Biquad, BUTTERWORTH_Q};
+use crate::input_trim::InputTrim;
+use crate::levels::{rms, Detection, NoiseFloor, Sample, SlidingRms, SubWindowRms};
+use crate::loudness::LufsMeter;
+use crate::mid_side::{apply_gain_ramp, MidSideBuffers, SIDE_COMPRESSION_RATIO};
+use crate::pre_emphasis::PreEmphasis;
+use crate::resampler::Resampler;
+use crate::rng::Pcg32;
+use crate::saturator::Saturator;
+use crate::stage::AudioStage;
+use crate::weighting::AWeighting;
+
 // ============================================================================
 // SpeechCompressor — RMS-sidechain, reduces crest factor from ~24 to ~6-8
 // ============================================================================
 
-/// 10ms RMS window at 48kHz
+/// Default 10ms RMS window at 48kHz
 const RMS_WINDOW: usize = 480;
+/// Shortest allowed RMS window: below this the "RMS" is just the waveform
+const MIN_RMS_WINDOW: usize = 8;
+/// Longest allowed RMS window in ms: 1s already averages over several
+/// words, and the window's history is allocated up front
+const MAX_RMS_WINDOW_MS: f32 = 1000.0;
 /// Threshold in linear (~-20 dBFS)
 const COMP_THRESHOLD: f32 = 0.1;
 /// 4:1 compression ratio
 const COMP_RATIO: f32 = 4.0;
 /// Soft knee width in dB
 const KNEE_DB: f32 = 6.0;
+/// Adaptive knee: width approached at high ratios, in dB
+const ADAPTIVE_KNEE_MAX_DB: f32 = 12.0;
 /// Attack coefficient: ~1ms at 48kHz (per-sample smoothing)
 /// alpha = 1 - exp(-1 / (sample_rate * time_s)) ≈ 1 - exp(-1/48) ≈ 0.021
 const ATTACK_COEFF: f32 = 0.02;
 /// Release coefficient: ~50ms at 48kHz
 /// alpha = 1 - exp(-1 / (48000 * 0.05)) ≈ 0.00042
 const RELEASE_COEFF: f32 = 0.00042;
+/// Adaptive release: how long compression must be sustained before the
+/// release reaches its slowest setting (1s at 48kHz)
+const ADAPTIVE_SUSTAIN_SAMPLES: usize = 48_000;
+/// Adaptive release multiplier on `RELEASE_COEFF` after a brief transient
+/// (~25ms release)
+const ADAPTIVE_FAST_SCALE: f32 = 2.0;
+/// Adaptive release multiplier after sustained compression (~400ms release)
+const ADAPTIVE_SLOW_SCALE: f32 = 0.125;
+/// Samples per millisecond at the 48kHz capture rate
+const SAMPLES_PER_MS: f32 = 48.0;
+/// Parallel-mode level matching: ~500ms power average of each path
+/// alpha ≈ 1 / (48000 * 0.5) ≈ 0.00004
+const PARALLEL_MATCH_COEFF: f32 = 0.00004;
+/// Default gain-reduction history length: 3s of 10ms blocks
+const GR_HISTORY_BLOCKS: usize = 300;
+/// Crest-target mode: output crest factor is measured over 1s windows,
+/// long enough to span several syllables and the pauses between them
+const CREST_WINDOW: usize = 48_000;
+/// Crest-target mode: upper bound on the adapted ratio
+const CREST_MAX_RATIO: f32 = 20.0;
+/// Crest-target mode: fraction of the crest error (in dB) the ratio corrects
+/// per window. Below 1 so a single odd window can't swing the ratio.
+const CREST_ADAPT_RATE: f32 = 0.5;
+/// Live parameter changes (threshold, ratio, makeup, normalizer target)
+/// are spread over 20ms at 48kHz instead of stepping the gain (a click)
+const PARAM_RAMP_SAMPLES: usize = 960;
+
+/// Validate an RMS window length in ms and convert it to samples.
+fn rms_window_samples(window_ms: f32) -> Result<usize> {
+    ensure!(window_ms.is_finite() && window_ms <= MAX_RMS_WINDOW_MS,
+        "RMS window must be at most {}ms, got {}ms", MAX_RMS_WINDOW_MS, window_ms);
+    let samples = (window_ms * SAMPLES_PER_MS).round();
+    ensure!(samples >= MIN_RMS_WINDOW as f32,
+        "RMS window must be at least {} samples ({:.2}ms), got {}ms",
+        MIN_RMS_WINDOW, MIN_RMS_WINDOW as f32 / SAMPLES_PER_MS, window_ms);
+    Ok(samples as usize)
+}
+
+/// f32 stored as its bit pattern so it can be shared without a lock
+struct AtomicF32(AtomicU32);
+
+impl AtomicF32 {
+    fn new(value: f32) -> Self {
+        Self(AtomicU32::new(value.to_bits()))
+    }
+
+    fn load(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    fn store(&self, value: f32) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// A parameter that moves linearly to a new target over
+/// `PARAM_RAMP_SAMPLES`, advanced once per sample
+struct ParamRamp {
+    value: f32,
+    target: f32,
+    step: f32,
+    remaining: usize,
+}
+
+impl ParamRamp {
+    fn new(value: f32) -> Self {
+        Self { value, target: value, step: 0.0, remaining: 0 }
+    }
+
+    /// Start a ramp from the current value, unless `target` is already
+    /// where it's heading.
+    fn set_target(&mut self, target: f32) {
+        if target == self.target {
+            return;
+        }
+        self.target = target;
+        self.step = (target - self.value) / PARAM_RAMP_SAMPLES as f32;
+        self.remaining = PARAM_RAMP_SAMPLES;
+    }
+
+    /// Jump straight to the target, e.g. on reset.
+    fn snap(&mut self) {
+        self.value = self.target;
+        self.remaining = 0;
+    }
+
+    /// Advance one sample and return the value. The last step lands
+    /// exactly on the target.
+    #[inline]
+    fn advance(&mut self) -> f32 {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            self.value = if self.remaining == 0 { self.target } else { self.value + self.step };
+        }
+        self.value
+    }
+
+    fn value(&self) -> f32 {
+        self.value
+    }
+
+    fn target(&self) -> f32 {
+        self.target
+    }
+}
+
+struct SharedCompressorParams {
+    threshold_db: AtomicF32,
+    ratio: AtomicF32,
+    makeup_db: AtomicF32,
+}
+
+/// Live-tunable compressor parameters, shareable across threads.
+///
+/// Clone the handle (via `SpeechCompressor::params`) into a settings/UI
+/// thread and write with the setters; the audio thread picks up new values
+/// at the start of each block with `Relaxed` loads — no mutex on the hot path.
+/// New values are ramped in over 20ms so a change mid-stream doesn't click.
+/// Out-of-range values are clamped (threshold ≤ 0 dBFS, ratio ≥ 1).
+#[derive(Clone)]
+pub struct CompressorParams {
+    shared: Arc<SharedCompressorParams>,
+}
+
+impl CompressorParams {
+    pub fn new() -> Self {
+        Self {
+            shared: Arc::new(SharedCompressorParams {
+                threshold_db: AtomicF32::new(20.0 * COMP_THRESHOLD.log10()),
+                ratio: AtomicF32::new(COMP_RATIO),
+                makeup_db: AtomicF32::new(0.0),
+            }),
+        }
+    }
+
+    pub fn set_threshold(&self, threshold_db: f32) {
+        self.shared.threshold_db.store(threshold_db.min(0.0));
+    }
+
+    pub fn threshold(&self) -> f32 {
+        self.shared.threshold_db.load()
+    }
+
+    pub fn set_ratio(&self, ratio: f32) {
+        self.shared.ratio.store(ratio.max(1.0));
+    }
+
+    pub fn ratio(&self) -> f32 {
+        self.shared.ratio.load()
+    }
+
+    pub fn set_makeup_db(&self, makeup_db: f32) {
+        self.shared.makeup_db.store(makeup_db);
+    }
+
+    pub fn makeup_db(&self) -> f32 {
+        self.shared.makeup_db.load()
+    }
+}
+
+impl Default for CompressorParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An upstream stage's current gain, published for a downstream one.
+///
+/// `AutoGainControl::gain_link` hands one out; giving a clone to
+/// `RmsNormalizer::set_upstream_gain` (or the processor's) makes the
+/// normalizer own the combined gain's recovery instead of fighting the AGC
+/// over it. Lock-free like `CompressorParams`, so the two can run on
+/// different threads.
+#[derive(Clone)]
+pub struct GainLink {
+    gain: Arc<AtomicF32>,
+}
+
+impl GainLink {
+    pub fn new(gain: f32) -> Self {
+        Self { gain: Arc::new(AtomicF32::new(gain)) }
+    }
+
+    pub fn set(&self, gain: f32) {
+        self.gain.store(gain);
+    }
+
+    pub fn get(&self) -> f32 {
+        self.gain.load()
+    }
+}
 
-pub struct SpeechCompressor {
-    /// Circular buffer for RMS computation
-    rms_buffer: [f32; RMS_WINDOW],
-    rms_index: usize,
-    rms_sum: f32,
+/// Where the compressor's level detector listens.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompressorTopology {
+    /// Detect the input: the configured ratio is what you get
+    #[default]
+    FeedForward,
+    /// Detect the gained output of the previous sample, as in classic
+    /// analog designs: the gain eases in as the output rises, so the
+    /// response is gentler and more program-dependent. A ratio `r` acts
+    /// like `2 - 1/r` (1.75:1 at 4:1) and never exceeds 2:1, so it suits
+    /// light leveling rather than taming speech peaks.
+    FeedBack,
+}
+
+/// Shape of the compressor's transition around the threshold.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KneeShape {
+    /// Full ratio from the threshold up: a more aggressive, audible onset
+    Hard,
+    /// Quadratic knee of the given width in dB, centred on the threshold
+    Soft(f32),
+    /// Soft knee whose width grows with the ratio, `12 · (1 − 1/ratio)` dB
+    /// (9 dB at 4:1), so heavy ratios ease in more gently
+    Adaptive,
+}
+
+impl Default for KneeShape {
+    fn default() -> Self {
+        KneeShape::Soft(KNEE_DB)
+    }
+}
+
+impl KneeShape {
+    /// Knee width in dB for `ratio` (0 = hard)
+    fn width_db(self, ratio: f32) -> f32 {
+        match self {
+            KneeShape::Hard => 0.0,
+            KneeShape::Soft(width_db) => width_db,
+            KneeShape::Adaptive => ADAPTIVE_KNEE_MAX_DB * (1.0 - 1.0 / ratio),
+        }
+    }
+}
+
+/// Generic over the sample type: `SpeechCompressor<f64>` runs the detector
+/// and the audio path in double precision for offline processing (the gain
+/// computer stays f32 either way).
+pub struct SpeechCompressor<T: Sample = f32> {
+    /// Sidechain level detector
+    rms: SlidingRms<T>,
+    /// Sub-window detector replacing `rms` for `Median`/`Max` detection
+    sub_rms: Option<SubWindowRms<T>>,
+    detection: Detection,
+    sub_windows: usize,
+    /// Optional high-pass on the detector input (never the output)
+    sidechain_highpass: Option<Biquad>,
+    topology: CompressorTopology,
+    /// Previous sample after gain reduction (makeup excluded), the
+    /// detector input in `FeedBack` topology
+    feedback_sample: T,
     /// Smoothed gain envelope
     gain_smooth: f32,
+    /// Slow power averages of the dry and compressed paths, used to
+    /// level-match the wet path in `process_parallel`
+    dry_power: f32,
+    wet_power: f32,
+    /// Optional slew limit on the gain envelope, in dB per sample
+    max_gain_step_db: Option<f32>,
+    /// Program-dependent release (see `set_adaptive_release`)
+    adaptive_release: bool,
+    /// Samples spent above the knee, capped at `ADAPTIVE_SUSTAIN_SAMPLES`;
+    /// counts back down while below it
+    sustain_samples: usize,
+    /// When set, `process` only tracks the input
+    bypass: bool,
+    /// Shared tunables (threshold, ratio, manual makeup)
+    params: CompressorParams,
+    /// Threshold in effect, ramping towards `params.threshold()`
+    threshold_db: ParamRamp,
+    /// Ratio in effect, ramping towards `params.ratio()` (or the adapted
+    /// ratio in crest-target mode)
+    ratio: ParamRamp,
+    /// Derive makeup from threshold/ratio instead of `params.makeup_db()`
+    auto_makeup: bool,
+    /// Effective linear makeup gain applied after gain reduction
+    makeup_gain: ParamRamp,
+    /// Per-block gain reduction in dB, oldest first (`None` = not recording)
+    gr_history: Option<VecDeque<f32>>,
+    gr_history_capacity: usize,
+    /// Crest factor the ratio adapts towards (`None` = fixed ratio)
+    crest_target: Option<f32>,
+    /// Adapted ratio in crest-target mode; replaces `params.ratio()`
+    crest_ratio: f32,
+    /// Output peak and energy over the current crest window
+    crest_peak: f32,
+    crest_energy: f32,
+    crest_count: usize,
+    /// Mean gain reduction of the last `process_frame` block, in dB
+    last_gain_reduction_db: f32,
+    knee: KneeShape,
 }
 
 impl SpeechCompressor {
     pub fn new() -> Self {
-        Self {
-            rms_buffer: [0.0; RMS_WINDOW],
-            rms_index: 0,
-            rms_sum: 0.0,
+        Self::with_params(CompressorParams::new())
+    }
+
+    /// Create a compressor driven by an existing parameter handle, e.g. to
+    /// tune several compressors from one control.
+    pub fn with_params(params: CompressorParams) -> Self {
+        Self::build(params)
+    }
+
+    /// Create a compressor with the given detector topology (the default
+    /// is `FeedForward`).
+    pub fn with_topology(topology: CompressorTopology) -> Self {
+        Self { topology, ..Self::new() }
+    }
+
+    /// Pre-emphasis followed by compression in one pass over `samples`,
+    /// for the capture hot path: each sample is filtered and compressed
+    /// while it's in a register instead of the buffer being walked twice.
+    /// Output is bit-identical to `pre_emphasis.process` then `process`.
+    pub fn process_pre_emphasized(&mut self, pre_emphasis: &mut PreEmphasis, samples: &mut [f32]) {
+        if self.bypass {
+            pre_emphasis.process(samples);
+            self.track(samples);
+            self.last_gain_reduction_db = 0.0;
+            return;
+        }
+        self.process_frame_with(samples, |sample| pre_emphasis.process_sample(sample));
+    }
+}
+
+impl SpeechCompressor<f64> {
+    /// Double-precision compressor for offline processing of long
+    /// recordings.
+    pub fn new_f64() -> Self {
+        Self::build(CompressorParams::new())
+    }
+}
+
+impl<T: Sample> SpeechCompressor<T> {
+    fn build(params: CompressorParams) -> Self {
+        let mut compressor = Self {
+            rms: SlidingRms::new(RMS_WINDOW),
+            sub_rms: None,
+            detection: Detection::Mean,
+            sub_windows: 1,
+            sidechain_highpass: None,
+            topology: CompressorTopology::default(),
+            feedback_sample: T::zero(),
             gain_smooth: 1.0,
+            dry_power: 0.0,
+            wet_power: 0.0,
+            max_gain_step_db: None,
+            adaptive_release: false,
+            sustain_samples: 0,
+            bypass: false,
+            params,
+            threshold_db: ParamRamp::new(0.0),
+            ratio: ParamRamp::new(1.0),
+            auto_makeup: false,
+            makeup_gain: ParamRamp::new(1.0),
+            gr_history: None,
+            gr_history_capacity: 0,
+            crest_target: None,
+            crest_ratio: 1.0,
+            crest_peak: 0.0,
+            crest_energy: 0.0,
+            crest_count: 0,
+            last_gain_reduction_db: 0.0,
+            knee: KneeShape::default(),
+        };
+        compressor.sync_params();
+        compressor.snap_params();
+        compressor
+    }
+
+    /// Handle for changing threshold/ratio/makeup from another thread.
+    pub fn params(&self) -> CompressorParams {
+        self.params.clone()
+    }
+
+    /// Makeup gain in dB applied after gain reduction, for standalone use
+    /// without the downstream normalizer. Default 0.0. Ignored while
+    /// auto-makeup is on.
+    pub fn set_makeup_db(&mut self, makeup_db: f32) {
+        self.params.set_makeup_db(makeup_db);
+        self.sync_params();
+    }
+
+    /// Derive makeup from the threshold and ratio: half the gain reduction
+    /// a full-scale signal would get, i.e. `-threshold * (1 - 1/ratio) / 2`.
+    /// Material sitting halfway between threshold and 0 dBFS comes out at
+    /// its input level. Follows later threshold/ratio changes.
+    pub fn set_auto_makeup(&mut self, enabled: bool) {
+        self.auto_makeup = enabled;
+        self.sync_params();
+    }
+
+    /// Compression threshold in dBFS (default -20). Must be ≤ 0.
+    pub fn set_threshold_db(&mut self, threshold_db: f32) -> Result<()> {
+        ensure!(threshold_db <= 0.0, "threshold must be <= 0 dBFS, got {}", threshold_db);
+        self.params.set_threshold(threshold_db);
+        self.sync_params();
+        Ok(())
+    }
+
+    /// Compression ratio (default 4:1). Must be ≥ 1.
+    pub fn set_ratio(&mut self, ratio: f32) -> Result<()> {
+        ensure!(ratio >= 1.0, "ratio must be >= 1, got {}", ratio);
+        self.params.set_ratio(ratio);
+        self.sync_params();
+        Ok(())
+    }
+
+    /// Adapt the ratio to hold the output crest factor (peak / RMS) near
+    /// `target` instead of using a fixed ratio, so codecs with different
+    /// dynamics need no manual tuning (speech typically lands at 6-8).
+    ///
+    /// The crest factor is measured on the compressed output over 1s
+    /// windows; after each window the ratio moves towards
+    /// `ratio · (measured_db / target_db)`, within 1:1..20:1. This is a
+    /// feedback loop on the output, not a mapping from the input crest
+    /// factor: how much a given ratio flattens the input depends on the
+    /// threshold, knee and release, so only the measured result tells
+    /// whether the target was hit. Material already flatter than the
+    /// target drifts to 1:1 and is left alone.
+    /// Starts from the configured ratio; `None` returns to it. Must be > 1.
+    pub fn set_target_crest(&mut self, target: Option<f32>) -> Result<()> {
+        if let Some(crest) = target {
+            ensure!(crest.is_finite() && crest > 1.0, "crest target must be > 1, got {}", crest);
+        }
+        self.crest_target = target;
+        self.reset_crest();
+        self.sync_params();
+        Ok(())
+    }
+
+    /// Ratio currently in effect: the configured one, or the adapted one in
+    /// crest-target mode (mid-ramp right after a change).
+    pub fn effective_ratio(&self) -> f32 {
+        self.ratio.value()
+    }
+
+    fn reset_crest(&mut self) {
+        self.crest_ratio = self.params.ratio();
+        self.crest_peak = 0.0;
+        self.crest_energy = 0.0;
+        self.crest_count = 0;
+    }
+
+    /// Accumulate one output sample into the crest window and adapt the
+    /// ratio when the window completes.
+    fn observe_crest(&mut self, output: f32, target: f32) {
+        self.crest_peak = self.crest_peak.max(output.abs());
+        self.crest_energy += output * output;
+        self.crest_count += 1;
+        if self.crest_count < CREST_WINDOW {
+            return;
+        }
+
+        let rms = (self.crest_energy / CREST_WINDOW as f32).sqrt();
+        // Skip silent windows: their crest factor is meaningless
+        if rms > 1e-6 {
+            let measured_db = 20.0 * (self.crest_peak / rms).log10();
+            let target_db = 20.0 * target.log10();
+            let step = (measured_db.max(1e-3) / target_db).powf(CREST_ADAPT_RATE);
+            self.crest_ratio = (self.crest_ratio * step).clamp(1.0, CREST_MAX_RATIO);
+            self.ratio.set_target(self.crest_ratio);
+        }
+        self.crest_peak = 0.0;
+        self.crest_energy = 0.0;
+        self.crest_count = 0;
+    }
+
+    /// Pick up the shared parameters as ramp targets for the next block and
+    /// refresh the derived makeup gain. Called once per block.
+    fn sync_params(&mut self) {
+        let threshold_db = self.params.threshold();
+        let ratio = if self.crest_target.is_some() {
+            self.crest_ratio
+        } else {
+            self.params.ratio()
+        };
+        let makeup_db = if self.auto_makeup {
+            -threshold_db * (1.0 - 1.0 / ratio) / 2.0
+        } else {
+            self.params.makeup_db()
+        };
+        self.threshold_db.set_target(threshold_db);
+        self.ratio.set_target(ratio);
+        self.makeup_gain.set_target(10.0f32.powf(makeup_db / 20.0));
+    }
+
+    /// Apply the parameter targets at once instead of ramping.
+    fn snap_params(&mut self) {
+        self.threshold_db.snap();
+        self.ratio.snap();
+        self.makeup_gain.snap();
+    }
+
+    /// Pass audio through untouched while keeping the detector and gain
+    /// envelope running, so un-bypassing is seamless.
+    pub fn set_bypass(&mut self, bypass: bool) {
+        self.bypass = bypass;
+    }
+
+    /// Program-dependent release, approximating an optical compressor:
+    /// release is fast after brief transients and lengthens (up to ~400ms)
+    /// the longer the signal has been held in compression. Reduces pumping
+    /// on speech with rapid level changes. Off by default (fixed ~50ms).
+    pub fn set_adaptive_release(&mut self, enabled: bool) {
+        self.adaptive_release = enabled;
+        self.sustain_samples = 0;
+    }
+
+    /// Start recording per-block gain reduction for metering, keeping the
+    /// last ~3s of 10ms blocks. Off by default so the audio path never
+    /// allocates unless asked to.
+    pub fn enable_gain_reduction_history(&mut self) {
+        self.set_gain_reduction_history_capacity(GR_HISTORY_BLOCKS);
+    }
+
+    /// Record up to `blocks` of the most recent blocks (one entry per
+    /// `process_frame` call). 0 stops recording and frees the buffer.
+    pub fn set_gain_reduction_history_capacity(&mut self, blocks: usize) {
+        self.gr_history_capacity = blocks;
+        if blocks == 0 {
+            self.gr_history = None;
+            return;
+        }
+        let history = self.gr_history.get_or_insert_with(VecDeque::new);
+        while history.len() > blocks {
+            history.pop_front();
+        }
+        history.reserve_exact(blocks - history.len());
+    }
+
+    /// Recorded gain reduction per block in dB (positive = quieter, makeup
+    /// excluded), oldest first. Empty unless recording is enabled.
+    pub fn gain_reduction_history(&self) -> Vec<f32> {
+        self.gr_history
+            .as_ref()
+            .map(|history| history.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// `gain_reduction_history` without the copy, for a meter redrawn every
+    /// frame: iterates the recorded values oldest first.
+    pub fn gain_reduction_history_iter(&self) -> impl Iterator<Item = f32> + '_ {
+        self.gr_history.iter().flatten().copied()
+    }
+
+    /// Limit how fast the gain may change, in dB per millisecond
+    /// (e.g. `Some(0.5)`). Overrides the attack/release smoothing when a
+    /// step would be larger, so a loud transient after silence ramps down
+    /// instead of stepping. Must be finite and > 0; `None` disables the
+    /// limit.
+    pub fn set_slew_limit(&mut self, db_per_ms: Option<f32>) -> Result<()> {
+        if let Some(db) = db_per_ms {
+            ensure!(db.is_finite() && db > 0.0, "slew limit must be > 0 dB/ms, got {}", db);
+        }
+        self.max_gain_step_db = db_per_ms.map(|db| db / SAMPLES_PER_MS);
+        Ok(())
+    }
+
+    /// Transition around the threshold (default `Soft(6.0)`). Soft widths
+    /// must be finite and ≥ 0; `Soft(0.0)` is the same as `Hard`.
+    pub fn set_knee(&mut self, knee: KneeShape) -> Result<()> {
+        if let KneeShape::Soft(width_db) = knee {
+            ensure!(width_db.is_finite() && width_db >= 0.0, "knee width must be >= 0 dB, got {}", width_db);
+        }
+        self.knee = knee;
+        Ok(())
+    }
+
+    pub fn knee(&self) -> KneeShape {
+        self.knee
+    }
+
+    /// How the detector reads its window (default `Mean`). `Median` and
+    /// `Max` split the RMS window into `sub_windows` sub-blocks and take
+    /// the median or loudest of their levels, updated per sub-block:
+    /// median detection stops isolated clicks on phone audio from pulling
+    /// the gain down for the whole window. `sub_windows` is ignored for
+    /// `Mean`; otherwise it must be between 2 and the window length.
+    /// Clears the detector.
+    pub fn set_detection(&mut self, detection: Detection, sub_windows: usize) -> Result<()> {
+        if detection != Detection::Mean {
+            ensure!((2..=self.rms.window()).contains(&sub_windows),
+                "sub-window count must be between 2 and {}, got {}", self.rms.window(), sub_windows);
+        }
+        self.detection = detection;
+        self.sub_windows = sub_windows;
+        self.rms.reset();
+        self.sub_rms = self.sub_window_detector();
+        Ok(())
+    }
+
+    fn sub_window_detector(&self) -> Option<SubWindowRms<T>> {
+        (self.detection != Detection::Mean)
+            .then(|| SubWindowRms::new(self.rms.window(), self.sub_windows, self.detection))
+    }
+
+    /// High-pass the signal feeding the RMS detector at `corner_hz`
+    /// (2nd-order Butterworth), so bass and rumble don't drive the gain
+    /// reduction. Detection only; the full-band audio is still what gets
+    /// compressed. `None` (the default) turns it off. The corner must be
+    /// between 0 and 24 kHz. The filter runs in f32 at any sample type.
+    pub fn set_sidechain_highpass(&mut self, corner_hz: Option<f32>) -> Result<()> {
+        if let Some(hz) = corner_hz {
+            ensure!(hz > 0.0 && hz < PIPELINE_RATE as f32 / 2.0,
+                "sidechain corner must be between 0 and 24000 Hz, got {}", hz);
         }
+        self.sidechain_highpass = corner_hz.map(|hz| Biquad::highpass(hz, BUTTERWORTH_Q, PIPELINE_RATE as f32));
+        Ok(())
     }
 
-    /// Compute gain reduction in dB for a given input level in dB,
-    /// with soft-knee transition around threshold.
-    fn compute_gain_db(input_db: f32) -> f32 {
-        let thresh_db = 20.0 * COMP_THRESHOLD.log10(); // ~-20 dB
-        let half_knee = KNEE_DB / 2.0;
+    /// Gain curve for an arbitrary threshold, ratio and knee width
+    /// (0 = hard knee).
+    fn knee_gain_db(input_db: f32, thresh_db: f32, ratio: f32, knee_db: f32) -> f32 {
+        let half_knee = knee_db / 2.0;
 
-        if input_db < thresh_db - half_knee {
+        if input_db < thresh_db - half_knee || (knee_db <= 0.0 && input_db <= thresh_db) {
             // Below knee: no compression
             0.0
-        } else if input_db > thresh_db + half_knee {
+        } else if input_db >= thresh_db + half_knee {
             // Above knee: full ratio compression
-            (thresh_db + (input_db - thresh_db) / COMP_RATIO) - input_db
+            (thresh_db + (input_db - thresh_db) / ratio) - input_db
         } else {
             // In knee: quadratic interpolation
             let x = input_db - thresh_db + half_knee;
-            let gain_reduction = (1.0 / COMP_RATIO - 1.0) * x * x / (2.0 * KNEE_DB);
-            gain_reduction
+            (1.0 / ratio - 1.0) * x * x / (2.0 * knee_db)
         }
     }
 
-    pub fn process(&mut self, samples: &mut [f32]) {
+    /// Advance the detector by one input sample and return the gain to apply.
+    fn next_gain(&mut self, input: T) -> f32 {
+        let detector_input = match self.topology {
+            CompressorTopology::FeedForward => input,
+            CompressorTopology::FeedBack => self.feedback_sample,
+        };
+        let detected = match self.sidechain_highpass.as_mut() {
+            Some(highpass) => highpass.process_sample(detector_input.as_()).into(),
+            None => detector_input,
+        };
+        let rms = match self.sub_rms.as_mut() {
+            Some(sub_rms) => sub_rms.push(detected),
+            None => self.rms.push(detected),
+        };
+        let rms = rms.as_().max(1e-10);
+        let input_db = 20.0 * rms.log10();
+
+        let threshold_db = self.threshold_db.advance();
+        let ratio = self.ratio.advance();
+        self.makeup_gain.advance();
+
+        // Desired gain in dB from compressor curve
+        let knee_db = self.knee.width_db(ratio);
+        let gain_db = Self::knee_gain_db(input_db, threshold_db, ratio, knee_db);
+        let desired_gain = 10.0f32.powf(gain_db / 20.0);
+
+        if self.adaptive_release {
+            if gain_db < 0.0 {
+                self.sustain_samples = (self.sustain_samples + 1).min(ADAPTIVE_SUSTAIN_SAMPLES);
+            } else {
+                self.sustain_samples = self.sustain_samples.saturating_sub(1);
+            }
+        }
+
+        // Smooth gain with attack/release
+        let coeff = if desired_gain < self.gain_smooth {
+            ATTACK_COEFF // fast attack for transients
+        } else if self.adaptive_release {
+            // Longer sustained compression → slower release
+            let sustain = self.sustain_samples as f32 / ADAPTIVE_SUSTAIN_SAMPLES as f32;
+            RELEASE_COEFF * (ADAPTIVE_FAST_SCALE + (ADAPTIVE_SLOW_SCALE - ADAPTIVE_FAST_SCALE) * sustain)
+        } else {
+            RELEASE_COEFF // slow release for smooth recovery
+        };
+        let smoothed = self.gain_smooth + coeff * (desired_gain - self.gain_smooth);
+
+        self.gain_smooth = match self.max_gain_step_db {
+            Some(max_step) => {
+                let step_db = 20.0 * (smoothed / self.gain_smooth).log10();
+                if step_db.abs() > max_step {
+                    self.gain_smooth * 10.0f32.powf(max_step.copysign(step_db) / 20.0)
+                } else {
+                    smoothed
+                }
+            }
+            None => smoothed,
+        };
+        if let Some(target) = self.crest_target {
+            self.observe_crest(input.as_() * self.gain_smooth, target);
+        }
+        if self.topology == CompressorTopology::FeedBack {
+            self.feedback_sample = input * self.gain_smooth.into();
+        }
+        self.gain_smooth
+    }
+
+    pub fn process(&mut self, samples: &mut [T]) {
+        self.process_frame(samples);
+    }
+
+    /// Process in-place and return the mean gain applied over the frame,
+    /// including makeup (1.0 for an empty or bypassed frame), for per-frame
+    /// gain logging.
+    pub fn process_frame(&mut self, samples: &mut [T]) -> f32 {
+        if self.bypass {
+            self.track(samples);
+            self.last_gain_reduction_db = 0.0;
+            return 1.0;
+        }
+        self.process_frame_with(samples, |sample| sample)
+    }
+
+    /// `process_frame` with `pre` applied to each sample before it reaches
+    /// the detector and gain (see `process_pre_emphasized`).
+    #[inline]
+    fn process_frame_with(&mut self, samples: &mut [T], mut pre: impl FnMut(T) -> T) -> f32 {
+        if samples.is_empty() {
+            return 1.0;
+        }
+        self.sync_params();
+        let mut gain_sum = 0.0;
+        for sample in samples.iter_mut() {
+            let input = pre(*sample);
+            let gain = self.next_gain(input);
+            *sample = input * (gain * self.makeup_gain.value()).into();
+            gain_sum += gain;
+        }
+        let mean_gain = gain_sum / samples.len() as f32;
+        self.last_gain_reduction_db = -20.0 * mean_gain.max(1e-10).log10();
+
+        if let Some(history) = self.gr_history.as_mut() {
+            if history.len() == self.gr_history_capacity {
+                history.pop_front();
+            }
+            history.push_back(self.last_gain_reduction_db);
+        }
+
+        mean_gain * self.makeup_gain.value()
+    }
+
+    /// Apply the current gain (with makeup) without advancing the detector
+    /// or envelope, e.g. while the surrounding processor is frozen.
+    pub fn apply_held_gain(&mut self, samples: &mut [T]) {
+        if self.bypass {
+            self.last_gain_reduction_db = 0.0;
+            return;
+        }
+        let gain = self.gain_smooth * self.makeup_gain.value();
+        samples.iter_mut().for_each(|s| *s = *s * gain.into());
+        self.last_gain_reduction_db = -20.0 * self.gain_smooth.max(1e-10).log10();
+    }
+
+    /// Mean gain reduction (dB, ≥ 0, makeup excluded) applied by the last
+    /// `process`/`process_frame` call; 0 when bypassed.
+    pub fn last_gain_reduction_db(&self) -> f32 {
+        self.last_gain_reduction_db
+    }
+
+    /// Process in-place and return the smoothed compressor gain applied to
+    /// each sample (linear, makeup excluded), for drawing a gain curve over
+    /// a waveform. The envelope is the same length as `samples`; in bypass
+    /// it is all 1.0.
+    pub fn process_with_envelope(&mut self, samples: &mut [T]) -> Vec<f32> {
+        if self.bypass {
+            self.track(samples);
+            return vec![1.0; samples.len()];
+        }
+        self.sync_params();
+        let mut envelope = Vec::with_capacity(samples.len());
+        for sample in samples.iter_mut() {
+            let gain = self.next_gain(*sample);
+            *sample = *sample * (gain * self.makeup_gain.value()).into();
+            envelope.push(gain);
+        }
+        envelope
+    }
+
+    /// Parallel ("New York") compression: blends the dry signal with a fully
+    /// compressed copy. `blend` is the wet fraction (0.0 = dry, 1.0 = same
+    /// as `process` plus makeup).
+    ///
+    /// The wet path is level-matched to the dry path using slow (~500ms)
+    /// power averages of both, so changing the blend changes dynamics rather
+    /// than loudness: loud passages sum to roughly the dry level while quiet
+    /// passages are lifted by the wet path's makeup gain.
+    ///
+    /// For speech, 0.3–0.5 keeps consonant attacks crisp while evening out
+    /// level; above ~0.7 it approaches plain compression.
+    pub fn process_parallel(&mut self, samples: &mut [T], blend: f32) {
+        if self.bypass {
+            self.track(samples);
+            return;
+        }
+        self.sync_params();
+        let blend: T = blend.clamp(0.0, 1.0).into();
         for sample in samples.iter_mut() {
-            let input = *sample;
-            let sq = input * input;
-
-            // Update sliding RMS window
-            self.rms_sum -= self.rms_buffer[self.rms_index];
-            self.rms_buffer[self.rms_index] = sq;
-            self.rms_sum += sq;
-            self.rms_index = (self.rms_index + 1) % RMS_WINDOW;
-
-            // Compute RMS level
-            let rms = (self.rms_sum / RMS_WINDOW as f32).sqrt().max(1e-10);
-            let input_db = 20.0 * rms.log10();
-
-            // Desired gain in dB from compressor curve
-            let gain_db = Self::compute_gain_db(input_db);
-            let desired_gain = 10.0f32.powf(gain_db / 20.0);
-
-            // Smooth gain with attack/release
-            let coeff = if desired_gain < self.gain_smooth {
-                ATTACK_COEFF // fast attack for transients
+            let dry = *sample;
+            let compressed = dry * self.next_gain(dry).into();
+
+            let (dry_f, compressed_f): (f32, f32) = (dry.as_(), compressed.as_());
+            self.dry_power += PARALLEL_MATCH_COEFF * (dry_f * dry_f - self.dry_power);
+            self.wet_power += PARALLEL_MATCH_COEFF * (compressed_f * compressed_f - self.wet_power);
+            let makeup = if self.wet_power > 1e-12 {
+                (self.dry_power / self.wet_power).sqrt()
             } else {
-                RELEASE_COEFF // slow release for smooth recovery
+                1.0
             };
-            self.gain_smooth += coeff * (desired_gain - self.gain_smooth);
 
-            *sample = input * self.gain_smooth;
+            *sample = dry + blend * (compressed * makeup.into() - dry);
+        }
+    }
+
+    /// Advance the detector and gain envelope without modifying audio,
+    /// so the stage can be re-enabled without a gain jump.
+    pub fn track(&mut self, samples: &[T]) {
+        self.sync_params();
+        for &sample in samples {
+            self.next_gain(sample);
         }
     }
+
+    /// Sidechain RMS window in ms (default 10). Longer windows stop bursty
+    /// phone audio from pumping the gain; shorter ones catch transients.
+    /// Must be at least 8 samples and at most 1s. Clears the detector.
+    pub fn set_rms_window_ms(&mut self, window_ms: f32) -> Result<()> {
+        self.rms = SlidingRms::new(rms_window_samples(window_ms)?);
+        self.sub_rms = self.sub_window_detector();
+        Ok(())
+    }
+
+    /// Clear the level detector and gain state (and any gain-reduction
+    /// history), keeping all settings. Use between unrelated streams.
+    pub fn reset(&mut self) {
+        self.rms.reset();
+        if let Some(sub_rms) = self.sub_rms.as_mut() {
+            sub_rms.reset();
+        }
+        if let Some(highpass) = self.sidechain_highpass.as_mut() {
+            highpass.reset();
+        }
+        self.gain_smooth = 1.0;
+        self.feedback_sample = T::zero();
+        self.dry_power = 0.0;
+        self.wet_power = 0.0;
+        self.sustain_samples = 0;
+        if let Some(history) = self.gr_history.as_mut() {
+            history.clear();
+        }
+        self.last_gain_reduction_db = 0.0;
+        self.reset_crest();
+        self.sync_params();
+        self.snap_params();
+    }
+}
+
+impl AudioStage for SpeechCompressor {
+    fn process(&mut self, samples: &mut [f32]) {
+        SpeechCompressor::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        SpeechCompressor::reset(self);
+    }
 }
 
 // ============================================================================
@@ -111,205 +931,1766 @@ const NORM_MIN_GAIN: f32 = 0.5;
 /// Smoothing coefficient: ~200ms time constant at per-sample rate
 /// alpha ≈ 1 / (48000 * 0.2) ≈ 0.000104
 const NORM_SMOOTH_COEFF: f32 = 0.0001;
-/// RMS floor — below this, hold gain (don't track silence)
+/// Anti-windup attack: ~5ms at 48kHz, used instead of the 200ms smoothing
+/// when the gain sits more than `NORM_WINDUP_RATIO` above what the input
+/// needs — i.e. loud audio arriving after a quiet passage let it ride up
+/// alpha ≈ 1 / (48000 * 0.005) ≈ 0.004
+const NORM_ATTACK_COEFF: f32 = 0.004;
+/// Gain excess (6 dB) beyond which the fast attack takes over
+const NORM_WINDUP_RATIO: f32 = 2.0;
+/// In the target tolerance band, a gain this close to unity (~0.01 dB)
+/// snaps to it, so leveled audio ends up passing bit-exact. The smoothing
+/// step alone stalls in f32 rounding just short of 1.0.
+const NORM_UNITY_SNAP: f32 = 1e-3;
+/// Default RMS floor — below this, hold gain (don't track silence)
 const NORM_SILENCE_FLOOR: f32 = 0.001;
 
-pub struct RmsNormalizer {
-    rms_buffer: [f32; RMS_WINDOW],
-    rms_index: usize,
-    rms_sum: f32,
+/// Generic over the sample type like `SpeechCompressor`; the gain is
+/// computed in f32 either way.
+pub struct RmsNormalizer<T: Sample = f32> {
+    rms: SlidingRms<T>,
     current_gain: f32,
+    /// Output RMS the gain converges towards (defaults to `TARGET_RMS`),
+    /// ramped on change
+    target_rms: ParamRamp,
+    /// Input within this many dB of the target is left at unity gain
+    target_tolerance_db: Option<f32>,
+    /// Peak bound for the output clamp, in (0, 1]
+    ceiling: f32,
+    /// When set, `process` only tracks the input
+    bypass: bool,
+    /// RMS below which the gain is held (defaults to `NORM_SILENCE_FLOOR`)
+    silence_floor: f32,
+    /// Integrated-loudness target; replaces the RMS target when set
+    target_lufs: Option<f32>,
+    lufs_meter: Option<LufsMeter>,
+    /// Linked upstream gain and the value last accounted for
+    upstream: Option<GainLink>,
+    upstream_gain: f32,
 }
 
 impl RmsNormalizer {
     pub fn new() -> Self {
+        Self::build()
+    }
+}
+
+impl RmsNormalizer<f64> {
+    /// Double-precision normalizer for offline processing of long
+    /// recordings.
+    pub fn new_f64() -> Self {
+        Self::build()
+    }
+}
+
+impl<T: Sample> RmsNormalizer<T> {
+    fn build() -> Self {
         Self {
-            rms_buffer: [0.0; RMS_WINDOW],
-            rms_index: 0,
-            rms_sum: 0.0,
+            rms: SlidingRms::new(RMS_WINDOW),
             current_gain: 1.0,
+            target_rms: ParamRamp::new(TARGET_RMS),
+            target_tolerance_db: None,
+            ceiling: 1.0,
+            bypass: false,
+            silence_floor: NORM_SILENCE_FLOOR,
+            target_lufs: None,
+            lufs_meter: None,
+            upstream: None,
+            upstream_gain: 1.0,
         }
     }
 
-    pub fn process(&mut self, samples: &mut [f32]) {
-        for sample in samples.iter_mut() {
-            let sq = *sample * *sample;
-
-            // Update sliding RMS
-            self.rms_sum -= self.rms_buffer[self.rms_index];
-            self.rms_buffer[self.rms_index] = sq;
-            self.rms_sum += sq;
-            self.rms_index = (self.rms_index + 1) % RMS_WINDOW;
-
-            let rms = (self.rms_sum / RMS_WINDOW as f32).sqrt();
+    /// Link to the gain of an upstream stage (e.g. `AutoGainControl` in the
+    /// legacy chain) so the two stop fighting. Unlinked, both raise their
+    /// gain after a loud passage and the combined gain overshoots, then
+    /// hunts back. Linked, the normalizer cancels (as far as its own gain
+    /// range allows) any rise the upstream gain took since the last block
+    /// and then adapts as usual, so recovery happens once, at the
+    /// normalizer's smooth rate. Upstream drops pass through untouched so
+    /// a loud onset is still caught at once; the upstream stage is left to
+    /// do coarse leveling. `None` unlinks.
+    pub fn set_upstream_gain(&mut self, link: Option<GainLink>) {
+        self.upstream_gain = link.as_ref().map_or(1.0, GainLink::get);
+        self.upstream = link;
+    }
 
-            // Only adapt gain when signal is above silence floor
-            if rms > NORM_SILENCE_FLOOR {
-                let desired_gain = (TARGET_RMS / rms).clamp(NORM_MIN_GAIN, NORM_MAX_GAIN);
-                self.current_gain += NORM_SMOOTH_COEFF * (desired_gain - self.current_gain);
-                self.current_gain = self.current_gain.clamp(NORM_MIN_GAIN, NORM_MAX_GAIN);
-            }
+    /// Absorb an upstream gain rise into the current gain.
+    fn sync_upstream(&mut self) {
+        let Some(link) = self.upstream.as_ref() else {
+            return;
+        };
+        let upstream_gain = link.get();
+        if upstream_gain > self.upstream_gain {
+            self.current_gain = (self.current_gain * self.upstream_gain / upstream_gain)
+                .clamp(NORM_MIN_GAIN, NORM_MAX_GAIN);
+        }
+        self.upstream_gain = upstream_gain;
+    }
 
-            // Apply gain with hard clip
-            *sample = (*sample * self.current_gain).clamp(-1.0, 1.0);
+    /// Target integrated loudness (BS.1770, e.g. -23.0 LUFS) instead of
+    /// linear RMS, for models trained on broadcast-normalized audio. The
+    /// gain follows the input's gated loudness over the last 30s, so it
+    /// settles over a few seconds, ignores pauses and keeps tracking on
+    /// long calls. Holds the
+    /// current gain until the first 400ms block is measured. `None`
+    /// returns to RMS targeting. Must be ≤ 0.
+    pub fn set_target_lufs(&mut self, target_lufs: Option<f32>) -> Result<()> {
+        if let Some(lufs) = target_lufs {
+            ensure!(lufs <= 0.0, "target must be <= 0 LUFS, got {}", lufs);
         }
+        self.target_lufs = target_lufs;
+        self.lufs_meter = target_lufs.map(|_| LufsMeter::new());
+        Ok(())
     }
-}
 
-// ============================================================================
-// NoiseGate — zeros out amplified noise during silence
-// ============================================================================
+    /// Clip output peaks at `ceiling` instead of ±1.0 to leave headroom for
+    /// a downstream limiter or the i16 conversion (e.g. 0.9 ≈ -1 dBFS).
+    /// Independent of the RMS target. Must be in (0, 1].
+    pub fn set_ceiling(&mut self, ceiling: f32) -> Result<()> {
+        ensure!(ceiling > 0.0 && ceiling <= 1.0, "ceiling must be in (0, 1], got {}", ceiling);
+        self.ceiling = ceiling;
+        Ok(())
+    }
 
-/// Open threshold: -46 dBFS RMS ≈ 0.005 linear
-const GATE_OPEN_THRESH: f32 = 0.005;
-/// Close threshold: -50 dBFS RMS ≈ 0.00316 (hysteresis)
-const GATE_CLOSE_THRESH: f32 = 0.00316;
-/// Hold time in samples: 50ms at 48kHz
-const GATE_HOLD_SAMPLES: usize = 2400;
-/// Release fade in samples: 10ms at 48kHz
-const GATE_RELEASE_SAMPLES: usize = 480;
+    /// Pass audio through untouched while the gain keeps adapting.
+    pub fn set_bypass(&mut self, bypass: bool) {
+        self.bypass = bypass;
+    }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum GateState {
-    Open,
-    Hold,
-    Release,
-    Closed,
-}
+    /// RMS level below which the gain is held rather than adapting
+    /// (default 0.001 ≈ -60 dBFS). Raise it when background hum sits above
+    /// the default so pauses aren't normalized up. Also used by
+    /// `calibrate` to skip silent blocks. Must be positive.
+    pub fn set_silence_floor(&mut self, floor: f32) -> Result<()> {
+        ensure!(floor > 0.0, "silence floor must be > 0, got {}", floor);
+        self.silence_floor = floor;
+        Ok(())
+    }
 
-pub struct NoiseGate {
-    rms_buffer: [f32; RMS_WINDOW],
-    rms_index: usize,
-    rms_sum: f32,
-    state: GateState,
-    hold_counter: usize,
-    release_counter: usize,
-}
+    /// Hold the gain at and below the calibrated noise floor.
+    pub fn set_noise_floor(&mut self, floor: NoiseFloor) {
+        self.silence_floor = floor.rms();
+    }
 
-impl NoiseGate {
-    pub fn new() -> Self {
-        Self {
-            rms_buffer: [0.0; RMS_WINDOW],
-            rms_index: 0,
-            rms_sum: 0.0,
-            state: GateState::Open, // start open so we don't gate initial speech
-            hold_counter: 0,
-            release_counter: 0,
+    /// Set the target to the RMS of a reference clip (e.g. audio the STT
+    /// model was trained on) and return it. Samples below the silence floor
+    /// are ignored so pauses in the clip don't drag the target down. If the
+    /// clip has no active audio the current target is kept.
+    pub fn calibrate(&mut self, reference: &[T]) -> f32 {
+        let (sum, count) = reference
+            .chunks(RMS_WINDOW)
+            .map(|block| (block.iter().fold(T::zero(), |acc, &s| acc + s * s), block.len()))
+            .filter(|&(sum, len)| (sum / (len as f32).into()).sqrt().as_() > self.silence_floor)
+            .fold((T::zero(), 0usize), |(s, c), (sum, len)| (s + sum, c + len));
+
+        if count > 0 {
+            self.target_rms.set_target((sum / (count as f32).into()).sqrt().as_().min(1.0));
         }
+        self.target_rms.target()
     }
 
-    pub fn process(&mut self, samples: &mut [f32]) {
-        for sample in samples.iter_mut() {
-            let sq = *sample * *sample;
-
-            // Update sliding RMS
-            self.rms_sum -= self.rms_buffer[self.rms_index];
-            self.rms_buffer[self.rms_index] = sq;
-            self.rms_sum += sq;
-            self.rms_index = (self.rms_index + 1) % RMS_WINDOW;
-
-            let rms = (self.rms_sum / RMS_WINDOW as f32).sqrt();
-
-            match self.state {
-                GateState::Closed => {
-                    if rms >= GATE_OPEN_THRESH {
-                        // Instant open — no speech onset delay
-                        self.state = GateState::Open;
-                    } else {
-                        *sample = 0.0;
-                    }
+    /// Output RMS target (linear, default 0.15 ≈ -16 dBFS). Mid-stream
+    /// changes are ramped in over 20ms. Must be in (0, 1].
+    pub fn set_target_rms(&mut self, target_rms: f32) -> Result<()> {
+        ensure!(target_rms > 0.0 && target_rms <= 1.0, "target RMS must be in (0, 1], got {}", target_rms);
+        self.target_rms.set_target(target_rms);
+        Ok(())
+    }
+
+    pub fn target_rms(&self) -> f32 {
+        self.target_rms.target()
+    }
+
+    /// Leave input that is already within `tolerance_db` of the RMS target
+    /// alone: the gain eases back to unity and, once there, stops being
+    /// recomputed, so well-leveled audio passes bit-exact. Input outside
+    /// the band is normalized as usual. `None` (the default) always
+    /// normalizes. Must be ≥ 0.
+    pub fn set_target_tolerance_db(&mut self, tolerance_db: Option<f32>) -> Result<()> {
+        if let Some(db) = tolerance_db {
+            ensure!(db >= 0.0, "target tolerance must be >= 0 dB, got {}", db);
+        }
+        self.target_tolerance_db = tolerance_db;
+        Ok(())
+    }
+
+    /// Whether an input level is inside the tolerance band around `target`.
+    fn within_tolerance(&self, rms: f32, target: f32) -> bool {
+        self.target_tolerance_db
+            .is_some_and(|db| (20.0 * (rms / target).log10()).abs() <= db)
+    }
+
+    /// Advance the detector by one input sample and return the gain to apply.
+    fn next_gain(&mut self, input: T) -> f32 {
+        let rms: f32 = self.rms.push(input).as_();
+        let target_rms = self.target_rms.advance();
+
+        if let (Some(target), Some(meter)) = (self.target_lufs, self.lufs_meter.as_mut()) {
+            meter.process_sample(input.as_());
+            // Gating already excludes silence, so adapt whenever measured
+            if let Some(lufs) = meter.integrated() {
+                let desired_gain = 10.0f32.powf((target - lufs) / 20.0).clamp(NORM_MIN_GAIN, NORM_MAX_GAIN);
+                self.current_gain += NORM_SMOOTH_COEFF * (desired_gain - self.current_gain);
+            }
+            return self.current_gain;
+        }
+
+        // Only adapt gain when signal is above silence floor
+        if rms > self.silence_floor {
+            let in_band = self.within_tolerance(rms, target_rms);
+            if in_band && self.current_gain == 1.0 {
+                return 1.0;
+            }
+            let desired_gain = if in_band {
+                1.0
+            } else {
+                (target_rms / rms).clamp(NORM_MIN_GAIN, NORM_MAX_GAIN)
+            };
+            // Anti-windup: pull a gain that is far too high down quickly so
+            // a loud onset after a quiet stretch isn't blasted for ~200ms
+            let coeff = if self.current_gain > desired_gain * NORM_WINDUP_RATIO {
+                NORM_ATTACK_COEFF
+            } else {
+                NORM_SMOOTH_COEFF
+            };
+            self.current_gain += coeff * (desired_gain - self.current_gain);
+            self.current_gain = self.current_gain.clamp(NORM_MIN_GAIN, NORM_MAX_GAIN);
+            if in_band && (self.current_gain - 1.0).abs() < NORM_UNITY_SNAP {
+                self.current_gain = 1.0;
+            }
+        }
+        self.current_gain
+    }
+
+    pub fn process(&mut self, samples: &mut [T]) {
+        self.process_frame(samples);
+    }
+
+    /// Process in-place and return the mean gain applied over the frame
+    /// (before clipping; 1.0 for an empty or bypassed frame).
+    pub fn process_frame(&mut self, samples: &mut [T]) -> f32 {
+        if self.bypass {
+            self.track(samples);
+            return 1.0;
+        }
+        if samples.is_empty() {
+            return 1.0;
+        }
+        self.sync_upstream();
+        let ceiling: T = self.ceiling.into();
+        let mut gain_sum = 0.0;
+        for sample in samples.iter_mut() {
+            let gain = self.next_gain(*sample);
+            // Apply gain with hard clip at the ceiling
+            *sample = (*sample * gain.into()).max(-ceiling).min(ceiling);
+            gain_sum += gain;
+        }
+        gain_sum / samples.len() as f32
+    }
+
+    /// Apply the current gain and ceiling without adapting.
+    pub fn apply_held_gain(&mut self, samples: &mut [T]) {
+        if self.bypass {
+            return;
+        }
+        let (gain, ceiling): (T, T) = (self.current_gain.into(), self.ceiling.into());
+        samples.iter_mut().for_each(|s| *s = (*s * gain).max(-ceiling).min(ceiling));
+    }
+
+    /// Advance the level tracker without modifying audio.
+    pub fn track(&mut self, samples: &[T]) {
+        self.sync_upstream();
+        for &sample in samples {
+            self.next_gain(sample);
+        }
+    }
+
+    /// Level-measurement window in ms (default 10). Must be at least 8
+    /// samples and at most 1s. Clears the level window.
+    pub fn set_rms_window_ms(&mut self, window_ms: f32) -> Result<()> {
+        self.rms = SlidingRms::new(rms_window_samples(window_ms)?);
+        Ok(())
+    }
+
+    /// Return to unity gain with an empty level window, keeping settings.
+    pub fn reset(&mut self) {
+        self.rms.reset();
+        self.current_gain = 1.0;
+        self.target_rms.snap();
+        self.upstream_gain = self.upstream.as_ref().map_or(1.0, GainLink::get);
+        if let Some(meter) = self.lufs_meter.as_mut() {
+            meter.reset();
+        }
+    }
+}
+
+impl AudioStage for RmsNormalizer {
+    fn process(&mut self, samples: &mut [f32]) {
+        RmsNormalizer::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        RmsNormalizer::reset(self);
+    }
+}
+
+// ============================================================================
+// NoiseGate — zeros out amplified noise during silence
+// ============================================================================
+
+/// Open threshold: -46 dBFS RMS ≈ 0.005 linear
+const GATE_OPEN_THRESH: f32 = 0.005;
+/// Close threshold: -50 dBFS RMS ≈ 0.00316 (hysteresis)
+const GATE_CLOSE_THRESH: f32 = 0.00316;
+/// Hold time in samples: 50ms at 48kHz
+const GATE_HOLD_SAMPLES: usize = 2400;
+/// Open/close hysteresis when the thresholds come from a `NoiseFloor`
+/// (the same 4 dB as the defaults)
+const GATE_HYSTERESIS_DB: f32 = 4.0;
+/// Release fade in samples: 10ms at 48kHz
+const GATE_RELEASE_SAMPLES: usize = 480;
+/// VAD-keyed mode: open at or above this speech probability
+const GATE_PROB_OPEN: f32 = 0.6;
+/// VAD-keyed mode: close below this speech probability (hysteresis)
+const GATE_PROB_CLOSE: f32 = 0.4;
+/// Transition-rate window: 1s at 48kHz
+const GATE_TRANSITION_WINDOW: u64 = 48_000;
+/// Transitions are counted in this many buckets across the window (100ms
+/// each), so a thrashing gate never allocates
+const GATE_TRANSITION_BUCKETS: usize = 10;
+const GATE_TRANSITION_BUCKET_SAMPLES: u64 = GATE_TRANSITION_WINDOW / GATE_TRANSITION_BUCKETS as u64;
+/// Comfort noise one-pole lowpass pole until the noise floor has been
+/// measured: gentle HF rolloff so the noise sounds like room tone
+const COMFORT_NOISE_POLE: f32 = 0.5;
+/// Range the measured pole is clamped to (keeps the generator stable)
+const COMFORT_NOISE_POLE_MIN: f32 = -0.9;
+const COMFORT_NOISE_POLE_MAX: f32 = 0.95;
+/// Noise floor spectrum estimate: ~1s averaging at 48kHz
+const COMFORT_NOISE_SHAPE_COEFF: f32 = 0.00002;
+/// Default comfort noise seed (override with `seed_comfort_noise`)
+const COMFORT_NOISE_SEED: u64 = 0x5eed;
+
+/// Low-level shaped noise filled in while the gate is closed.
+///
+/// The spectrum follows the gated input: while closed, the lag-0 and lag-1
+/// autocorrelation of the noise floor are averaged and the generator's
+/// one-pole filter uses their ratio, which is the best first-order (AR(1))
+/// fit to the floor's spectral tilt. The level is fixed by configuration.
+struct ComfortNoise {
+    rng: Pcg32,
+    /// Linear RMS of the generated noise
+    level: f32,
+    /// Generator filter pole and state
+    pole: f32,
+    state: f32,
+    /// Noise floor autocorrelation estimates (lag 0, lag 1)
+    floor_r0: f32,
+    floor_r1: f32,
+    floor_prev: f32,
+}
+
+impl ComfortNoise {
+    fn new(level: f32, seed: u64) -> Self {
+        Self {
+            rng: Pcg32::new(seed),
+            level,
+            pole: COMFORT_NOISE_POLE,
+            state: 0.0,
+            floor_r0: 0.0,
+            floor_r1: 0.0,
+            floor_prev: 0.0,
+        }
+    }
+
+    /// Feed one gated input sample to the noise floor estimate.
+    #[inline]
+    fn observe(&mut self, input: f32) {
+        self.floor_r0 += COMFORT_NOISE_SHAPE_COEFF * (input * input - self.floor_r0);
+        self.floor_r1 += COMFORT_NOISE_SHAPE_COEFF * (input * self.floor_prev - self.floor_r1);
+        self.floor_prev = input;
+        if self.floor_r0 > 1e-12 {
+            self.pole = (self.floor_r1 / self.floor_r0).clamp(COMFORT_NOISE_POLE_MIN, COMFORT_NOISE_POLE_MAX);
+        }
+    }
+
+    #[inline]
+    fn next_sample(&mut self) -> f32 {
+        // Uniform white noise has RMS 1/√3; the one-pole filter scales
+        // power by (1 - p) / (1 + p). Undo both so the output RMS equals
+        // `level`.
+        let scale = (3.0 * (1.0 + self.pole) / (1.0 - self.pole)).sqrt();
+        let white = 2.0 * self.rng.next_f32() - 1.0;
+        self.state = self.pole * self.state + (1.0 - self.pole) * white;
+        self.state * scale * self.level
+    }
+}
+
+/// Per-sample decay multiplier for a close-decision time constant in ms
+/// (0 = none).
+fn close_decay(decay_ms: f32) -> f32 {
+    if decay_ms <= 0.0 {
+        0.0
+    } else {
+        (-1.0 / (decay_ms * SAMPLES_PER_MS)).exp()
+    }
+}
+
+/// Called with the transition rate (per second) when it rises above the
+/// limit given to `NoiseGate::set_transition_warning`.
+pub type TransitionWarning = Box<dyn FnMut(f32) + Send>;
+
+/// What the gate does to the signal once it has closed.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum GateMode {
+    /// Hard mute (original behaviour)
+    #[default]
+    Cut,
+    /// Duck by a fixed amount in dB (negative, e.g. -20.0) so quiet room
+    /// tone stays audible on monitoring feeds
+    Attenuate(f32),
+    /// Downward expansion instead of gating: below `threshold_db` (RMS,
+    /// dBFS) every dB of level drop becomes `ratio` dB at the output, so a
+    /// signal 10 dB under threshold at 2:1 is attenuated by a further 10 dB.
+    /// Gentler than gating for music under speech. Applies to the energy
+    /// path (`process`); `process_keyed` keeps the gate behaviour.
+    Expand { threshold_db: f32, ratio: f32 },
+}
+
+impl GateMode {
+    /// Linear gain applied while closed
+    fn floor_gain(self) -> f32 {
+        match self {
+            GateMode::Cut | GateMode::Expand { .. } => 0.0,
+            GateMode::Attenuate(db) => 10.0f32.powf(db.min(0.0) / 20.0),
+        }
+    }
+
+    /// Downward-expander gain for a detector level (linear RMS)
+    fn expander_gain(rms: f32, threshold_db: f32, ratio: f32) -> f32 {
+        let level_db = 20.0 * rms.max(1e-10).log10();
+        if level_db >= threshold_db {
+            1.0
+        } else {
+            10.0f32.powf((ratio.max(1.0) - 1.0) * (level_db - threshold_db) / 20.0)
+        }
+    }
+}
+
+/// Gate timing options. `GateConfig::default()` matches `NoiseGate::new()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GateConfig {
+    /// Minimum time the gate stays open once it opens, in ms (default 0).
+    /// Stops borderline speech from flapping open/closed every few frames.
+    /// Unlike the hold time, this counts from the moment of opening, not
+    /// from when the level drops. Must be finite and ≥ 0.
+    pub min_open_ms: f32,
+    /// Minimum time the gate stays closed once it fully closes, in ms
+    /// (default 0). A stray click mid-pause can't reopen it until this has
+    /// passed; counts from the end of the release fade. Must be finite and
+    /// ≥ 0.
+    pub min_closed_ms: f32,
+    /// Comfort noise level in dBFS RMS while closed (see
+    /// `NoiseGate::set_comfort_noise`); `None` = silence
+    pub comfort_noise: Option<f32>,
+    /// A-weight the detector so rumble doesn't open the gate (see
+    /// `NoiseGate::set_a_weighting`); detection only
+    pub a_weighting: bool,
+    /// Time constant in ms of the level the close decision looks at (see
+    /// `NoiseGate::set_close_decay_ms`); 0 (default) uses the raw detector
+    pub close_decay_ms: f32,
+    /// Level the closed gate leaves, in dB relative to the input (e.g.
+    /// -40.0), so ambience is reduced rather than removed; the same as
+    /// `GateMode::Attenuate`. `None` (default) or -inf mutes fully.
+    pub floor_db: Option<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum GateState {
+    Open,
+    Hold,
+    Release,
+    Closed,
+}
+
+pub struct NoiseGate {
+    rms: SlidingRms,
+    state: GateState,
+    hold_counter: usize,
+    release_counter: usize,
+    /// Detector thresholds, linear RMS (close < open for hysteresis)
+    open_thresh: f32,
+    close_thresh: f32,
+    /// `process_keyed` speech-probability thresholds (close < open)
+    prob_open: f32,
+    prob_close: f32,
+    /// Samples left before an open gate may start closing
+    min_open_counter: usize,
+    min_open_samples: usize,
+    /// Samples left before a closed gate may reopen
+    min_closed_counter: usize,
+    min_closed_samples: usize,
+    mode: GateMode,
+    /// Cached `mode.floor_gain()`
+    floor_gain: f32,
+    /// When set, `process` only runs the state machine
+    bypass: bool,
+    /// Samples spent in each `GateState` since the last reset
+    state_durations: [u64; 4],
+    /// Noise filled in while closed/releasing (`None` = silence)
+    comfort_noise: Option<ComfortNoise>,
+    comfort_noise_seed: u64,
+    /// Optional A-weighting of the detector input (never the output)
+    detector_weighting: Option<AWeighting>,
+    /// Gain applied to the most recent sample, reused by `apply_held_gain`
+    held_gain: f32,
+    /// Peak-hold of the detector level with exponential decay, used for
+    /// the close decision; `close_decay` is its per-sample multiplier
+    /// (0 = no hold, the raw level decides)
+    close_level: f32,
+    close_decay: f32,
+    /// Samples stepped since the last reset, and state changes counted per
+    /// bucket of the window (indexed by bucket number modulo the count);
+    /// `transition_bucket` is the newest bucket number written
+    clock: u64,
+    transitions: [u32; GATE_TRANSITION_BUCKETS],
+    transition_bucket: u64,
+    /// Rate limit and callback; `warned` is set once it has fired
+    transition_warning: Option<(f32, TransitionWarning)>,
+    warned: bool,
+}
+
+impl NoiseGate {
+    pub fn new() -> Self {
+        Self::build(GateConfig::default())
+    }
+
+    /// Gate from a config. Errors if `min_open_ms`, `min_closed_ms` or
+    /// `close_decay_ms` is negative or not finite.
+    pub fn with_config(config: GateConfig) -> Result<Self> {
+        ensure!(config.min_open_ms.is_finite() && config.min_open_ms >= 0.0,
+            "min open time must be >= 0 ms, got {}", config.min_open_ms);
+        ensure!(config.min_closed_ms.is_finite() && config.min_closed_ms >= 0.0,
+            "min closed time must be >= 0 ms, got {}", config.min_closed_ms);
+        let mut gate = Self::build(config);
+        gate.set_close_decay_ms(config.close_decay_ms)?;
+        Ok(gate)
+    }
+
+    /// Everything in `config` but the close decay, which starts disabled.
+    fn build(config: GateConfig) -> Self {
+        let mut gate = Self {
+            rms: SlidingRms::new(RMS_WINDOW),
+            state: GateState::Open, // start open so we don't gate initial speech
+            hold_counter: 0,
+            release_counter: 0,
+            open_thresh: GATE_OPEN_THRESH,
+            close_thresh: GATE_CLOSE_THRESH,
+            prob_open: GATE_PROB_OPEN,
+            prob_close: GATE_PROB_CLOSE,
+            min_open_counter: 0,
+            min_open_samples: (config.min_open_ms * SAMPLES_PER_MS) as usize,
+            min_closed_counter: 0,
+            min_closed_samples: (config.min_closed_ms * SAMPLES_PER_MS) as usize,
+            mode: GateMode::Cut,
+            floor_gain: 0.0,
+            bypass: false,
+            state_durations: [0; 4],
+            comfort_noise: None,
+            comfort_noise_seed: COMFORT_NOISE_SEED,
+            detector_weighting: None,
+            held_gain: 1.0,
+            close_level: 0.0,
+            close_decay: 0.0,
+            clock: 0,
+            transitions: [0; GATE_TRANSITION_BUCKETS],
+            transition_bucket: 0,
+            transition_warning: None,
+            warned: false,
+        };
+        gate.set_comfort_noise(config.comfort_noise);
+        gate.set_a_weighting(config.a_weighting);
+        if let Some(db) = config.floor_db {
+            gate.set_mode(GateMode::Attenuate(db));
+        }
+        gate
+    }
+
+    /// Gate with custom open/close thresholds (RMS, dBFS).
+    /// See `set_thresholds`.
+    pub fn with_thresholds(open_db: f32, close_db: f32) -> Result<Self> {
+        let mut gate = Self::new();
+        gate.set_thresholds(open_db, close_db)?;
+        Ok(gate)
+    }
+
+    /// Set the detector's open and close thresholds (RMS, dBFS; defaults
+    /// -46 / -50). `close_db` must be below `open_db` — the gap is the
+    /// hysteresis that stops the gate chattering on a level near either.
+    pub fn set_thresholds(&mut self, open_db: f32, close_db: f32) -> Result<()> {
+        ensure!(open_db.is_finite() && close_db.is_finite(), "thresholds must be finite");
+        ensure!(close_db < open_db,
+            "close threshold ({} dB) must be below open threshold ({} dB)", close_db, open_db);
+        self.open_thresh = 10.0f32.powf(open_db / 20.0);
+        self.close_thresh = 10.0f32.powf(close_db / 20.0);
+        Ok(())
+    }
+
+    /// Close at the calibrated noise floor and open `GATE_HYSTERESIS_DB`
+    /// above it.
+    pub fn set_noise_floor(&mut self, floor: NoiseFloor) {
+        self.close_thresh = floor.rms();
+        self.open_thresh = 10.0f32.powf((floor.db() + GATE_HYSTERESIS_DB) / 20.0);
+    }
+
+    /// Set the thresholds from a recording of the background alone: open
+    /// `margin_db` above its RMS and close `GATE_HYSTERESIS_DB` below that.
+    /// Returns the chosen `(open_db, close_db)`. The sample must not be
+    /// empty or digital silence; `margin_db` must be ≥ 0.
+    pub fn calibrate_from_noise(&mut self, noise: &[f32], margin_db: f32) -> Result<(f32, f32)> {
+        ensure!(margin_db.is_finite() && margin_db >= 0.0, "margin must be >= 0 dB, got {}", margin_db);
+        let noise_rms = rms(noise);
+        ensure!(noise_rms > 0.0, "noise sample is empty or silent");
+        let open_db = 20.0 * noise_rms.log10() + margin_db;
+        let close_db = open_db - GATE_HYSTERESIS_DB;
+        self.set_thresholds(open_db, close_db)?;
+        Ok((open_db, close_db))
+    }
+
+    /// Set the speech probabilities at which `process_keyed` opens and
+    /// closes (defaults 0.6 / 0.4), e.g. to match a VAD whose scores run
+    /// high or low. Both in [0, 1] with `close` below `open`.
+    pub fn set_probability_thresholds(&mut self, open: f32, close: f32) -> Result<()> {
+        ensure!((0.0..=1.0).contains(&open) && (0.0..=1.0).contains(&close),
+            "probability thresholds must be in [0, 1], got {} / {}", open, close);
+        ensure!(close < open,
+            "close probability ({}) must be below open probability ({})", close, open);
+        self.prob_open = open;
+        self.prob_close = close;
+        Ok(())
+    }
+
+    /// Make the gate reluctant to close right after loud audio: the close
+    /// decision follows a level that jumps up with the detector but decays
+    /// with this time constant, so the low-energy tail of a word (/s/, /f/)
+    /// isn't cut the moment the vowel ends. Opening still reacts to the raw
+    /// level. 50-100ms keeps word endings; 0 disables it. Must be ≥ 0.
+    pub fn set_close_decay_ms(&mut self, decay_ms: f32) -> Result<()> {
+        ensure!(decay_ms.is_finite() && decay_ms >= 0.0, "close decay must be >= 0 ms, got {}", decay_ms);
+        self.close_decay = close_decay(decay_ms);
+        Ok(())
+    }
+
+    /// A-weight the signal feeding the RMS detector so low-frequency
+    /// rumble (HVAC, mains hum) doesn't hold the gate open. Detection only;
+    /// the audio passing through is not filtered. Off by default.
+    pub fn set_a_weighting(&mut self, enabled: bool) {
+        self.detector_weighting = enabled.then(|| AWeighting::new(PIPELINE_RATE as f32));
+    }
+
+    /// Fill gated passages with low-level noise at `level_db` (RMS, dBFS,
+    /// e.g. -60.0) so a closed gate doesn't sound like a dropped call. Set
+    /// it near the capture's noise floor; the noise's spectral tilt is
+    /// learned from the gated input. Crossfades in over the release.
+    /// `None` (default) leaves gated audio silent.
+    pub fn set_comfort_noise(&mut self, level_db: Option<f32>) {
+        self.comfort_noise = level_db
+            .map(|db| ComfortNoise::new(10.0f32.powf(db.min(0.0) / 20.0), self.comfort_noise_seed));
+    }
+
+    /// Seed the comfort noise generator, restarting its sequence, so output
+    /// is reproducible (e.g. in tests).
+    pub fn seed_comfort_noise(&mut self, seed: u64) {
+        self.comfort_noise_seed = seed;
+        if let Some(noise) = self.comfort_noise.as_mut() {
+            *noise = ComfortNoise::new(noise.level, seed);
+        }
+    }
+
+    /// Apply the gate gain to one sample, adding comfort noise in the
+    /// gap it leaves while closed or releasing.
+    #[inline]
+    fn apply(&mut self, sample: f32, gain: f32) -> f32 {
+        match (self.state, self.comfort_noise.as_mut()) {
+            (GateState::Closed, Some(noise)) => {
+                noise.observe(sample);
+                sample * gain + noise.next_sample() * (1.0 - gain)
+            }
+            (GateState::Release, Some(noise)) => sample * gain + noise.next_sample() * (1.0 - gain),
+            _ => sample * gain,
+        }
+    }
+
+    /// Pass audio through untouched while the state machine keeps running.
+    pub fn set_bypass(&mut self, bypass: bool) {
+        self.bypass = bypass;
+    }
+
+    /// Samples spent in each state since the last reset, indexed
+    /// `[Open, Hold, Release, Closed]`. Useful when tuning hold/release.
+    pub fn state_durations(&self) -> [u64; 4] {
+        self.state_durations
+    }
+
+    pub fn reset_state_durations(&mut self) {
+        self.state_durations = [0; 4];
+    }
+
+    pub fn set_mode(&mut self, mode: GateMode) {
+        self.mode = mode;
+        self.floor_gain = mode.floor_gain();
+    }
+
+    pub fn mode(&self) -> GateMode {
+        self.mode
+    }
+
+    /// Fully open: the detector is above the close threshold (or the VAD
+    /// key says speech). Hold and release count as neither open nor closed.
+    pub fn is_open(&self) -> bool {
+        self.state == GateState::Open
+    }
+
+    /// Fully closed: hold and release have run out.
+    pub fn is_closed(&self) -> bool {
+        self.state == GateState::Closed
+    }
+
+    /// Update the sliding RMS window with one sample and return the level.
+    fn update_rms(&mut self, input: f32) -> f32 {
+        let input = match self.detector_weighting.as_mut() {
+            Some(weighting) => weighting.process_sample(input),
+            None => input,
+        };
+        self.rms.push(input)
+    }
+
+    /// Advance the gate state machine by one input sample and return the
+    /// gain to apply (1.0 = open, floor gain = closed, in between during release).
+    fn next_gain(&mut self, input: f32) -> f32 {
+        let rms = self.update_rms(input);
+        self.close_level = rms.max(self.close_level * self.close_decay);
+        let gate_gain = self.step(rms >= self.open_thresh, self.close_level < self.close_thresh);
+        self.held_gain = match self.mode {
+            GateMode::Expand { threshold_db, ratio } => GateMode::expander_gain(rms, threshold_db, ratio),
+            _ => gate_gain,
+        };
+        self.held_gain
+    }
+
+    /// One state-machine tick given the detector's open/close decisions.
+    fn step(&mut self, should_open: bool, should_close: bool) -> f32 {
+        let before = self.state;
+        let gain = self.transition(should_open, should_close);
+        self.state_durations[self.state as usize] += 1;
+        self.clock += 1;
+        if self.state != before {
+            self.record_transition();
+        }
+        gain
+    }
+
+    fn record_transition(&mut self) {
+        let bucket = self.clock / GATE_TRANSITION_BUCKET_SAMPLES;
+        let stale = (bucket - self.transition_bucket).min(GATE_TRANSITION_BUCKETS as u64);
+        for b in bucket + 1 - stale..=bucket {
+            self.transitions[b as usize % GATE_TRANSITION_BUCKETS] = 0;
+        }
+        self.transition_bucket = bucket;
+        self.transitions[bucket as usize % GATE_TRANSITION_BUCKETS] += 1;
+        let rate = self.transitions_per_second();
+        if let Some((limit, callback)) = self.transition_warning.as_mut() {
+            if rate > *limit && !self.warned {
+                callback(rate);
+                self.warned = true;
+            } else if rate < *limit / 2.0 {
+                self.warned = false;
+            }
+        }
+    }
+
+    /// State changes (between open, hold, release and closed) per second
+    /// over the last second of input, in 100ms steps (so under-reads during
+    /// the first second). Normal speech stays in single figures; tens per
+    /// second means the thresholds are misconfigured and the gate is
+    /// thrashing.
+    pub fn transitions_per_second(&self) -> f32 {
+        let bucket = self.clock / GATE_TRANSITION_BUCKET_SAMPLES;
+        let oldest = (bucket + 1).saturating_sub(GATE_TRANSITION_BUCKETS as u64);
+        (oldest..=self.transition_bucket)
+            .map(|b| self.transitions[b as usize % GATE_TRANSITION_BUCKETS])
+            .sum::<u32>() as f32
+    }
+
+    /// Call `callback` with the rate whenever `transitions_per_second`
+    /// rises above `limit` (e.g. 20.0). It fires once per excursion and
+    /// re-arms when the rate drops below half the limit, so a rate hovering
+    /// around the limit doesn't repeat it. Must be > 0.
+    pub fn set_transition_warning<F>(&mut self, limit: f32, callback: F) -> Result<()>
+    where
+        F: FnMut(f32) + Send + 'static,
+    {
+        ensure!(limit > 0.0, "transition limit must be > 0, got {}", limit);
+        self.transition_warning = Some((limit, Box::new(callback)));
+        self.warned = false;
+        Ok(())
+    }
+
+    pub fn clear_transition_warning(&mut self) {
+        self.transition_warning = None;
+    }
+
+    fn open(&mut self) {
+        self.state = GateState::Open;
+        self.min_open_counter = self.min_open_samples;
+    }
+
+    fn transition(&mut self, should_open: bool, should_close: bool) -> f32 {
+        match self.state {
+            GateState::Closed => {
+                if self.min_closed_counter > 0 {
+                    // Debounce: stay closed for the minimum time
+                    self.min_closed_counter -= 1;
+                    self.floor_gain
+                } else if should_open {
+                    // Instant open — no speech onset delay
+                    self.open();
+                    1.0
+                } else {
+                    self.floor_gain
                 }
-                GateState::Open => {
-                    if rms < GATE_CLOSE_THRESH {
-                        self.state = GateState::Hold;
-                        self.hold_counter = GATE_HOLD_SAMPLES;
-                    }
-                    // Pass through
+            }
+            GateState::Open => {
+                if self.min_open_counter > 0 {
+                    // Chatter guard: stay open for the minimum time
+                    self.min_open_counter -= 1;
+                } else if should_close {
+                    self.state = GateState::Hold;
+                    self.hold_counter = GATE_HOLD_SAMPLES;
                 }
-                GateState::Hold => {
-                    if rms >= GATE_OPEN_THRESH {
-                        self.state = GateState::Open;
-                    } else if self.hold_counter > 0 {
-                        self.hold_counter -= 1;
-                    } else {
-                        self.state = GateState::Release;
-                        self.release_counter = GATE_RELEASE_SAMPLES;
-                    }
-                    // Pass through during hold
+                // Pass through
+                1.0
+            }
+            GateState::Hold => {
+                if should_open {
+                    self.open();
+                } else if self.hold_counter > 0 {
+                    self.hold_counter -= 1;
+                } else {
+                    self.state = GateState::Release;
+                    self.release_counter = GATE_RELEASE_SAMPLES;
                 }
-                GateState::Release => {
-                    if rms >= GATE_OPEN_THRESH {
-                        self.state = GateState::Open;
-                    } else if self.release_counter > 0 {
-                        // Linear fade to zero
-                        let fade = self.release_counter as f32 / GATE_RELEASE_SAMPLES as f32;
-                        *sample *= fade;
-                        self.release_counter -= 1;
-                    } else {
-                        self.state = GateState::Closed;
-                        *sample = 0.0;
-                    }
+                // Pass through during hold
+                1.0
+            }
+            GateState::Release => {
+                if should_open {
+                    self.open();
+                    1.0
+                } else if self.release_counter > 0 {
+                    // Linear fade down to the floor
+                    let fade = self.release_counter as f32 / GATE_RELEASE_SAMPLES as f32;
+                    self.release_counter -= 1;
+                    self.floor_gain + (1.0 - self.floor_gain) * fade
+                } else {
+                    self.state = GateState::Closed;
+                    self.min_closed_counter = self.min_closed_samples;
+                    self.floor_gain
                 }
             }
         }
     }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        if self.bypass {
+            self.track(samples);
+            return;
+        }
+        for sample in samples.iter_mut() {
+            let gain = self.next_gain(*sample);
+            *sample = self.apply(*sample, gain);
+        }
+    }
+
+    /// Gate keyed by a VAD speech probability for this frame instead of
+    /// energy: ≥ 0.6 opens, < 0.4 starts the hold/release countdown (see
+    /// `set_probability_thresholds`); in between the state holds. Quiet
+    /// but clear speech stays open; loud non-speech noise is gated. The RMS
+    /// detector keeps running so switching back to `process` (the energy
+    /// path, used when no probability is available) is seamless.
+    pub fn process_keyed(&mut self, samples: &mut [f32], speech_prob: f32) {
+        let should_open = speech_prob >= self.prob_open;
+        let should_close = speech_prob < self.prob_close;
+        for sample in samples.iter_mut() {
+            self.update_rms(*sample);
+            let gain = self.step(should_open, should_close);
+            self.held_gain = gain;
+            if !self.bypass {
+                *sample = self.apply(*sample, gain);
+            }
+        }
+    }
+
+    /// Apply the current gain with the state machine and detector held:
+    /// the state doesn't change (time spent in it is still counted) and
+    /// comfort noise keeps playing without learning from the input.
+    pub fn apply_held_gain(&mut self, samples: &mut [f32]) {
+        self.state_durations[self.state as usize] += samples.len() as u64;
+        self.clock += samples.len() as u64;
+        if self.bypass {
+            return;
+        }
+        let gain = self.held_gain;
+        for sample in samples.iter_mut() {
+            *sample = match (self.state, self.comfort_noise.as_mut()) {
+                (GateState::Closed | GateState::Release, Some(noise)) => {
+                    *sample * gain + noise.next_sample() * (1.0 - gain)
+                }
+                _ => *sample * gain,
+            };
+        }
+    }
+
+    /// Run the gate state machine without modifying audio.
+    pub fn track(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            self.next_gain(sample);
+        }
+    }
+
+    /// Detector RMS window in ms (default 10). A longer window rides over
+    /// short gaps between words; a shorter one closes sooner. Must be at
+    /// least 8 samples and at most 1s. Clears the detector.
+    pub fn set_rms_window_ms(&mut self, window_ms: f32) -> Result<()> {
+        self.rms = SlidingRms::new(rms_window_samples(window_ms)?);
+        Ok(())
+    }
+
+    /// Return to the initial open state with an empty detector, keeping
+    /// settings. Comfort noise restarts from its seed.
+    pub fn reset(&mut self) {
+        self.rms.reset();
+        self.state = GateState::Open;
+        self.hold_counter = 0;
+        self.release_counter = 0;
+        self.min_open_counter = 0;
+        self.min_closed_counter = 0;
+        self.held_gain = 1.0;
+        self.close_level = 0.0;
+        self.state_durations = [0; 4];
+        self.clock = 0;
+        self.transitions = [0; GATE_TRANSITION_BUCKETS];
+        self.transition_bucket = 0;
+        self.warned = false;
+        if let Some(noise) = self.comfort_noise.as_mut() {
+            *noise = ComfortNoise::new(noise.level, self.comfort_noise_seed);
+        }
+        if let Some(weighting) = self.detector_weighting.as_mut() {
+            weighting.reset();
+        }
+    }
 }
 
-// ============================================================================
-// SystemAudioProcessor — combines all three into one `process(&mut [f32])`
-// ============================================================================
+impl AudioStage for NoiseGate {
+    fn process(&mut self, samples: &mut [f32]) {
+        NoiseGate::process(self, samples);
+    }
 
-pub struct SystemAudioProcessor {
-    compressor: SpeechCompressor,
-    normalizer: RmsNormalizer,
+    fn reset(&mut self) {
+        NoiseGate::reset(self);
+    }
+}
+
+/// How `StereoNoiseGate` combines the two channels into one detector input.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum StereoLink {
+    /// Louder channel, sample by sample: a source panned hard to either
+    /// side keeps the gate open
+    #[default]
+    Max,
+    /// Mid signal `(l + r) / 2`; out-of-phase content partly cancels
+    Sum,
+}
+
+impl StereoLink {
+    #[inline]
+    fn detect(self, left: f32, right: f32) -> f32 {
+        match self {
+            StereoLink::Max => if left.abs() >= right.abs() { left } else { right },
+            StereoLink::Sum => 0.5 * (left + right),
+        }
+    }
+}
+
+/// Noise gate for a stereo pair: one detector and one gain for both
+/// channels, so the image doesn't lurch sideways when a quieter channel
+/// would close ahead of the other. Configure thresholds, mode etc. through
+/// `gate_mut`. Comfort noise is not applied on this path.
+pub struct StereoNoiseGate {
     gate: NoiseGate,
+    link: StereoLink,
 }
 
-impl SystemAudioProcessor {
+impl StereoNoiseGate {
     pub fn new() -> Self {
-        Self {
-            compressor: SpeechCompressor::new(),
-            normalizer: RmsNormalizer::new(),
-            gate: NoiseGate::new(),
+        Self::with_gate(NoiseGate::new(), StereoLink::default())
+    }
+
+    /// Link an already configured gate.
+    pub fn with_gate(gate: NoiseGate, link: StereoLink) -> Self {
+        Self { gate, link }
+    }
+
+    pub fn set_link(&mut self, link: StereoLink) {
+        self.link = link;
+    }
+
+    pub fn gate(&self) -> &NoiseGate {
+        &self.gate
+    }
+
+    pub fn gate_mut(&mut self) -> &mut NoiseGate {
+        &mut self.gate
+    }
+
+    /// Gate both channels in-place with a shared gain.
+    /// Panics if the lengths differ.
+    pub fn process(&mut self, left: &mut [f32], right: &mut [f32]) {
+        assert_eq!(left.len(), right.len(), "StereoNoiseGate: channel lengths differ");
+        for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+            let gain = self.gate.next_gain(self.link.detect(*l, *r));
+            if !self.gate.bypass {
+                *l *= gain;
+                *r *= gain;
+            }
         }
     }
 
-    /// Process audio in-place: compress → normalize → gate.
-    /// Same API as the old `AutoGainControl::process`.
-    pub fn process(&mut self, samples: &mut [f32]) {
-        self.compressor.process(samples);
-        self.normalizer.process(samples);
-        self.gate.process(samples);
+    pub fn reset(&mut self) {
+        self.gate.reset();
+    }
+}
+
+impl Default for StereoNoiseGate {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 // ============================================================================
-// Tests
+// SystemAudioProcessor — combines all three into one `process(&mut [f32])`
 // ============================================================================
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Capture rate the whole chain is tuned for
+const PIPELINE_RATE: u32 = 48_000;
 
-    fn make_sine(freq: f32, amplitude: f32, sample_rate: f32, num_samples: usize) -> Vec<f32> {
-        (0..num_samples)
-            .map(|i| amplitude * (2.0 * std::f32::consts::PI * freq * i as f32 / sample_rate).sin())
-            .collect()
-    }
+/// Per-frame diagnostics from `SystemAudioProcessor::process_reporting`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameInfo {
+    /// The gate held the whole frame closed (output is floor/comfort noise)
+    pub gated: bool,
+    /// Output peak, linear
+    pub peak: f32,
+    /// Output RMS, linear
+    pub rms: f32,
+    /// Mean compressor gain reduction over the frame in dB (0 when the
+    /// compressor didn't run)
+    pub gain_reduction_db: f32,
+}
+
+/// Level window for the adaptive bypass: ~1s at 48kHz
+const ADAPTIVE_BYPASS_WINDOW: f32 = 48_000.0;
+/// Input within this many dB of the normalizer target counts as leveled,
+/// unless the normalizer has its own tolerance band
+const ADAPTIVE_BYPASS_LEVEL_TOLERANCE_DB: f32 = 3.0;
+/// Maximum crest factor (peak / RMS, ~14 dB) for input to count as leveled
+const ADAPTIVE_BYPASS_MAX_CREST: f32 = 5.0;
+/// Blend toward/away from passthrough: ~500ms at 48kHz
+/// alpha = 1 - exp(-1 / (48000 * 0.5)) ≈ 0.0000417
+const ADAPTIVE_BYPASS_MIX_COEFF: f32 = 0.0000417;
+
+/// Measures the raw input and fades the processor toward passthrough while
+/// it is already well leveled (long-term RMS near the normalizer's target,
+/// low crest), where compressing and re-normalizing would only add pumping.
+struct AdaptiveBypass {
+    /// Long-term mean-square of the input (0 = nothing seen yet)
+    power: f32,
+    /// Peak over roughly the same window, decaying exponentially
+    peak: f32,
+    /// Share of dry input in the output: 0 = fully processed, 1 = passthrough
+    mix: f32,
+    /// Unprocessed copy of the current block
+    dry: Vec<f32>,
+}
 
-    fn rms(samples: &[f32]) -> f32 {
-        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+impl AdaptiveBypass {
+    fn new() -> Self {
+        Self { power: 0.0, peak: 0.0, mix: 0.0, dry: Vec::new() }
     }
 
-    fn crest_factor(samples: &[f32]) -> f32 {
-        let peak = samples.iter().map(|s| s.abs()).fold(0.0f32, f32::max);
-        let r = rms(samples);
-        if r > 0.0 { peak / r } else { 0.0 }
+    /// Keep a dry copy of the block for `blend` and, unless `hold` is set
+    /// (frozen processor), measure it.
+    fn observe(&mut self, samples: &[f32], hold: bool) {
+        self.dry.clear();
+        self.dry.extend_from_slice(samples);
+        if hold || samples.is_empty() {
+            return;
+        }
+        let block_power = samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32;
+        let block_peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        let decay = (-(samples.len() as f32) / ADAPTIVE_BYPASS_WINDOW).exp();
+        if self.power == 0.0 {
+            self.power = block_power;
+        } else {
+            self.power += (1.0 - decay) * (block_power - self.power);
+        }
+        self.peak = block_peak.max(self.peak * decay);
     }
 
-    // --- SpeechCompressor tests ---
+    /// Whether the measured input needs no leveling by `normalizer`.
+    fn leveled(&self, normalizer: &RmsNormalizer) -> bool {
+        let level = self.power.sqrt();
+        if level <= 0.0 {
+            return false;
+        }
+        let tolerance_db = normalizer.target_tolerance_db.unwrap_or(ADAPTIVE_BYPASS_LEVEL_TOLERANCE_DB);
+        (20.0 * (level / normalizer.target_rms()).log10()).abs() <= tolerance_db
+            && self.peak / level <= ADAPTIVE_BYPASS_MAX_CREST
+    }
 
-    #[test]
+    /// Mix the dry copy back into the processed block, moving the mix
+    /// toward passthrough or full processing per sample. `hold` keeps the
+    /// mix where it is (frozen processor).
+    fn blend(&mut self, samples: &mut [f32], hold: bool, normalizer: &RmsNormalizer) {
+        let target = if self.leveled(normalizer) { 1.0 } else { 0.0 };
+        for (out, &dry) in samples.iter_mut().zip(self.dry.iter()) {
+            if !hold {
+                self.mix += ADAPTIVE_BYPASS_MIX_COEFF * (target - self.mix);
+            }
+            *out += self.mix * (dry - *out);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.power = 0.0;
+        self.peak = 0.0;
+        self.mix = 0.0;
+    }
+}
+
+/// Side channel of `SystemAudioProcessor::process_mid_side`: its own
+/// compressor, the mid level gain it follows, and scratch reused between
+/// calls
+struct MidSideState {
+    side: SpeechCompressor,
+    /// Mid level gain at the end of the last block, ramped from on the side
+    linked_gain: f32,
+    buffers: MidSideBuffers,
+    /// Copy of the input while bypassed, restored after tracking
+    dry: Vec<f32>,
+    /// Unprocessed side while adaptive bypass is on, blended back in
+    side_dry: Vec<f32>,
+}
+
+impl MidSideState {
+    fn new() -> Self {
+        let side = SpeechCompressor::new();
+        side.params().set_ratio(SIDE_COMPRESSION_RATIO);
+        Self { side, linked_gain: 1.0, buffers: MidSideBuffers::new(), dry: Vec::new(), side_dry: Vec::new() }
+    }
+}
+
+/// A reorderable stage of `SystemAudioProcessor`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StageKind {
+    Compressor,
+    Saturator,
+    Normalizer,
+    Gate,
+}
+
+/// Compress → saturate → normalize → gate
+const DEFAULT_STAGE_ORDER: [StageKind; 4] =
+    [StageKind::Compressor, StageKind::Saturator, StageKind::Normalizer, StageKind::Gate];
+
+/// Timing feature: weight of the newest call in the rolling average
+/// (~20 calls, 200ms of 10ms blocks)
+#[cfg(feature = "timing")]
+const TIMING_AVG_COEFF: f64 = 0.05;
+
+/// Wall-clock cost of recent `SystemAudioProcessor::process` calls
+#[cfg(feature = "timing")]
+#[derive(Default)]
+struct ProcessTiming {
+    last_micros: f64,
+    avg_micros: f64,
+    calls: u64,
+}
+
+#[cfg(feature = "timing")]
+impl ProcessTiming {
+    fn record(&mut self, elapsed: Duration) {
+        self.last_micros = elapsed.as_secs_f64() * 1e6;
+        // The first call seeds the average instead of ramping up from 0
+        self.avg_micros = if self.calls == 0 {
+            self.last_micros
+        } else {
+            self.avg_micros + TIMING_AVG_COEFF * (self.last_micros - self.avg_micros)
+        };
+        self.calls += 1;
+    }
+}
+
+pub struct SystemAudioProcessor {
+    /// Negate the input before the chain (phase-inverted capture paths)
+    invert_polarity: bool,
+    /// Optional coarse gain ahead of the whole chain (`None` = off)
+    input_trim: Option<InputTrim>,
+    /// Optional fade toward passthrough for already-leveled input (`None` = off)
+    adaptive_bypass: Option<AdaptiveBypass>,
+    compressor: SpeechCompressor,
+    /// Optional warmth for monitoring; zero drive (off) by default
+    saturator: Saturator,
+    normalizer: RmsNormalizer,
+    gate: NoiseGate,
+    /// Per-stage enables. Disabled stages still track their input so
+    /// re-enabling picks up with a settled detector instead of jumping.
+    compressor_enabled: bool,
+    normalizer_enabled: bool,
+    gate_enabled: bool,
+    /// When set, the chain runs on `bypass_scratch` and the input is left
+    /// untouched, so every stage's envelope stays warm
+    bypass: bool,
+    bypass_scratch: Vec<f32>,
+    /// When set, every stage applies its current gain without adapting
+    frozen: bool,
+    /// Shared silence threshold, if calibrated (kept for a later-enabled trim)
+    noise_floor: Option<NoiseFloor>,
+    /// Stages in the order they run; stages not listed are skipped
+    order: Vec<StageKind>,
+    /// Rate of `process_resampled` output
+    output_rate: u32,
+    /// Final anti-aliased rate conversion (`None` when `output_rate` is 48kHz)
+    output_resampler: Option<Resampler>,
+    /// Side-channel compressor and scratch, created by the first
+    /// `process_mid_side` call
+    mid_side: Option<MidSideState>,
+    #[cfg(feature = "timing")]
+    timing: ProcessTiming,
+}
+
+impl SystemAudioProcessor {
+    pub fn new() -> Self {
+        Self::build(PIPELINE_RATE, None)
+    }
+
+    /// Processor whose `process_resampled` delivers `output_rate` audio
+    /// (e.g. 16000 for the recognizer) from 48kHz input, through a
+    /// polyphase low-pass resampler. Fractional remainders are carried
+    /// between calls. `process` itself always stays at 48kHz. Errors if
+    /// `output_rate` is 0.
+    pub fn with_output_rate(output_rate: u32) -> Result<Self> {
+        let output_resampler = (output_rate != PIPELINE_RATE)
+            .then(|| Resampler::new(PIPELINE_RATE as f64, output_rate as f64))
+            .transpose()?;
+        Ok(Self::build(output_rate, output_resampler))
+    }
+
+    fn build(output_rate: u32, output_resampler: Option<Resampler>) -> Self {
+        Self {
+            invert_polarity: false,
+            input_trim: None,
+            adaptive_bypass: None,
+            compressor: SpeechCompressor::new(),
+            saturator: Saturator::new(),
+            normalizer: RmsNormalizer::new(),
+            gate: NoiseGate::new(),
+            compressor_enabled: true,
+            normalizer_enabled: true,
+            gate_enabled: true,
+            bypass: false,
+            bypass_scratch: Vec::new(),
+            frozen: false,
+            noise_floor: None,
+            order: DEFAULT_STAGE_ORDER.to_vec(),
+            output_rate,
+            output_resampler,
+            mid_side: None,
+            #[cfg(feature = "timing")]
+            timing: ProcessTiming::default(),
+        }
+    }
+
+    /// Processor running only the listed stages, in the given order, e.g.
+    /// `[Gate, Compressor, Normalizer]` so noise is removed before the
+    /// compressor and normalizer can lift it. See `set_order`.
+    pub fn with_order(order: &[StageKind]) -> Result<Self> {
+        let mut processor = Self::new();
+        processor.set_order(order)?;
+        Ok(processor)
+    }
+
+    /// Set which stages run and in what order. Each stage may appear at
+    /// most once; stages left out are skipped entirely (unlike a disabled
+    /// stage, their detectors don't keep tracking).
+    pub fn set_order(&mut self, order: &[StageKind]) -> Result<()> {
+        for (i, kind) in order.iter().enumerate() {
+            ensure!(!order[..i].contains(kind), "{:?} appears more than once in the stage order", kind);
+        }
+        self.order = order.to_vec();
+        Ok(())
+    }
+
+    pub fn order(&self) -> &[StageKind] {
+        &self.order
+    }
+
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    /// Let audio pass untouched while the whole chain keeps processing a
+    /// scratch copy, so every envelope is warm when bypass is lifted.
+    pub fn set_bypass(&mut self, bypass: bool) {
+        self.bypass = bypass;
+    }
+
+    /// Negate every sample, for capture paths that deliver phase-inverted
+    /// audio (mixing it with the AEC reference would otherwise add echo
+    /// instead of cancelling it; the reference side has
+    /// `ReferenceConfig::invert_polarity`). Applied ahead of the chain, so
+    /// bypassed audio is left as it came. Off by default.
+    pub fn set_invert_polarity(&mut self, invert: bool) {
+        self.invert_polarity = invert;
+    }
+
+    /// Coarse input trim ahead of the chain: slowly lifts a very quiet
+    /// capture into the compressor's working range so it can actually
+    /// compress, rather than leaving all the gain to the normalizer. Off
+    /// by default; turning it off drops its learned gain.
+    pub fn set_input_trim_enabled(&mut self, enabled: bool) {
+        match (enabled, self.input_trim.is_some()) {
+            (true, false) => {
+                let mut trim = InputTrim::new();
+                if let Some(floor) = self.noise_floor {
+                    trim.set_noise_floor(floor);
+                }
+                self.input_trim = Some(trim);
+            }
+            (false, true) => self.input_trim = None,
+            _ => {}
+        }
+    }
+
+    /// Adaptive bypass: while the input is already well leveled (about a
+    /// second of RMS within 3 dB of the normalizer's target, or inside its
+    /// tolerance band if one is set with `set_target_tolerance_db`, and
+    /// crest factor below ~14 dB, e.g. clean wideband audio), fade smoothly
+    /// toward passing it through, since compressing it again only degrades
+    /// it. Phone-codec or quiet, peaky input stays fully processed. Off by
+    /// default; the stages keep running underneath either way.
+    pub fn set_adaptive_bypass_enabled(&mut self, enabled: bool) {
+        match (enabled, self.adaptive_bypass.is_some()) {
+            (true, false) => self.adaptive_bypass = Some(AdaptiveBypass::new()),
+            (false, true) => self.adaptive_bypass = None,
+            _ => {}
+        }
+    }
+
+    /// Leave input within `tolerance_db` of the normalizer's target
+    /// unnormalized (see `RmsNormalizer::set_target_tolerance_db`); when
+    /// set, the same band replaces adaptive bypass's default 3 dB.
+    pub fn set_target_tolerance_db(&mut self, tolerance_db: Option<f32>) -> Result<()> {
+        self.normalizer.set_target_tolerance_db(tolerance_db)
+    }
+
+    /// Current share of unprocessed input in the output (0 = fully
+    /// processed, 1 = passthrough; 0 when adaptive bypass is off).
+    pub fn adaptive_bypass_mix(&self) -> f32 {
+        self.adaptive_bypass.as_ref().map_or(0.0, |bypass| bypass.mix)
+    }
+
+    /// Drive every silence decision in the chain from one calibrated noise
+    /// floor (RMS, dBFS), e.g. measured with a `LevelHistogram`: the
+    /// normalizer holds its gain and the gate closes at the same level
+    /// (reopening 4 dB above it), and the input trim stops adapting there.
+    /// Replaces the stages' separate defaults and any gate thresholds set
+    /// earlier. Must be below 0.
+    pub fn set_noise_floor(&mut self, floor_db: f32) -> Result<()> {
+        let floor = NoiseFloor::from_db(floor_db)?;
+        self.normalizer.set_noise_floor(floor);
+        self.gate.set_noise_floor(floor);
+        if let Some(trim) = self.input_trim.as_mut() {
+            trim.set_noise_floor(floor);
+        }
+        self.noise_floor = Some(floor);
+        Ok(())
+    }
+
+    /// Link the normalizer to an upstream gain, typically the legacy
+    /// `AutoGainControl` running ahead of this processor
+    /// (`agc.gain_link()`), so the two don't oscillate against each other.
+    /// See `RmsNormalizer::set_upstream_gain`. `None` unlinks.
+    pub fn set_upstream_gain(&mut self, link: Option<GainLink>) {
+        self.normalizer.set_upstream_gain(link);
+    }
+
+    /// Current input trim gain (linear; 1.0 when the trim is off).
+    pub fn input_trim_gain(&self) -> f32 {
+        self.input_trim.as_ref().map_or(1.0, InputTrim::gain)
+    }
+
+    /// Hold all adaptation during audio known to be garbage (a device
+    /// switch, a notification sound): every stage keeps applying its
+    /// current gain, but envelopes, level detectors and the gate state stop
+    /// updating. Unfreezing resumes from the held state, so the transient
+    /// can't corrupt the converged gains. Disabled stages stop tracking.
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    pub fn set_compressor_enabled(&mut self, enabled: bool) {
+        self.compressor_enabled = enabled;
+    }
+
+    /// Live handle on the compressor's threshold/ratio/makeup.
+    pub fn compressor_params(&self) -> CompressorParams {
+        self.compressor.params()
+    }
+
+    pub fn set_normalizer_enabled(&mut self, enabled: bool) {
+        self.normalizer_enabled = enabled;
+    }
+
+    pub fn set_gate_enabled(&mut self, enabled: bool) {
+        self.gate_enabled = enabled;
+    }
+
+    /// Combined linear gain of the level stages (input trim, normalizer and
+    /// gate) at the last processed sample; 1.0 in bypass. Lets a linked
+    /// channel, like a mid/side pair's side, follow this chain's leveling
+    /// and gating without measuring itself.
+    pub fn level_gain(&self) -> f32 {
+        if self.bypass {
+            return 1.0;
+        }
+        let trim = self.input_trim.as_ref().map_or(1.0, InputTrim::gain);
+        let normalizer = if self.normalizer_enabled && !self.normalizer.bypass
+            && self.order.contains(&StageKind::Normalizer)
+        {
+            self.normalizer.current_gain
+        } else {
+            1.0
+        };
+        let gate = if self.gate_enabled && !self.gate.bypass && self.order.contains(&StageKind::Gate) {
+            self.gate.held_gain
+        } else {
+            1.0
+        };
+        trim * normalizer * gate
+    }
+
+    /// The gate, for reading its state (it keeps running while disabled).
+    pub fn gate(&self) -> &NoiseGate {
+        &self.gate
+    }
+
+    pub fn set_gate_mode(&mut self, mode: GateMode) {
+        self.gate.set_mode(mode);
+    }
+
+    /// Fill the closed gate with comfort noise; see
+    /// `NoiseGate::set_comfort_noise`.
+    pub fn set_comfort_noise(&mut self, level_db: Option<f32>) {
+        self.gate.set_comfort_noise(level_db);
+    }
+
+    /// Fix the comfort noise seed, so two processors seeded alike produce
+    /// identical output.
+    pub fn seed_comfort_noise(&mut self, seed: u64) {
+        self.gate.seed_comfort_noise(seed);
+    }
+
+    /// Soft saturation between the compressor and the normalizer's peak
+    /// ceiling, for a warmer monitoring feed. 0 (the default) turns it off;
+    /// leave it off for STT. Must be ≥ 0.
+    pub fn set_saturation_drive(&mut self, drive: f32) -> Result<()> {
+        self.saturator.set_drive(drive)
+    }
+
+    /// Process audio in-place: compress → saturate (if enabled) → normalize
+    /// → gate, or the order given to `set_order`.
+    /// Same API as the old `AutoGainControl::process`.
+    /// With the `timing` feature, each call's wall-clock time is recorded
+    /// (see `avg_process_micros`).
+    pub fn process(&mut self, samples: &mut [f32]) {
+        #[cfg(feature = "timing")]
+        let start = Instant::now();
+        self.process_reporting(samples);
+        #[cfg(feature = "timing")]
+        self.timing.record(start.elapsed());
+    }
+
+    /// `pre_emphasis.process` then `process`, bit-identically, for the
+    /// capture loop. When the compressor is the first stage to touch the
+    /// audio (the default chain, with no input trim, adaptive bypass or
+    /// polarity invert, and not bypassed or frozen) the filter runs inside
+    /// the compressor's pass instead of walking the buffer twice;
+    /// otherwise the two run in turn.
+    pub fn process_pre_emphasized(&mut self, pre_emphasis: &mut PreEmphasis, samples: &mut [f32]) {
+        let fusable = !self.bypass && !self.frozen && !self.invert_polarity
+            && self.input_trim.is_none() && self.adaptive_bypass.is_none()
+            && self.compressor_enabled && self.order.first() == Some(&StageKind::Compressor);
+        if !fusable {
+            pre_emphasis.process(samples);
+            self.process(samples);
+            return;
+        }
+        #[cfg(feature = "timing")]
+        let start = Instant::now();
+        self.compressor.process_pre_emphasized(pre_emphasis, samples);
+        for i in 1..self.order.len() {
+            self.run_stage(self.order[i], samples);
+        }
+        #[cfg(feature = "timing")]
+        self.timing.record(start.elapsed());
+    }
+
+    /// Wall-clock time of the last `process` call, in µs (0 before the
+    /// first).
+    #[cfg(feature = "timing")]
+    pub fn last_process_micros(&self) -> f64 {
+        self.timing.last_micros
+    }
+
+    /// Rolling average of `process` wall-clock time over roughly the last
+    /// 20 calls, in µs (0 before the first), for a diagnostics readout.
+    /// Compare against the block duration (10ms for 480 samples) to see
+    /// the headroom left.
+    #[cfg(feature = "timing")]
+    pub fn avg_process_micros(&self) -> f64 {
+        self.timing.avg_micros
+    }
+
+    /// Process in-place and return diagnostics for the output frame: whether
+    /// the gate muted it, its level, and the compressor's gain reduction.
+    /// In bypass the output is the input, so nothing counts as gated or
+    /// compressed.
+    pub fn process_reporting(&mut self, samples: &mut [f32]) -> FrameInfo {
+        if self.bypass {
+            let mut scratch = std::mem::take(&mut self.bypass_scratch);
+            scratch.clear();
+            scratch.extend_from_slice(samples);
+            self.run_chain(&mut scratch);
+            self.bypass_scratch = scratch;
+            return FrameInfo {
+                peak: samples.iter().fold(0.0f32, |m, s| m.max(s.abs())),
+                rms: rms(samples),
+                ..Default::default()
+            };
+        }
+
+        let closed = |gate: &NoiseGate| gate.state_durations()[GateState::Closed as usize];
+        let closed_before = closed(&self.gate);
+        self.run_chain(samples);
+
+        let gate_ran = self.gate_enabled && self.order.contains(&StageKind::Gate);
+        let compressor_ran = self.compressor_enabled && self.order.contains(&StageKind::Compressor);
+        FrameInfo {
+            gated: gate_ran && !samples.is_empty()
+                && closed(&self.gate) - closed_before == samples.len() as u64,
+            peak: samples.iter().fold(0.0f32, |m, s| m.max(s.abs())),
+            rms: rms(samples),
+            gain_reduction_db: if compressor_ran { self.compressor.last_gain_reduction_db() } else { 0.0 },
+        }
+    }
+
+    /// Process interleaved L/R stereo in-place as mid/side: the mid (the
+    /// centre, usually the voice) runs through this processor's chain and
+    /// the side (width, ambience) through a separate, gentler 2:1
+    /// compressor, then follows the mid's trim, normalizer and gate gain
+    /// (`level_gain`), so the centre is leveled hard without squashing the
+    /// image. Polarity inversion, bypass, freeze, adaptive bypass and
+    /// removing the compressor from the stage order apply to both channels.
+    /// For a full chain per channel use `MidSideProcessor`. Panics if the
+    /// length is odd.
+    pub fn process_mid_side(&mut self, interleaved: &mut [f32]) {
+        let mut state = self.mid_side.take().unwrap_or_else(MidSideState::new);
+        if self.bypass {
+            state.dry.clear();
+            state.dry.extend_from_slice(interleaved);
+        }
+        let MidSideState { side, linked_gain, buffers, side_dry, .. } = &mut state;
+        buffers.process(interleaved, |mid_buf, side_buf| {
+            let mix_before = self.adaptive_bypass_mix();
+            if self.adaptive_bypass.is_some() {
+                side_dry.clear();
+                side_dry.extend_from_slice(side_buf);
+            }
+            self.process(mid_buf);
+            let compressor_ran = self.compressor_enabled && self.order.contains(&StageKind::Compressor);
+            if self.bypass || !compressor_ran {
+                side.track(side_buf);
+            } else if self.frozen {
+                side.apply_held_gain(side_buf);
+            } else {
+                side.process(side_buf);
+            }
+            let gain = self.level_gain();
+            apply_gain_ramp(side_buf, *linked_gain, gain);
+            *linked_gain = gain;
+            if self.adaptive_bypass.is_some() {
+                // Follow the mid's fade toward passthrough
+                let step = (self.adaptive_bypass_mix() - mix_before) / side_buf.len().max(1) as f32;
+                for (i, (out, &dry)) in side_buf.iter_mut().zip(side_dry.iter()).enumerate() {
+                    *out += (mix_before + step * (i + 1) as f32) * (dry - *out);
+                }
+            }
+            if self.invert_polarity && !self.bypass {
+                side_buf.iter_mut().for_each(|s| *s = -*s);
+            }
+        });
+        if self.bypass {
+            interleaved.copy_from_slice(&state.dry);
+        }
+        self.mid_side = Some(state);
+    }
+
+    /// The side-channel compressor of `process_mid_side`, for tuning it
+    /// (created on first use).
+    pub fn side_compressor_mut(&mut self) -> &mut SpeechCompressor {
+        &mut self.mid_side.get_or_insert_with(MidSideState::new).side
+    }
+
+    /// Process 48kHz audio in-place, then return it converted to
+    /// `output_rate`. Output length varies by ±1 sample between calls.
+    pub fn process_resampled(&mut self, samples: &mut [f32]) -> Vec<f32> {
+        self.process(samples);
+        match self.output_resampler.as_mut() {
+            Some(resampler) => resampler.process(samples),
+            None => samples.to_vec(),
+        }
+    }
+
+    /// Process a copy of `input` and return it, for callers holding an
+    /// immutable buffer (e.g. offline processing of a decoded recording).
+    /// Allocates on every call, so this is the non-realtime convenience
+    /// path; the capture loop should use `process` or `process_to`. Every
+    /// stage has the same helper as `AudioStage::process_to_vec`.
+    pub fn process_copy(&mut self, input: &[f32]) -> Vec<f32> {
+        self.process_to_vec(input)
+    }
+
+    fn run_chain(&mut self, samples: &mut [f32]) {
+        if self.invert_polarity {
+            samples.iter_mut().for_each(|s| *s = -*s);
+        }
+        if let Some(bypass) = self.adaptive_bypass.as_mut() {
+            bypass.observe(samples, self.frozen);
+        }
+        if let Some(trim) = self.input_trim.as_mut() {
+            if self.frozen {
+                trim.apply_held_gain(samples);
+            } else {
+                trim.process(samples);
+            }
+        }
+        for i in 0..self.order.len() {
+            self.run_stage(self.order[i], samples);
+        }
+        if let Some(bypass) = self.adaptive_bypass.as_mut() {
+            bypass.blend(samples, self.frozen, &self.normalizer);
+        }
+    }
+
+    fn run_stage(&mut self, kind: StageKind, samples: &mut [f32]) {
+        if self.frozen {
+            self.run_stage_frozen(kind, samples);
+            return;
+        }
+        match kind {
+            StageKind::Compressor if self.compressor_enabled => self.compressor.process(samples),
+            StageKind::Compressor => self.compressor.track(samples),
+            StageKind::Saturator => self.saturator.process(samples),
+            StageKind::Normalizer if self.normalizer_enabled => self.normalizer.process(samples),
+            StageKind::Normalizer => self.normalizer.track(samples),
+            StageKind::Gate if self.gate_enabled => self.gate.process(samples),
+            StageKind::Gate => self.gate.track(samples),
+        }
+    }
+
+    fn run_stage_frozen(&mut self, kind: StageKind, samples: &mut [f32]) {
+        match kind {
+            StageKind::Compressor if self.compressor_enabled => self.compressor.apply_held_gain(samples),
+            StageKind::Saturator => self.saturator.process(samples),
+            StageKind::Normalizer if self.normalizer_enabled => self.normalizer.apply_held_gain(samples),
+            StageKind::Gate if self.gate_enabled => self.gate.apply_held_gain(samples),
+            _ => {}
+        }
+    }
+
+    /// Process a buffer of any length in `block`-sized pieces (480 = 10ms
+    /// at 48kHz), so timing behaviour matches the live capture loop no
+    /// matter how the caller slices its input. `block == 0` processes the
+    /// whole buffer at once.
+    pub fn process_blocked(&mut self, samples: &mut [f32], block: usize) {
+        if block == 0 {
+            self.process(samples);
+            return;
+        }
+        for chunk in samples.chunks_mut(block) {
+            self.process(chunk);
+        }
+    }
+
+    /// Reset every stage (and the output resampler), keeping settings.
+    pub fn reset(&mut self) {
+        if let Some(trim) = self.input_trim.as_mut() {
+            trim.reset();
+        }
+        if let Some(bypass) = self.adaptive_bypass.as_mut() {
+            bypass.reset();
+        }
+        self.compressor.reset();
+        self.saturator.reset();
+        self.normalizer.reset();
+        self.gate.reset();
+        if let Some(resampler) = self.output_resampler.as_mut() {
+            resampler.reset();
+        }
+        if let Some(state) = self.mid_side.as_mut() {
+            state.side.reset();
+            state.linked_gain = 1.0;
+        }
+    }
+}
+
+impl AudioStage for SystemAudioProcessor {
+    fn process(&mut self, samples: &mut [f32]) {
+        SystemAudioProcessor::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        SystemAudioProcessor::reset(self);
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agc::AutoGainControl;
+    use crate::test_signals::{crest_factor, make_impulse_train, make_sine, make_white_noise, peak, rms, tone_level};
+
+    // --- SpeechCompressor tests ---
+
+    #[test]
     fn test_compressor_attenuates_loud_signal() {
         let mut comp = SpeechCompressor::new();
 
@@ -324,96 +2705,1186 @@ mod tests {
         comp.process(&mut loud);
         let rms_after = rms(&loud);
 
-        // Compressor should reduce the level of loud signal (gain < 1.0)
-        assert!(rms_after < rms_before,
-            "Compressor should attenuate signal above threshold: before={:.4}, after={:.4}",
-            rms_before, rms_after);
+        // Compressor should reduce the level of loud signal (gain < 1.0)
+        assert!(rms_after < rms_before,
+            "Compressor should attenuate signal above threshold: before={:.4}, after={:.4}",
+            rms_before, rms_after);
+    }
+
+    #[test]
+    fn test_compressor_process_frame_reports_gain() {
+        let mut comp = SpeechCompressor::new();
+        for _ in 0..20 {
+            let mut warmup = make_sine(440.0, 0.5, 48000.0, 480);
+            comp.process(&mut warmup);
+        }
+        let input = make_sine(440.0, 0.5, 48000.0, 480);
+        let mut frame = input.clone();
+        let gain = comp.process_frame(&mut frame);
+        assert!(gain < 1.0, "Loud frame should report gain reduction: {}", gain);
+        let applied = rms(&frame) / rms(&input);
+        assert!((applied - gain).abs() < 0.01,
+            "Reported gain should match applied: reported={:.4}, applied={:.4}", gain, applied);
+    }
+
+    #[test]
+    fn test_compressor_no_warmup_transient() {
+        // Loud from sample zero: the detector should read the true level
+        // immediately, not ramp up over the first window
+        let mut signal = make_sine(1000.0, 0.5, 48000.0, 4800);
+        let mut comp = SpeechCompressor::new();
+        let envelope = comp.process_with_envelope(&mut signal);
+        let settled = envelope[4799];
+        for (ms, &gain) in [(4, &envelope[192]), (6, &envelope[288])] {
+            let error_db = 20.0 * (gain / settled).log10();
+            assert!(error_db.abs() < 1.0,
+                "Gain at {}ms should already be within 1 dB of settled: {:.2} dB off", ms, error_db);
+        }
+    }
+
+    #[test]
+    fn test_compressor_envelope_export() {
+        // 0.5s loud, then 1s quiet
+        let mut signal = make_sine(440.0, 0.5, 48000.0, 24000);
+        signal.extend(make_sine(440.0, 0.01, 48000.0, 48000));
+        let mut comp = SpeechCompressor::new();
+        let envelope = comp.process_with_envelope(&mut signal);
+        assert_eq!(envelope.len(), signal.len());
+
+        let loud_min = envelope[..24000].iter().cloned().fold(f32::MAX, f32::min);
+        assert!(loud_min < 0.5, "Envelope should dip while loud: min={:.3}", loud_min);
+        let recovered = envelope[envelope.len() - 1];
+        assert!(recovered > 0.95, "Envelope should return toward 1.0 when quiet: {:.3}", recovered);
+        assert!(envelope[24000..].windows(2).skip(480).all(|w| w[1] >= w[0] - 1e-6),
+            "Envelope should release monotonically after the loud part");
+    }
+
+    #[test]
+    fn test_rms_window_length() {
+        assert!(SpeechCompressor::new().set_rms_window_ms(0.1).is_err());
+        assert!(RmsNormalizer::new().set_rms_window_ms(f32::NAN).is_err());
+        assert!(NoiseGate::new().set_rms_window_ms(-5.0).is_err());
+        assert!(NoiseGate::new().set_rms_window_ms(30.0).is_ok());
+        assert!(SpeechCompressor::new().set_rms_window_ms(f32::INFINITY).is_err());
+        assert!(RmsNormalizer::new().set_rms_window_ms(1e12).is_err());
+        assert!(NoiseGate::new().set_rms_window_ms(MAX_RMS_WINDOW_MS + 1.0).is_err());
+        assert!(NoiseGate::new().set_rms_window_ms(MAX_RMS_WINDOW_MS).is_ok());
+
+        // Bursty input: 4ms loud / 16ms quiet
+        let burst = make_sine(1000.0, 0.6, 48000.0, 48000);
+        let bursty: Vec<f32> = burst
+            .iter()
+            .enumerate()
+            .map(|(i, &s)| if i % 960 < 192 { s } else { s * 0.05 })
+            .collect();
+
+        let variance = |window_ms: f32| {
+            let mut comp = SpeechCompressor::new();
+            comp.set_rms_window_ms(window_ms).unwrap();
+            let envelope = comp.process_with_envelope(&mut bursty.clone());
+            let settled = &envelope[24000..];
+            let mean = settled.iter().sum::<f32>() / settled.len() as f32;
+            settled.iter().map(|g| (g - mean).powi(2)).sum::<f32>() / settled.len() as f32
+        };
+        let short = variance(2.0);
+        let long = variance(50.0);
+        assert!(long < short * 0.5,
+            "Longer window should smooth the gain: var(2ms)={:.5}, var(50ms)={:.5}", short, long);
+    }
+
+    #[test]
+    fn test_compressor_makeup_gain() {
+        let input = make_sine(440.0, 0.5, 48000.0, 9600);
+
+        let mut plain = input.clone();
+        SpeechCompressor::new().process(&mut plain);
+
+        let mut comp = SpeechCompressor::new();
+        comp.set_makeup_db(6.0);
+        let mut made_up = input.clone();
+        comp.process(&mut made_up);
+
+        let ratio = rms(&made_up[4800..]) / rms(&plain[4800..]);
+        assert!((ratio - 2.0).abs() < 0.02, "+6dB makeup should double the level: {:.3}", ratio);
+    }
+
+    #[test]
+    fn test_compressor_auto_makeup_level_matches() {
+        // Steady sine at -10 dBFS RMS: halfway between -20 dB threshold and 0 dBFS
+        let amplitude = 10.0f32.powf(-10.0 / 20.0) * 2.0f32.sqrt();
+        let input = make_sine(440.0, amplitude, 48000.0, 9600);
+
+        let mut comp = SpeechCompressor::new();
+        comp.set_auto_makeup(true);
+        let mut output = input.clone();
+        comp.process(&mut output);
+        let diff_db = 20.0 * (rms(&output[4800..]) / rms(&input[4800..])).log10();
+        assert!(diff_db.abs() < 0.5, "Auto-makeup should level-match: {:.2}dB", diff_db);
+
+        // Makeup follows threshold changes: -30 dB threshold matches at -15 dBFS
+        let amplitude = 10.0f32.powf(-15.0 / 20.0) * 2.0f32.sqrt();
+        let input = make_sine(440.0, amplitude, 48000.0, 9600);
+        let mut comp = SpeechCompressor::new();
+        comp.set_auto_makeup(true);
+        comp.set_threshold_db(-30.0).unwrap();
+        let mut output = input.clone();
+        comp.process(&mut output);
+        let diff_db = 20.0 * (rms(&output[4800..]) / rms(&input[4800..])).log10();
+        assert!(diff_db.abs() < 0.5, "Auto-makeup should track threshold: {:.2}dB", diff_db);
+    }
+
+    #[test]
+    fn test_compressor_params_live_update() {
+        let mut comp = SpeechCompressor::new();
+        let params = comp.params();
+
+        let writer = std::thread::spawn(move || {
+            for i in 0..1000 {
+                // Sweep, then settle on -40 dB
+                params.set_threshold(-20.0 - (i % 20) as f32);
+                params.set_ratio(2.0 + (i % 4) as f32);
+            }
+            params.set_threshold(-40.0);
+            params.set_ratio(8.0);
+        });
+
+        // Audio thread keeps running while the writer sweeps
+        for _ in 0..200 {
+            let mut frame = make_sine(440.0, 0.08, 48000.0, 480);
+            comp.process(&mut frame);
+            assert!(frame.iter().all(|s| s.is_finite()));
+        }
+        writer.join().unwrap();
+
+        // -25 dBFS-ish signal: barely touched at -20 dB, heavily compressed at -40 dB / 8:1
+        let mut gain = 1.0;
+        for _ in 0..20 {
+            let mut frame = make_sine(440.0, 0.08, 48000.0, 480);
+            gain = comp.process_frame(&mut frame);
+        }
+        assert_eq!(comp.threshold_db.value(), -40.0);
+        assert_eq!(comp.ratio.value(), 8.0);
+        assert!(gain < 0.3, "New threshold should take effect: gain={:.3}", gain);
+    }
+
+    #[test]
+    fn test_live_parameter_change_does_not_click() {
+        // Auto-makeup turns the threshold change into a +14 dB makeup jump
+        let mut comp = SpeechCompressor::new();
+        comp.set_auto_makeup(true);
+        comp.set_threshold_db(-10.0).unwrap();
+        let input = make_sine(100.0, 0.3, 48000.0, 48000);
+        let mut output = input.clone();
+        let (before, after) = output.split_at_mut(24000);
+        comp.process(before);
+        comp.params().set_threshold(-40.0);
+        comp.params().set_ratio(8.0);
+        for block in after.chunks_mut(480) {
+            comp.process(block);
+        }
+
+        // Applied gain per sample, away from zero crossings
+        let gain: Vec<Option<f32>> = input.iter().zip(&output)
+            .map(|(&x, &y)| (x.abs() > 0.05).then(|| y / x))
+            .collect();
+        let max_jump = gain.windows(2)
+            .filter_map(|w| Some((w[1]? - w[0]?).abs()))
+            .fold(0.0, f32::max);
+        assert!(max_jump < 0.02, "Gain should ramp, not step: {:.4} in one sample", max_jump);
+        let settled = gain[47000].unwrap();
+        assert!(settled < 0.6, "New settings should take effect: gain {:.3}", settled);
+
+        // The normalizer target ramps the same way. Its gain is smoothed
+        // anyway, so the unramped step shows up as a kink: the gain's slope
+        // jumping from flat to full speed in one sample
+        let mut normalizer = RmsNormalizer::new();
+        let tone = make_sine(100.0, 0.15 * std::f32::consts::SQRT_2, 48000.0, 48000 * 3);
+        normalizer.process(&mut tone[..47998].to_vec());
+        let mut gains = Vec::new();
+        for (i, &x) in tone.iter().enumerate().take(50000).skip(47998) {
+            if i == 48000 {
+                normalizer.set_target_rms(0.3).unwrap();
+            }
+            normalizer.process(&mut [x]);
+            gains.push(normalizer.current_gain);
+        }
+        assert_eq!(normalizer.target_rms(), 0.3);
+        let max_kink = gains.windows(3)
+            .map(|w| (w[2] - 2.0 * w[1] + w[0]).abs())
+            .fold(0.0, f32::max);
+        assert!(max_kink < 1e-5, "Normalizer gain slope should ramp, not step: {:.2e}", max_kink);
+        normalizer.process(&mut tone[50000..].to_vec());
+        assert!((normalizer.current_gain - 2.0).abs() < 0.05,
+            "New target should take effect: gain {:.3}", normalizer.current_gain);
+
+        // Out-of-range targets are rejected
+        assert!(normalizer.set_target_rms(0.0).is_err());
+        assert!(normalizer.set_target_rms(1.5).is_err());
+    }
+
+    #[test]
+    fn test_gain_reduction_history() {
+        let mut comp = SpeechCompressor::new();
+        comp.process_frame(&mut make_sine(440.0, 0.01, 48000.0, 480));
+        assert!(comp.gain_reduction_history().is_empty(), "History should be opt-in");
+
+        comp.set_gain_reduction_history_capacity(20);
+        for _ in 0..10 {
+            comp.process_frame(&mut make_sine(440.0, 0.01, 48000.0, 480));
+        }
+        for _ in 0..15 {
+            comp.process_frame(&mut make_sine(440.0, 0.5, 48000.0, 480));
+        }
+
+        let history = comp.gain_reduction_history();
+        assert_eq!(history.len(), 20);
+        // Oldest 5 quiet blocks rolled off; 5 quiet then 15 loud remain
+        let quiet = history[..5].iter().cloned().fold(0.0f32, f32::max);
+        let loud = history[10..].iter().cloned().fold(f32::MAX, f32::min);
+        assert!(quiet < 0.5, "Quiet blocks should show ~no reduction: {:.2}dB", quiet);
+        assert!(loud > quiet + 6.0, "Loud blocks should show more reduction: {:.2}dB", loud);
+
+        assert!(comp.gain_reduction_history_iter().eq(history.iter().copied()));
+        assert_eq!(comp.gain_reduction_history_iter().last(), Some(comp.last_gain_reduction_db()));
+
+        comp.set_gain_reduction_history_capacity(0);
+        assert!(comp.gain_reduction_history().is_empty());
+        assert_eq!(comp.gain_reduction_history_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_compressor_quiet_signal_passes_through() {
+        let mut comp = SpeechCompressor::new();
+        // Below threshold signal should pass mostly unchanged
+        let mut signal = make_sine(440.0, 0.01, 48000.0, 4800);
+        let rms_before = rms(&signal);
+        comp.process(&mut signal);
+        let rms_after = rms(&signal);
+        // Gain should be ~1.0 (no compression below threshold)
+        assert!((rms_after / rms_before - 1.0).abs() < 0.3,
+            "Quiet signal shouldn't be heavily modified: ratio={:.2}", rms_after / rms_before);
+    }
+
+    #[test]
+    fn test_compressor_soft_knee() {
+        // Verify soft knee provides smooth transition, on the default
+        // compressor's live threshold, ratio and knee
+        let comp = SpeechCompressor::new();
+        let (thresh, ratio) = (comp.threshold_db.value(), comp.ratio.value());
+        let knee_db = comp.knee().width_db(ratio);
+        let gain_below = SpeechCompressor::<f32>::knee_gain_db(thresh - 10.0, thresh, ratio, knee_db);
+        let gain_at_thresh = SpeechCompressor::<f32>::knee_gain_db(thresh, thresh, ratio, knee_db);
+        let gain_above = SpeechCompressor::<f32>::knee_gain_db(thresh + 10.0, thresh, ratio, knee_db);
+
+        assert!(gain_below.abs() < 0.01, "No compression below knee: {}", gain_below);
+        assert!(gain_above < -1.0, "Should compress above knee: {}", gain_above);
+        // At threshold (middle of knee), should have some but not full compression
+        assert!(gain_at_thresh <= 0.0, "Should have some compression at threshold: {}", gain_at_thresh);
+    }
+
+    #[test]
+    fn test_median_detection_ignores_clicks() {
+        // -29 dBFS RMS tone (below threshold) with a 0.5ms click every 50ms
+        let mut input = make_sine(440.0, 0.05, 48000.0, 48000);
+        for start in (0..input.len()).step_by(2400) {
+            input[start..start + 24].fill(0.9);
+        }
+        let reduction = |detection: Detection| {
+            let mut comp = SpeechCompressor::new();
+            comp.set_detection(detection, 8).unwrap();
+            let mut output = input.clone();
+            let blocks = output.chunks_mut(480).map(|block| {
+                comp.process(block);
+                comp.last_gain_reduction_db()
+            });
+            blocks.sum::<f32>() / 100.0
+        };
+        let median = reduction(Detection::Median);
+        let max = reduction(Detection::Max);
+        let mean = reduction(Detection::Mean);
+        assert!(median < 0.5, "Median detection should ignore the clicks: {:.2} dB", median);
+        assert!(max > median + 3.0 && mean > median + 1.0,
+            "median {:.2} dB, mean {:.2} dB, max {:.2} dB", median, mean, max);
+
+        let mut comp = SpeechCompressor::new();
+        assert!(comp.set_detection(Detection::Median, 1).is_err());
+        assert!(comp.set_detection(Detection::Max, 481).is_err());
+        assert!(comp.set_detection(Detection::Mean, 0).is_ok());
+    }
+
+    #[test]
+    fn test_sidechain_highpass_ignores_bass() {
+        // Loud 60 Hz hum under a moderate 1 kHz tone
+        let input: Vec<f32> = make_sine(60.0, 0.5, 48000.0, 48000)
+            .iter()
+            .zip(make_sine(1000.0, 0.1, 48000.0, 48000))
+            .map(|(low, mid)| low + mid)
+            .collect();
+        let reduction = |corner_hz: Option<f32>| {
+            let mut comp = SpeechCompressor::new();
+            comp.set_sidechain_highpass(corner_hz).unwrap();
+            let mut output = input.clone();
+            comp.process(&mut output);
+            comp.last_gain_reduction_db()
+        };
+        let full_band = reduction(None);
+        let highpassed = reduction(Some(200.0));
+        assert!(full_band > 6.0, "Bass should drive the plain compressor: {:.1} dB", full_band);
+        assert!(highpassed < 1.0, "Sidechain HP should ignore the bass: {:.1} dB", highpassed);
+
+        // The output is still full band: the hum passes at unity
+        let mut comp = SpeechCompressor::new();
+        comp.set_sidechain_highpass(Some(200.0)).unwrap();
+        let mut output = input.clone();
+        comp.process(&mut output);
+        let hum_change = tone_level(&output[24000..], 60.0, 48000.0) / tone_level(&input[24000..], 60.0, 48000.0);
+        assert!((hum_change - 1.0).abs() < 0.05, "Hum level changed by {:.3}x", hum_change);
+
+        assert!(comp.set_sidechain_highpass(Some(0.0)).is_err());
+        assert!(comp.set_sidechain_highpass(Some(30000.0)).is_err());
+    }
+
+    #[test]
+    fn test_pre_emphasized_matches_two_passes() {
+        let input: Vec<f32> = make_white_noise(0.4, 21, 48000)
+            .iter()
+            .zip(make_sine(300.0, 0.3, 48000.0, 48000))
+            .map(|(n, s)| n + s)
+            .collect();
+
+        let (mut pre_a, mut comp_a) = (PreEmphasis::new(), SpeechCompressor::new());
+        let (mut pre_b, mut comp_b) = (PreEmphasis::new(), SpeechCompressor::new());
+        comp_a.enable_gain_reduction_history();
+        comp_b.enable_gain_reduction_history();
+        let mut two_pass = input.clone();
+        let mut fused = input;
+        for (a, b) in two_pass.chunks_mut(480).zip(fused.chunks_mut(480)) {
+            pre_a.process(a);
+            comp_a.process(a);
+            comp_b.process_pre_emphasized(&mut pre_b, b);
+        }
+        assert!(two_pass.iter().zip(&fused).all(|(a, b)| a.to_bits() == b.to_bits()));
+        assert_eq!(comp_a.gain_reduction_history(), comp_b.gain_reduction_history());
+    }
+
+    #[test]
+    fn test_processor_pre_emphasized_matches_two_passes() {
+        let input: Vec<f32> = make_white_noise(0.4, 23, 48000)
+            .iter()
+            .zip(make_sine(300.0, 0.3, 48000.0, 48000))
+            .map(|(n, s)| n + s)
+            .collect();
+        // Default chain (fused) and with input trim (two passes inside)
+        for trim in [false, true] {
+            let (mut pre_a, mut proc_a) = (PreEmphasis::new(), SystemAudioProcessor::new());
+            let (mut pre_b, mut proc_b) = (PreEmphasis::new(), SystemAudioProcessor::new());
+            proc_a.set_input_trim_enabled(trim);
+            proc_b.set_input_trim_enabled(trim);
+            let mut two_pass = input.clone();
+            let mut fused = input.clone();
+            for (a, b) in two_pass.chunks_mut(480).zip(fused.chunks_mut(480)) {
+                pre_a.process(a);
+                proc_a.process(a);
+                proc_b.process_pre_emphasized(&mut pre_b, b);
+            }
+            assert!(two_pass.iter().zip(&fused).all(|(a, b)| a.to_bits() == b.to_bits()), "trim {}", trim);
+        }
+    }
+
+    #[test]
+    fn test_compressor_knee_shapes() {
+        // Static curve at the compressor's live threshold, ratio and knee
+        let curve = |comp: &SpeechCompressor, input_db: f32| {
+            let ratio = comp.ratio.value();
+            SpeechCompressor::<f32>::knee_gain_db(input_db, comp.threshold_db.value(), ratio, comp.knee().width_db(ratio))
+        };
+        let slope = |comp: &SpeechCompressor, at: f32| {
+            let d = 0.01;
+            (curve(comp, at + d) - curve(comp, at - d)) / (2.0 * d)
+        };
+        let mut comp = SpeechCompressor::new();
+        let thresh = comp.threshold_db.value();
+        let full_slope = 1.0 / comp.ratio.value() - 1.0;
+
+        // Soft(6.0) is the default: the slope at the threshold is halfway,
+        // and continuous
+        let soft = KneeShape::Soft(6.0);
+        assert_eq!(comp.knee(), soft);
+        assert!((slope(&comp, thresh) - full_slope / 2.0).abs() < 1e-2);
+        assert!((slope(&comp, thresh - 0.05) - slope(&comp, thresh + 0.05)).abs() < 0.02);
+        assert_eq!(slope(&comp, thresh - 4.0), 0.0);
+
+        // Hard: flat right up to the threshold, full ratio right after it
+        comp.set_knee(KneeShape::Hard).unwrap();
+        assert_eq!(curve(&comp, thresh - 0.01), 0.0);
+        assert!(slope(&comp, thresh - 0.05).abs() < 1e-3);
+        assert!((slope(&comp, thresh + 0.05) - full_slope).abs() < 1e-3);
+
+        // Adaptive is wider at 4:1
+        comp.set_knee(KneeShape::Adaptive).unwrap();
+        assert!(slope(&comp, thresh - 4.0) < -1e-3, "9 dB knee starts 4.5 dB below threshold");
+
+        assert!(comp.set_knee(KneeShape::Soft(-1.0)).is_err());
+        assert_eq!(comp.knee(), KneeShape::Adaptive);
+
+        // The live path follows the knee: a tone 1 dB under the threshold
+        // is untouched with a hard knee and reduced inside a 12 dB one
+        let amplitude = 10.0f32.powf((thresh - 1.0) / 20.0) * std::f32::consts::SQRT_2;
+        let settled_gain = |knee| {
+            let mut comp = SpeechCompressor::new();
+            comp.set_knee(knee).unwrap();
+            *comp.process_with_envelope(&mut make_sine(440.0, amplitude, 48000.0, 48000)).last().unwrap()
+        };
+        assert!(settled_gain(KneeShape::Hard) > 0.999, "hard {}", settled_gain(KneeShape::Hard));
+        let soft_db = 20.0 * settled_gain(KneeShape::Soft(12.0)).log10();
+        // Quadratic knee: (1/4 - 1) · 5² / 24 ≈ -0.78 dB
+        assert!((soft_db + 0.78).abs() < 0.15, "soft {:.2} dB", soft_db);
+    }
+
+    #[test]
+    fn test_feedback_topology_compresses_more_gently() {
+        // Quiet, then a steady tone 11 dB over the threshold
+        let mut signal = make_sine(440.0, 0.05, 48000.0, 24000);
+        signal.extend(make_sine(440.0, 0.5, 48000.0, 48000));
+        let envelope = |topology| {
+            let mut comp = SpeechCompressor::with_topology(topology);
+            comp.process_with_envelope(&mut signal.clone())
+        };
+        let forward = envelope(CompressorTopology::FeedForward);
+        let back = envelope(CompressorTopology::FeedBack);
+        assert_eq!(forward, SpeechCompressor::new().process_with_envelope(&mut signal.clone()));
+
+        let reduction_db = |env: &[f32]| -20.0 * env[70000].log10();
+        let (forward_db, back_db) = (reduction_db(&forward), reduction_db(&back));
+        // 4:1 feed-forward takes ~8 dB off; feedback acts like 1.75:1
+        assert!(forward_db - back_db > 2.0,
+            "Feedback should reduce less: forward {:.1} dB, feedback {:.1} dB", forward_db, back_db);
+        assert!(back_db > 1.0, "Feedback should still compress: {:.1} dB", back_db);
+        let mean_diff_db = forward.iter().zip(&back)
+            .map(|(f, b)| (20.0 * (f / b).log10()).abs())
+            .sum::<f32>() / forward.len() as f32;
+        assert!(mean_diff_db > 1.0, "Envelopes should differ: {:.2} dB mean", mean_diff_db);
+    }
+
+    #[test]
+    fn test_compressor_parallel_crest_between_extremes() {
+        // Bursty speech-like signal: loud syllables (100ms rise so the RMS
+        // detector can follow without onset overshoot) separated by quiet passages
+        let burst = make_sine(440.0, 0.6, 48000.0, 9600);
+        let mut signal = Vec::new();
+        for _ in 0..10 {
+            signal.extend(burst.iter().enumerate().map(|(i, s)| s * (i as f32 / 4800.0).min(1.0)));
+            signal.extend(make_sine(440.0, 0.02, 48000.0, 9600));
+        }
+
+        let dry_crest = crest_factor(&signal);
+
+        let mut full = signal.clone();
+        SpeechCompressor::new().process(&mut full);
+        let full_crest = crest_factor(&full);
+
+        let mut parallel = signal.clone();
+        SpeechCompressor::new().process_parallel(&mut parallel, 0.5);
+        let parallel_crest = crest_factor(&parallel);
+
+        assert!(full_crest < parallel_crest && parallel_crest < dry_crest,
+            "Parallel crest should sit between extremes: full={:.2}, parallel={:.2}, dry={:.2}",
+            full_crest, parallel_crest, dry_crest);
+    }
+
+    #[test]
+    fn test_compressor_crest_target() {
+        // Speech-like: a loud 200ms syllable (100ms rise so the RMS detector
+        // follows without onset overshoot) once a second over a quieter bed
+        let syllable = make_sine(440.0, 0.9, 48000.0, 9600);
+        let bed = make_sine(440.0, 0.1, 48000.0, 48000);
+        let mut signal = Vec::new();
+        for _ in 0..20 {
+            signal.extend(syllable.iter().enumerate().map(|(i, s)| s * (i as f32 / 4800.0).min(1.0)));
+            signal.extend(&bed[9600..]);
+        }
+        let target = 2.5;
+        assert!(crest_factor(&signal) > 1.4 * target);
+
+        let mut comp = SpeechCompressor::new();
+        comp.set_target_crest(Some(target)).unwrap();
+        let mut output = signal.clone();
+        comp.process(&mut output);
+        let settled = crest_factor(&output[output.len() - 48000..]);
+        let error_db = 20.0 * (settled / target).log10();
+        assert!(error_db.abs() < 1.5,
+            "Crest should converge near {}: got {:.2} (ratio {:.1})", target, settled, comp.effective_ratio());
+
+        // Already flat material relaxes to 1:1 and passes nearly unchanged
+        let flat = make_sine(440.0, 0.5, 48000.0, 480000);
+        let mut comp = SpeechCompressor::new();
+        comp.set_target_crest(Some(target)).unwrap();
+        let mut output = flat.clone();
+        comp.process(&mut output);
+        assert!(comp.effective_ratio() < 1.1, "Ratio should relax: {:.2}", comp.effective_ratio());
+        let tail = flat.len() - 48000;
+        let level_db = 20.0 * (rms(&output[tail..]) / rms(&flat[tail..])).log10();
+        assert!(level_db.abs() < 1.0, "Flat signal should be left mostly alone: {:.2} dB", level_db);
+
+        assert!(comp.set_target_crest(Some(0.5)).is_err());
+        assert!(comp.set_target_crest(Some(f32::NAN)).is_err());
+    }
+
+    #[test]
+    fn test_compressor_slew_limit() {
+        let limit_db_per_ms = 0.5;
+        let mut comp = SpeechCompressor::new();
+        assert!(comp.set_slew_limit(Some(0.0)).is_err());
+        assert!(comp.set_slew_limit(Some(-0.5)).is_err());
+        assert!(comp.set_slew_limit(Some(f32::NAN)).is_err());
+        assert!(comp.set_slew_limit(Some(f32::INFINITY)).is_err());
+        comp.set_slew_limit(Some(limit_db_per_ms)).unwrap();
+
+        let mut signal = vec![0.0f32; 4800];
+        signal.extend(make_sine(440.0, 0.9, 48000.0, 4800));
+
+        let max_step = limit_db_per_ms / SAMPLES_PER_MS;
+        let mut prev_gain = comp.gain_smooth;
+        for s in signal.iter_mut() {
+            comp.process(std::slice::from_mut(s));
+            let step_db = 20.0 * (comp.gain_smooth / prev_gain).log10();
+            assert!(step_db.abs() <= max_step * 1.001,
+                "Gain step {:.5}dB exceeds limit {:.5}dB", step_db, max_step);
+            prev_gain = comp.gain_smooth;
+        }
+        // Still compresses, just more slowly
+        assert!(comp.gain_smooth < 0.5, "Loud signal should still be compressed: {}", comp.gain_smooth);
+    }
+
+    #[test]
+    fn test_compressor_adaptive_release_reduces_pumping() {
+        // 3s loud passage with brief syllable dips every 150ms
+        let mut signal = Vec::new();
+        for _ in 0..20 {
+            signal.extend(make_sine(440.0, 0.6, 48000.0, 6000));
+            signal.extend(make_sine(440.0, 0.15, 48000.0, 1200));
+        }
+
+        // Total gain movement over the last 2s (after adaptive release has settled)
+        let gain_variation = |comp: &mut SpeechCompressor| -> f32 {
+            let mut total = 0.0;
+            let mut prev = comp.gain_smooth;
+            for (i, &x) in signal.iter().enumerate() {
+                let mut s = x;
+                comp.process(std::slice::from_mut(&mut s));
+                if i >= 48000 {
+                    total += (comp.gain_smooth - prev).abs();
+                }
+                prev = comp.gain_smooth;
+            }
+            total
+        };
+
+        let fixed = gain_variation(&mut SpeechCompressor::new());
+        let mut adaptive_comp = SpeechCompressor::new();
+        adaptive_comp.set_adaptive_release(true);
+        let adaptive = gain_variation(&mut adaptive_comp);
+
+        assert!(adaptive < fixed * 0.7,
+            "Adaptive release should pump less: fixed={:.3}, adaptive={:.3}", fixed, adaptive);
+    }
+
+    // --- RmsNormalizer tests ---
+
+    #[test]
+    fn test_normalizer_amplifies_quiet_signal() {
+        let mut norm = RmsNormalizer::new();
+        // Feed quiet signal for a few seconds to let it converge
+        for _ in 0..200 {
+            let mut frame = make_sine(440.0, 0.005, 48000.0, 480);
+            norm.process(&mut frame);
+        }
+        // After convergence, check output level
+        let mut frame = make_sine(440.0, 0.005, 48000.0, 480);
+        norm.process(&mut frame);
+        let out_rms = rms(&frame);
+        assert!(out_rms > 0.05, "Normalizer should amplify quiet signal: rms={:.4}", out_rms);
+    }
+
+    #[test]
+    fn test_normalizer_loud_onset_after_quiet_passage() {
+        // 5s of quiet-but-above-floor audio winds the gain up to the max
+        let mut norm = RmsNormalizer::new();
+        let mut quiet = make_sine(440.0, 0.003, 48000.0, 48000 * 5);
+        norm.process(&mut quiet);
+        assert!(norm.current_gain > 0.9 * NORM_MAX_GAIN);
+
+        // A loud onset must come down to near the target within ~20ms
+        let mut loud = make_sine(440.0, 0.3, 48000.0, 9600);
+        norm.process(&mut loud);
+        let early = rms(&loud[960..4800]);
+        assert!(early < 2.0 * TARGET_RMS,
+            "Onset over-amplified: {:.3} RMS vs target {:.3}", early, TARGET_RMS);
+    }
+
+    #[test]
+    fn test_normalizer_output_clipped() {
+        let mut norm = RmsNormalizer::new();
+        // Even with max gain, output should never exceed ±1.0
+        for _ in 0..100 {
+            let mut frame = make_sine(440.0, 0.1, 48000.0, 480);
+            norm.process(&mut frame);
+            for &s in &frame {
+                assert!(s.abs() <= 1.0, "Output must be in [-1,1], got {}", s);
+            }
+        }
+    }
+
+    #[test]
+    fn test_normalizer_ceiling() {
+        let mut norm = RmsNormalizer::new();
+        norm.set_ceiling(0.9).unwrap();
+        for _ in 0..100 {
+            // Over-hot input: peaks at 1.5
+            let mut frame = make_sine(440.0, 1.5, 48000.0, 480);
+            norm.process(&mut frame);
+            for &s in &frame {
+                assert!(s.abs() <= 0.9, "Output must stay under ceiling, got {}", s);
+            }
+        }
+    }
+
+    #[test]
+    fn test_normalizer_ceiling_validation() {
+        let mut norm = RmsNormalizer::new();
+        assert!(norm.set_ceiling(0.0).is_err());
+        assert!(norm.set_ceiling(1.1).is_err());
+        assert!(norm.set_ceiling(f32::NAN).is_err());
+        assert!(norm.set_ceiling(1.0).is_ok());
+    }
+
+    #[test]
+    fn test_normalizer_tolerance_band_leaves_leveled_input() {
+        // 0.5 dB under the -16.5 dBFS target
+        let amp = 0.15 * 2.0f32.sqrt() * 10.0f32.powf(-0.5 / 20.0);
+        let input = make_sine(440.0, amp, 48000.0, 48000 * 2);
+        let mut norm = RmsNormalizer::new();
+        norm.set_target_tolerance_db(Some(1.0)).unwrap();
+        let mut output = input.clone();
+        for block in output.chunks_mut(480) {
+            norm.process(block);
+        }
+        // Barely touched while the detector fills, then bit-exact
+        let deviation = output.iter().zip(&input).map(|(o, i)| (o - i).abs()).fold(0.0f32, f32::max);
+        assert!(deviation < 0.005, "In-band input changed by up to {}", deviation);
+        assert_eq!(&output[48000..], &input[48000..], "Settled in-band input should pass untouched");
+
+        // Drifting 6 dB down leaves the band, so it is normalized again
+        for _ in 0..200 {
+            norm.process(&mut make_sine(440.0, amp / 2.0, 48000.0, 480));
+        }
+        assert!(norm.current_gain > 1.7, "Gain should follow the drift: {}", norm.current_gain);
+
+        assert!(norm.set_target_tolerance_db(Some(-1.0)).is_err());
+    }
+
+    #[test]
+    fn test_normalizer_holds_during_silence() {
+        let mut norm = RmsNormalizer::new();
+        // Feed signal to set gain
+        for _ in 0..100 {
+            let mut frame = make_sine(440.0, 0.01, 48000.0, 480);
+            norm.process(&mut frame);
+        }
+        let gain_before = norm.current_gain;
+        // Feed silence
+        let mut silence = vec![0.0f32; 480];
+        norm.process(&mut silence);
+        let gain_after = norm.current_gain;
+        assert!((gain_before - gain_after).abs() < 0.5,
+            "Gain should hold during silence: before={:.2}, after={:.2}", gain_before, gain_after);
+    }
+
+    #[test]
+    fn test_normalizer_calibrate() {
+        let mut norm = RmsNormalizer::new();
+        // Reference at 0.05 RMS, with a silent gap that should be ignored
+        let mut reference = make_sine(440.0, 0.05 * 2.0f32.sqrt(), 48000.0, 24000);
+        reference.extend(vec![0.0; 24000]);
+        let target = norm.calibrate(&reference);
+        assert!((target - 0.05).abs() < 0.002, "Measured target should be ~0.05: {:.4}", target);
+
+        // Arbitrary input should now converge to the calibrated level
+        for _ in 0..400 {
+            let mut frame = make_sine(440.0, 0.01, 48000.0, 480);
+            norm.process(&mut frame);
+        }
+        let mut frame = make_sine(440.0, 0.01, 48000.0, 480);
+        norm.process(&mut frame);
+        let out_rms = rms(&frame);
+        assert!((out_rms - 0.05).abs() < 0.01, "Output should track calibrated target: {:.4}", out_rms);
+    }
+
+    #[test]
+    fn test_normalizer_calibrate_silence_keeps_target() {
+        let mut norm = RmsNormalizer::new();
+        assert_eq!(norm.calibrate(&[0.0; 4800]), TARGET_RMS);
+        assert_eq!(norm.calibrate(&[]), TARGET_RMS);
+    }
+
+    #[test]
+    fn test_normalizer_f64_detector_does_not_drift() {
+        // 3 minutes of loud noise: every detector update rounds the running
+        // window sum, and at f32 the error accumulates
+        let input = make_white_noise(0.5, 7, 48000 * 180);
+        let input_f64: Vec<f64> = input.iter().map(|&s| s as f64).collect();
+
+        let mut norm = RmsNormalizer::new();
+        let mut norm_f64 = RmsNormalizer::new_f64();
+        for (block, block_f64) in input.chunks(480).zip(input_f64.chunks(480)) {
+            norm.track(block);
+            norm_f64.track(block_f64);
+        }
+
+        // Exact RMS over the detector's final window, in f64
+        let tail = &input_f64[input_f64.len() - RMS_WINDOW..];
+        let exact = (tail.iter().map(|s| s * s).sum::<f64>() / RMS_WINDOW as f64).sqrt();
+
+        // Read each detector back by pushing one sample equal to the one
+        // leaving the window, which leaves the true RMS unchanged
+        let leaving = input_f64[input_f64.len() - RMS_WINDOW];
+        let error = (norm.rms.push(leaving as f32) as f64 - exact).abs();
+        let error_f64 = (norm_f64.rms.push(leaving) - exact).abs();
+        assert!(error_f64 < 1e-9, "f64 detector drifted: {:e}", error_f64);
+        assert!(error_f64 < error, "f64 should beat f32: {:e} vs {:e}", error_f64, error);
+    }
+
+    #[test]
+    fn test_f64_paths_match_f32() {
+        let input = make_sine(440.0, 0.3, 48000.0, 9600);
+        let input_f64: Vec<f64> = input.iter().map(|&s| s as f64).collect();
+
+        let mut out = input.clone();
+        SpeechCompressor::new().process(&mut out);
+        RmsNormalizer::new().process(&mut out);
+
+        let mut out_f64 = input_f64;
+        SpeechCompressor::new_f64().process(&mut out_f64);
+        RmsNormalizer::new_f64().process(&mut out_f64);
+
+        for (a, b) in out.iter().zip(&out_f64) {
+            assert!((*a as f64 - b).abs() < 1e-4, "f32 {} vs f64 {}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_normalizer_silence_floor_holds_on_hum() {
+        let mut norm = RmsNormalizer::new();
+        assert!(norm.set_silence_floor(0.0).is_err());
+        assert!(norm.set_silence_floor(-0.1).is_err());
+
+        // 60Hz hum at ~-51 dBFS RMS: above the default floor
+        let hum = make_sine(60.0, 0.004, 48000.0, 48000);
+
+        let mut tracked = hum.clone();
+        let mut default = RmsNormalizer::new();
+        default.process(&mut tracked);
+        assert!(default.current_gain > 2.0, "Default floor should track hum: {}", default.current_gain);
+
+        let mut held = hum.clone();
+        let mut raised = RmsNormalizer::new();
+        raised.set_silence_floor(0.005).unwrap();
+        raised.process(&mut held);
+        assert_eq!(raised.current_gain, 1.0, "Raised floor should hold gain");
+        assert_eq!(held, hum);
+    }
+
+    #[test]
+    fn test_normalizer_lufs_target() {
+        let mut norm = RmsNormalizer::new();
+        assert!(norm.set_target_lufs(Some(3.0)).is_err());
+        norm.set_target_lufs(Some(-23.0)).unwrap();
+
+        // 1kHz at 0.02 peak ≈ -37 LUFS in
+        let mut signal = make_sine(1000.0, 0.02, 48000.0, 48000 * 5);
+        norm.process(&mut signal);
+
+        let mut meter = LufsMeter::new();
+        meter.process(&signal[48000 * 3..]);
+        let out = meter.integrated().unwrap();
+        assert!((out + 23.0).abs() < 1.0, "Output should settle at -23 LUFS, got {:.2}", out);
+    }
+
+    // --- NoiseGate tests ---
+
+    #[test]
+    fn test_gate_zeros_silence() {
+        let mut gate = NoiseGate::new();
+        // Feed enough low-level noise to fill RMS window and let gate close
+        let mut noise: Vec<f32> = (0..48000).map(|_| 0.0001).collect();
+        gate.process(&mut noise);
+        // Last portion should be gated (zeroed)
+        let tail_rms = rms(&noise[40000..]);
+        assert!(tail_rms < 0.0001, "Gate should zero out very quiet signal: rms={:.6}", tail_rms);
+    }
+
+    #[test]
+    fn test_gate_custom_thresholds() {
+        assert!(NoiseGate::with_thresholds(-50.0, -46.0).is_err());
+        assert!(NoiseGate::with_thresholds(-50.0, -50.0).is_err());
+
+        // -54 dBFS closes the default gate but is above a -60/-64 gate
+        let level = vec![0.002f32; 48000];
+        let mut default = NoiseGate::new();
+        default.process(&mut level.clone());
+        assert_eq!(default.state, GateState::Closed);
+        let mut gate = NoiseGate::with_thresholds(-60.0, -64.0).unwrap();
+        gate.process(&mut level.clone());
+        assert_eq!(gate.state, GateState::Open);
+
+        // Between the custom thresholds (-63 dBFS) the gate keeps its state
+        gate.process(&mut vec![0.0001f32; 48000]);
+        assert_eq!(gate.state, GateState::Closed);
+        gate.process(&mut vec![0.0007f32; 48000]);
+        assert_eq!(gate.state, GateState::Closed);
+        gate.process(&mut level.clone());
+        assert_eq!(gate.state, GateState::Open);
+    }
+
+    #[test]
+    fn test_gate_calibrate_from_noise() {
+        // Uniform noise at amplitude 0.01: RMS 0.01/√3 ≈ -49.5 dBFS
+        let noise = make_white_noise(0.01, 11, 48000);
+        let noise_db = 20.0 * rms(&noise).log10();
+        let mut gate = NoiseGate::new();
+        let (open_db, close_db) = gate.calibrate_from_noise(&noise, 10.0).unwrap();
+        assert!((open_db - (noise_db + 10.0)).abs() < 1e-3, "open {:.2} dB, noise {:.2} dB", open_db, noise_db);
+        assert!(close_db < open_db && close_db > noise_db, "close {:.2} dB", close_db);
+
+        // The noise itself keeps the gate closed; speech-level audio opens it
+        gate.process(&mut noise.clone());
+        assert_eq!(gate.state, GateState::Closed);
+        gate.process(&mut make_sine(440.0, 0.1, 48000.0, 4800));
+        assert_eq!(gate.state, GateState::Open);
+
+        assert!(gate.calibrate_from_noise(&[0.0; 480], 10.0).is_err());
+        assert!(gate.calibrate_from_noise(&noise, -3.0).is_err());
+    }
+
+    #[test]
+    fn test_gate_attenuate_mode() {
+        let mut gate = NoiseGate::new();
+        gate.set_mode(GateMode::Attenuate(-20.0));
+        let mut noise: Vec<f32> = vec![0.0001; 48000];
+        gate.process(&mut noise);
+        assert_eq!(gate.state, GateState::Closed);
+        // -20 dB = 0.1x, not silence
+        let tail_rms = rms(&noise[40000..]);
+        assert!((tail_rms / 0.0001 - 0.1).abs() < 0.001,
+            "Attenuate(-20) should duck by 20dB: ratio={:.4}", tail_rms / 0.0001);
+    }
+
+    #[test]
+    fn test_gate_config_floor_db() {
+        let input = make_white_noise(0.0002, 13, 48000);
+        let closed_output = |floor_db: Option<f32>| {
+            let mut gate = NoiseGate::with_config(GateConfig { floor_db, ..Default::default() }).unwrap();
+            let mut out = input.clone();
+            gate.process(&mut out);
+            assert_eq!(gate.state, GateState::Closed);
+            out
+        };
+
+        let floor_gain = 10.0f32.powf(-40.0 / 20.0);
+        let floored = closed_output(Some(-40.0));
+        for (out, inp) in floored[40000..].iter().zip(&input[40000..]) {
+            assert!((out - inp * floor_gain).abs() < 1e-9, "{} vs {}", out, inp * floor_gain);
+        }
+        assert!(closed_output(Some(f32::NEG_INFINITY))[40000..].iter().all(|&s| s == 0.0));
+        assert_eq!(closed_output(Some(f32::NEG_INFINITY)), closed_output(None));
+    }
+
+    #[test]
+    fn test_gate_expand_mode() {
+        let mut gate = NoiseGate::new();
+        gate.set_mode(GateMode::Expand { threshold_db: -40.0, ratio: 2.0 });
+        // Sine at -50 dBFS RMS: 10 dB below threshold
+        let amplitude = 10.0f32.powf(-50.0 / 20.0) * 2.0f32.sqrt();
+        let input = make_sine(440.0, amplitude, 48000.0, 9600);
+        let mut output = input.clone();
+        gate.process(&mut output);
+
+        let atten_db = 20.0 * (rms(&output[4800..]) / rms(&input[4800..])).log10();
+        assert!((atten_db + 10.0).abs() < 0.5,
+            "2:1 expansion 10dB under threshold should attenuate by 10dB: {:.2}dB", atten_db);
+
+        // Above threshold: untouched
+        let loud = make_sine(440.0, 0.1, 48000.0, 4800);
+        let mut output = loud.clone();
+        gate.process(&mut output);
+        assert!((rms(&output[2400..]) / rms(&loud[2400..]) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_gate_keyed_by_speech_probability() {
+        // Quiet (below close threshold) but confidently speech: stays open
+        let mut gate = NoiseGate::new();
+        let quiet = make_sine(440.0, 0.002, 48000.0, 9600);
+        let mut frame = quiet.clone();
+        gate.process_keyed(&mut frame, 0.9);
+        assert_eq!(gate.state, GateState::Open);
+        assert_eq!(frame, quiet);
+
+        // Loud but non-speech: gated after hold + release
+        let mut gate = NoiseGate::new();
+        let mut loud = make_sine(440.0, 0.3, 48000.0, 9600);
+        gate.process_keyed(&mut loud, 0.05);
+        assert_eq!(gate.state, GateState::Closed);
+        assert!(rms(&loud[4800..]) < 1e-6, "Loud non-speech should be gated");
+    }
+
+    #[test]
+    fn test_gate_keyed_probability_hysteresis() {
+        let mut gate = NoiseGate::new();
+        gate.set_probability_thresholds(0.8, 0.3).unwrap();
+        let loud = make_sine(440.0, 0.3, 48000.0, 9600);
+
+        // Between the thresholds the gate holds whatever state it is in,
+        // whatever the level
+        let mut frame = loud.clone();
+        gate.process_keyed(&mut frame, 0.5);
+        assert_eq!(gate.state, GateState::Open);
+        assert_eq!(frame, loud);
+
+        let mut frame = loud.clone();
+        gate.process_keyed(&mut frame, 0.2);
+        assert_eq!(gate.state, GateState::Closed);
+
+        // 0.7 would open with the defaults, but not with open = 0.8
+        let mut frame = loud.clone();
+        gate.process_keyed(&mut frame, 0.7);
+        assert_eq!(gate.state, GateState::Closed);
+        assert!(rms(&frame) < 1e-6);
+
+        let quiet = make_sine(440.0, 0.002, 48000.0, 9600);
+        let mut frame = quiet.clone();
+        gate.process_keyed(&mut frame, 0.85);
+        assert_eq!(gate.state, GateState::Open);
+
+        assert!(gate.set_probability_thresholds(0.4, 0.6).is_err());
+        assert!(gate.set_probability_thresholds(1.5, 0.5).is_err());
     }
 
     #[test]
-    fn test_compressor_quiet_signal_passes_through() {
-        let mut comp = SpeechCompressor::new();
-        // Below threshold signal should pass mostly unchanged
-        let mut signal = make_sine(440.0, 0.01, 48000.0, 4800);
-        let rms_before = rms(&signal);
-        comp.process(&mut signal);
-        let rms_after = rms(&signal);
-        // Gain should be ~1.0 (no compression below threshold)
-        assert!((rms_after / rms_before - 1.0).abs() < 0.3,
-            "Quiet signal shouldn't be heavily modified: ratio={:.2}", rms_after / rms_before);
+    fn test_gate_state_durations() {
+        let mut gate = NoiseGate::new();
+        // Warm up the RMS window so the empty-window startup blip isn't counted
+        gate.process(&mut make_sine(440.0, 0.3, 48000.0, 4800));
+        assert!(gate.state_durations()[1] > 0);
+        gate.reset_state_durations();
+        assert_eq!(gate.state_durations(), [0; 4]);
+
+        let mut signal = make_sine(440.0, 0.3, 48000.0, 9600);
+        signal.extend(vec![0.0; 9600]);
+        gate.process(&mut signal);
+
+        let [open, hold, release, closed] = gate.state_durations();
+        assert_eq!(open + hold + release + closed, 19200);
+        assert!(hold.abs_diff(GATE_HOLD_SAMPLES as u64) <= 2, "hold={}", hold);
+        assert!(release.abs_diff(GATE_RELEASE_SAMPLES as u64) <= 2, "release={}", release);
+        assert!(open >= 9600 && closed > 0);
     }
 
     #[test]
-    fn test_compressor_soft_knee() {
-        // Verify soft knee provides smooth transition
-        let gain_below = SpeechCompressor::compute_gain_db(-30.0);
-        let gain_at_thresh = SpeechCompressor::compute_gain_db(-20.0);
-        let gain_above = SpeechCompressor::compute_gain_db(-10.0);
+    fn test_gate_comfort_noise() {
+        let mut signal = make_sine(440.0, 0.3, 48000.0, 4800);
+        signal.extend(vec![0.0; 24000]);
 
-        assert!(gain_below.abs() < 0.01, "No compression below knee: {}", gain_below);
-        assert!(gain_above < -1.0, "Should compress above knee: {}", gain_above);
-        // At threshold (middle of knee), should have some but not full compression
-        assert!(gain_at_thresh <= 0.0, "Should have some compression at threshold: {}", gain_at_thresh);
+        let mut plain = signal.clone();
+        NoiseGate::new().process(&mut plain);
+        assert!(plain[12000..].iter().all(|&s| s == 0.0));
+
+        let mut gate = NoiseGate::new();
+        gate.set_comfort_noise(Some(-60.0));
+        gate.seed_comfort_noise(7);
+        let mut filled = signal.clone();
+        gate.process(&mut filled);
+        let level_db = 20.0 * rms(&filled[12000..]).log10();
+        assert!((level_db + 60.0).abs() < 1.0, "Comfort noise should sit at -60dB, got {:.1}dB", level_db);
+
+        // Same seed → same noise
+        let mut again = NoiseGate::new();
+        again.set_comfort_noise(Some(-60.0));
+        again.seed_comfort_noise(7);
+        let mut repeat = signal.clone();
+        again.process(&mut repeat);
+        assert_eq!(filled, repeat);
     }
 
-    // --- RmsNormalizer tests ---
+    #[test]
+    fn test_processors_with_same_seed_match() {
+        let mut signal = make_sine(440.0, 0.3, 48000.0, 4800);
+        signal.extend(vec![0.0; 24000]);
+        let run = |seed| {
+            let mut proc = SystemAudioProcessor::new();
+            proc.set_comfort_noise(Some(-60.0));
+            proc.seed_comfort_noise(seed);
+            let mut output = signal.clone();
+            for block in output.chunks_mut(480) {
+                proc.process(block);
+            }
+            output
+        };
+        let output = run(3);
+        assert!(rms(&output[12000..]) > 0.0, "Gate should fill with comfort noise");
+        assert_eq!(output, run(3));
+        assert_ne!(output, run(4));
+    }
 
+    #[cfg(feature = "timing")]
     #[test]
-    fn test_normalizer_amplifies_quiet_signal() {
-        let mut norm = RmsNormalizer::new();
-        // Feed quiet signal for a few seconds to let it converge
-        for _ in 0..200 {
-            let mut frame = make_sine(440.0, 0.005, 48000.0, 480);
-            norm.process(&mut frame);
+    fn test_process_timing_populated() {
+        let mut proc = SystemAudioProcessor::new();
+        assert_eq!(proc.last_process_micros(), 0.0);
+        assert_eq!(proc.avg_process_micros(), 0.0);
+        for block in make_white_noise(0.3, 2, 4800).chunks_mut(480) {
+            proc.process(block);
         }
-        // After convergence, check output level
-        let mut frame = make_sine(440.0, 0.005, 48000.0, 480);
-        norm.process(&mut frame);
-        let out_rms = rms(&frame);
-        assert!(out_rms > 0.05, "Normalizer should amplify quiet signal: rms={:.4}", out_rms);
+        assert!(proc.last_process_micros() > 0.0);
+        assert!(proc.avg_process_micros() > 0.0);
     }
 
     #[test]
-    fn test_normalizer_output_clipped() {
-        let mut norm = RmsNormalizer::new();
-        // Even with max gain, output should never exceed ±1.0
-        for _ in 0..100 {
-            let mut frame = make_sine(440.0, 0.1, 48000.0, 480);
-            norm.process(&mut frame);
-            for &s in &frame {
-                assert!(s.abs() <= 1.0, "Output must be in [-1,1], got {}", s);
+    fn test_gate_min_open_time() {
+        // 20ms bursts / 20ms gaps: the RMS crosses both thresholds every burst
+        let burst = make_sine(440.0, 0.02, 48000.0, 960);
+        let mut signal = Vec::new();
+        for _ in 0..25 {
+            signal.extend(&burst);
+            signal.extend(vec![0.0; 960]);
+        }
+
+        // Length of every complete Open stretch, in samples
+        let open_runs = |mut gate: NoiseGate| {
+            let mut runs = Vec::new();
+            let mut run = 0;
+            for &s in &signal {
+                gate.process(&mut [s]);
+                if gate.state == GateState::Open {
+                    run += 1;
+                } else if run > 0 {
+                    runs.push(run);
+                    run = 0;
+                }
             }
+            runs
+        };
+
+        let flat = open_runs(NoiseGate::new());
+        assert!(flat.len() > 10 && flat.iter().any(|&r| r < 4800), "Expected churn without a guard");
+
+        for invalid in [-1.0, f32::INFINITY, f32::NAN] {
+            assert!(NoiseGate::with_config(GateConfig { min_open_ms: invalid, ..Default::default() }).is_err());
         }
+        let guarded = open_runs(NoiseGate::with_config(GateConfig { min_open_ms: 100.0, ..Default::default() }).unwrap());
+        assert!(guarded.len() > 1 && guarded.len() < flat.len());
+        // The initial stretch started before any guard applied; all later
+        // openings must last at least 100ms
+        assert!(guarded[1..].iter().all(|&r| r >= 4800), "Gate churned: {:?}", guarded);
     }
 
     #[test]
-    fn test_normalizer_holds_during_silence() {
-        let mut norm = RmsNormalizer::new();
-        // Feed signal to set gain
-        for _ in 0..100 {
-            let mut frame = make_sine(440.0, 0.01, 48000.0, 480);
-            norm.process(&mut frame);
+    fn test_gate_min_closed_time() {
+        // Speech, then silence with a 5ms click shortly after the gate closes
+        let mut signal = make_sine(440.0, 0.1, 48000.0, 4800);
+        signal.extend(vec![0.0; 9600]);
+        let click = signal.len();
+        signal.extend(make_sine(440.0, 0.1, 48000.0, 240));
+        signal.extend(vec![0.0; 4800]);
+
+        // Whether the gate reopened after first closing
+        let reopens = |mut gate: NoiseGate| {
+            let mut closed = false;
+            for (i, &s) in signal.iter().enumerate() {
+                gate.process(&mut [s]);
+                if gate.state == GateState::Closed && !closed {
+                    assert!(i < click, "Gate should fully close before the click");
+                    closed = true;
+                } else if closed && gate.state == GateState::Open {
+                    return true;
+                }
+            }
+            false
+        };
+
+        for invalid in [-1.0, f32::INFINITY, f32::NAN] {
+            assert!(NoiseGate::with_config(GateConfig { min_closed_ms: invalid, ..Default::default() }).is_err());
         }
-        let gain_before = norm.current_gain;
-        // Feed silence
-        let mut silence = vec![0.0f32; 480];
-        norm.process(&mut silence);
-        let gain_after = norm.current_gain;
-        assert!((gain_before - gain_after).abs() < 0.5,
-            "Gain should hold during silence: before={:.2}, after={:.2}", gain_before, gain_after);
+        assert!(reopens(NoiseGate::new()), "The click should reopen an unguarded gate");
+        assert!(!reopens(NoiseGate::with_config(GateConfig { min_closed_ms: 200.0, ..Default::default() }).unwrap()),
+            "The click fell inside the minimum closed time");
     }
 
-    // --- NoiseGate tests ---
+    #[test]
+    fn test_gate_a_weighting_ignores_rumble() {
+        // Strong 60Hz hum (RMS ≈ 0.014, above the open threshold) plus
+        // quiet speech-band noise
+        let hum = make_sine(60.0, 0.02, 48000.0, 48000);
+        let hiss = make_white_noise(0.002, 11, 48000);
+        let signal: Vec<f32> = hum.iter().zip(&hiss).map(|(a, b)| a + b).collect();
+
+        let mut flat = NoiseGate::new();
+        let mut flat_out = signal.clone();
+        flat.process(&mut flat_out);
+        assert_eq!(flat.state, GateState::Open, "Flat detector should be held open by hum");
+
+        let mut weighted = NoiseGate::new();
+        weighted.set_a_weighting(true);
+        let mut weighted_out = signal.clone();
+        weighted.process(&mut weighted_out);
+        assert_eq!(weighted.state, GateState::Closed, "A-weighted detector should ignore hum");
+        assert!(rms(&weighted_out[24000..]) < 1e-6);
+    }
 
     #[test]
-    fn test_gate_zeros_silence() {
-        let mut gate = NoiseGate::new();
-        // Feed enough low-level noise to fill RMS window and let gate close
-        let mut noise: Vec<f32> = (0..48000).map(|_| 0.0001).collect();
-        gate.process(&mut noise);
-        // Last portion should be gated (zeroed)
-        let tail_rms = rms(&noise[40000..]);
-        assert!(tail_rms < 0.0001, "Gate should zero out very quiet signal: rms={:.6}", tail_rms);
+    fn test_gate_config_a_weighting() {
+        // Same RMS (≈ -37 dBFS, well above the open threshold) at 60Hz and 1kHz
+        let config = GateConfig { a_weighting: true, ..Default::default() };
+        let mut hum_gate = NoiseGate::with_config(config).unwrap();
+        hum_gate.process(&mut make_sine(60.0, 0.02, 48000.0, 48000));
+        assert_eq!(hum_gate.state, GateState::Closed, "60Hz should not hold an A-weighted gate open");
+
+        let mut tone_gate = NoiseGate::with_config(config).unwrap();
+        let tone = make_sine(1000.0, 0.02, 48000.0, 48000);
+        let mut out = tone.clone();
+        tone_gate.process(&mut out);
+        assert_eq!(tone_gate.state, GateState::Open, "1kHz at the same RMS should open it");
+        // Detection only: the audio itself is not filtered
+        assert_eq!(out, tone);
+    }
+
+    #[test]
+    fn test_gate_config_comfort_noise() {
+        // Speech, then a quiet low-frequency-heavy floor the gate closes on
+        let floor: Vec<f32> = make_white_noise(0.004, 5, 48000 * 2)
+            .iter()
+            .scan(0.0f32, |lp, &w| {
+                *lp = 0.9 * *lp + 0.1 * w;
+                Some(*lp)
+            })
+            .collect();
+        let mut signal = make_sine(440.0, 0.3, 48000.0, 4800);
+        signal.extend(&floor);
+
+        let mut silent = signal.clone();
+        NoiseGate::with_config(GateConfig::default()).unwrap().process(&mut silent);
+        assert!(silent[24000..].iter().all(|&s| s == 0.0));
+
+        let mut gate = NoiseGate::with_config(GateConfig { comfort_noise: Some(-70.0), ..Default::default() }).unwrap();
+        let mut filled = signal.clone();
+        gate.process(&mut filled);
+        let tail = &filled[48000..];
+        let level = rms(tail);
+        assert!(level > 0.0 && level < GATE_CLOSE_THRESH, "Comfort noise should be faint: {}", level);
+
+        // Generated noise picks up the floor's low-pass tilt
+        let lag1 = tail.windows(2).map(|w| w[0] * w[1]).sum::<f32>() / tail.iter().map(|s| s * s).sum::<f32>();
+        assert!(lag1 > 0.8, "Comfort noise should match the floor's spectrum: r1/r0={:.2}", lag1);
     }
 
     #[test]
@@ -480,6 +3951,184 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_processor_gate_toggle_live() {
+        let mut proc = SystemAudioProcessor::new();
+        // Quiet noise long enough for the gate to close
+        for _ in 0..500 {
+            let mut frame = vec![0.0001f32; 480];
+            proc.process(&mut frame);
+        }
+        let mut gated = vec![0.0001f32; 480];
+        proc.process(&mut gated);
+        assert!(rms(&gated) < 1e-6, "Gate should be closed: rms={:.6}", rms(&gated));
+
+        // Disable gate mid-stream — same audio should now pass
+        proc.set_gate_enabled(false);
+        let mut passed = vec![0.0001f32; 480];
+        proc.process(&mut passed);
+        assert!(rms(&passed) > 0.00005,
+            "Disabled gate should pass quiet audio: rms={:.6}", rms(&passed));
+
+        // Detector kept running while disabled, so re-enabling closes immediately
+        proc.set_gate_enabled(true);
+        let mut regated = vec![0.0001f32; 480];
+        proc.process(&mut regated);
+        assert!(rms(&regated) < 1e-6, "Re-enabled gate should still be closed");
+    }
+
+    #[test]
+    fn test_processor_stage_order() {
+        use StageKind::*;
+        assert!(SystemAudioProcessor::with_order(&[Gate, Compressor, Gate]).is_err());
+        assert_eq!(SystemAudioProcessor::new().order(), &[Compressor, Saturator, Normalizer, Gate]);
+
+        let mut proc = SystemAudioProcessor::with_order(&[Gate, Compressor, Normalizer]).unwrap();
+        assert_eq!(proc.order(), &[Gate, Compressor, Normalizer]);
+
+        // Speech burst then a quiet floor, run through the gate first
+        let mut input = make_sine(440.0, 0.3, 48000.0, 24000);
+        input.extend(make_white_noise(0.001, 31, 24000));
+
+        let mut expected = input.clone();
+        let (mut gate, mut comp, mut norm) = (NoiseGate::new(), SpeechCompressor::new(), RmsNormalizer::new());
+        for block in expected.chunks_mut(480) {
+            gate.process(block);
+            comp.process(block);
+            norm.process(block);
+        }
+
+        let mut output = input.clone();
+        proc.process_blocked(&mut output, 480);
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_processor_output_rate_decimation() {
+        // 2kHz speech-band tone + 8kHz tone at the 16kHz Nyquist
+        let input: Vec<f32> = make_sine(2000.0, 0.1, 48000.0, 48000)
+            .iter()
+            .zip(make_sine(8000.0, 0.1, 48000.0, 48000))
+            .map(|(a, b)| a + b)
+            .collect();
+
+        let mut proc = SystemAudioProcessor::with_output_rate(16000).unwrap();
+        assert_eq!(proc.output_rate(), 16000);
+        let mut output = Vec::new();
+        for chunk in input.chunks(441) {
+            output.extend(proc.process_resampled(&mut chunk.to_vec()));
+        }
+        assert!((output.len() as i32 - 16000).abs() <= 32, "Expected ~16000 samples, got {}", output.len());
+
+        let settled = &output[8000..];
+        let low = tone_level(settled, 2000.0, 16000.0);
+        let high = tone_level(settled, 8000.0, 16000.0);
+        assert!(low > 0.05, "2kHz tone should survive: {:.4}", low);
+        assert!(high < low * 0.01, "8kHz tone should be suppressed: {:.5} vs {:.4}", high, low);
+
+        // Default rate is a straight copy
+        let mut same = SystemAudioProcessor::new();
+        let mut chunk = input[..480].to_vec();
+        assert_eq!(same.process_resampled(&mut chunk), chunk);
+        assert!(SystemAudioProcessor::with_output_rate(0).is_err());
+    }
+
+    #[test]
+    fn test_process_to_matches_in_place() {
+        let input = make_sine(440.0, 0.3, 48000.0, 4800);
+
+        let mut in_place = input.clone();
+        SystemAudioProcessor::new().process(&mut in_place);
+        let mut out = vec![0.0; input.len()];
+        let original = input.clone();
+        SystemAudioProcessor::new().process_to(&input, &mut out);
+        assert_eq!(input, original, "process_to must not modify its input");
+        assert_eq!(out, in_place);
+
+        let mut in_place = input.clone();
+        SpeechCompressor::new().process(&mut in_place);
+        SpeechCompressor::new().process_to(&input, &mut out);
+        assert_eq!(out, in_place);
+
+        let mut in_place = input.clone();
+        RmsNormalizer::new().process(&mut in_place);
+        RmsNormalizer::new().process_to(&input, &mut out);
+        assert_eq!(out, in_place);
+
+        let mut in_place = input.clone();
+        NoiseGate::new().process(&mut in_place);
+        NoiseGate::new().process_to(&input, &mut out);
+        assert_eq!(out, in_place);
+    }
+
+    #[test]
+    #[should_panic(expected = "lengths differ")]
+    fn test_process_to_length_mismatch() {
+        SystemAudioProcessor::new().process_to(&[0.0; 10], &mut [0.0; 5]);
+    }
+
+    #[test]
+    fn test_processor_blocked_matches_chunked() {
+        let mut input = make_sine(440.0, 0.003, 48000.0, 48000);
+        input.extend(make_sine(440.0, 0.3, 48000.0, 48000));
+        input.extend(vec![0.0001; 48000]);
+
+        let mut chunked = input.clone();
+        let mut proc_a = SystemAudioProcessor::new();
+        for chunk in chunked.chunks_mut(480) {
+            proc_a.process(chunk);
+        }
+
+        let mut blocked = input.clone();
+        let mut proc_b = SystemAudioProcessor::new();
+        proc_b.process_blocked(&mut blocked, 480);
+
+        for (i, (a, b)) in chunked.iter().zip(&blocked).enumerate() {
+            assert!((a - b).abs() < 1e-6, "Mismatch at sample {}: {} vs {}", i, a, b);
+        }
+    }
+
+    #[test]
+    fn test_processor_bypass_keeps_envelopes_warm() {
+        let mut proc = SystemAudioProcessor::new();
+        proc.set_bypass(true);
+
+        let input = make_sine(440.0, 0.5, 48000.0, 4800);
+        let mut frame = input.clone();
+        proc.process(&mut frame);
+        assert_eq!(frame, input, "Bypassed processor must not alter audio");
+
+        // Envelope already reflects the loud input
+        assert!(proc.compressor.gain_smooth < 0.7,
+            "Compressor envelope should have tracked loud input: {}", proc.compressor.gain_smooth);
+
+        proc.set_bypass(false);
+        let mut next = make_sine(440.0, 0.5, 48000.0, 480);
+        let gain = proc.compressor.gain_smooth;
+        proc.process(&mut next);
+        assert!((proc.compressor.gain_smooth - gain).abs() < 0.05,
+            "Un-bypass should continue from the warm envelope");
+    }
+
+    #[test]
+    fn test_stage_bypass_passes_audio() {
+        let input = make_sine(440.0, 0.5, 48000.0, 4800);
+
+        let mut comp = SpeechCompressor::new();
+        comp.set_bypass(true);
+        let mut frame = input.clone();
+        assert_eq!(comp.process_frame(&mut frame), 1.0);
+        assert_eq!(frame, input);
+        assert!(comp.gain_smooth < 0.7);
+
+        let mut gate = NoiseGate::new();
+        gate.set_bypass(true);
+        let mut quiet = vec![0.0001f32; 48000];
+        gate.process(&mut quiet);
+        assert_eq!(gate.state, GateState::Closed);
+        assert!(quiet.iter().all(|&s| s == 0.0001));
+    }
+
     #[test]
     fn test_processor_silence_is_quiet() {
         let mut proc = SystemAudioProcessor::new();
@@ -494,4 +4143,434 @@ mod tests {
         assert!(out_rms < 0.01,
             "Silence should remain quiet after processing: rms={:.6}", out_rms);
     }
+
+    #[test]
+    fn test_process_reporting_flags_gated_frames() {
+        let mut proc = SystemAudioProcessor::new();
+        for _ in 0..50 {
+            proc.process(&mut make_sine(440.0, 0.3, 48000.0, 480));
+        }
+        let mut speech = make_sine(440.0, 0.3, 48000.0, 480);
+        let mut info = proc.process_reporting(&mut speech);
+        assert!(!info.gated, "Speech frame should not be gated");
+        assert!(info.peak > 0.1 && info.rms > 0.05, "Speech level: {:?}", info);
+        assert!((info.peak - peak(&speech)).abs() < 1e-6);
+
+        // Past hold and release the gate mutes whole frames
+        for _ in 0..100 {
+            info = proc.process_reporting(&mut [0.0; 480]);
+        }
+        assert!(info.gated, "Silence frame should be gated");
+        assert_eq!(info.gain_reduction_db, proc.compressor.last_gain_reduction_db());
+
+        proc.set_bypass(true);
+        assert!(!proc.process_reporting(&mut [0.0; 480]).gated, "Bypassed output is never gated");
+    }
+
+    #[test]
+    fn test_gate_close_decay_keeps_word_tail() {
+        // 200ms vowel, then a 150ms fricative 4 dB under the close threshold
+        let fricative_amp = 10.0f32.powf(-54.0 / 20.0) * 3.0f32.sqrt();
+        let mut input = make_sine(300.0, 0.3, 48000.0, 9600);
+        input.extend(make_white_noise(fricative_amp, 23, 7200));
+        let tail = 9600 + 6240..9600 + 7200; // last 20ms of the fricative
+
+        let mut plain = input.clone();
+        NoiseGate::new().process(&mut plain);
+        assert!(rms(&plain[tail.clone()]) < 0.1 * rms(&input[tail.clone()]), "Default gate should cut the tail");
+
+        let mut gate = NoiseGate::with_config(GateConfig { close_decay_ms: 50.0, ..Default::default() }).unwrap();
+        let mut held = input.clone();
+        gate.process(&mut held);
+        assert_eq!(held[tail.clone()], input[tail.clone()], "Fricative should pass untouched");
+
+        // Silence after the word still closes it
+        gate.process(&mut vec![0.0; 24000]);
+        assert!(gate.is_closed());
+        assert!(gate.set_close_decay_ms(-1.0).is_err());
+        for decay_ms in [f32::INFINITY, f32::NAN, -1.0] {
+            assert!(NoiseGate::with_config(GateConfig { close_decay_ms: decay_ms, ..Default::default() }).is_err());
+        }
+    }
+
+    #[test]
+    fn test_gate_reports_thrashing() {
+        let warnings = Arc::new(AtomicU32::new(0));
+        let sink = warnings.clone();
+        let mut gate = NoiseGate::new();
+        gate.set_transition_warning(20.0, move |_| {
+            sink.fetch_add(1, Ordering::Relaxed);
+        }).unwrap();
+
+        // Normal speech: 1s talk, 1s pause
+        let mut speech = make_sine(440.0, 0.1, 48000.0, 48000);
+        speech.extend(vec![0.0; 48000]);
+        gate.process(&mut speech);
+        assert!(gate.transitions_per_second() < 5.0, "Speech rate {}", gate.transitions_per_second());
+
+        // Level bouncing across the thresholds: 10ms over, 70ms under
+        let pathological: Vec<f32> = make_sine(440.0, 0.02, 48000.0, 48000 * 2)
+            .iter()
+            .enumerate()
+            .map(|(i, &s)| if i % 3840 < 480 { s } else { s * 0.05 })
+            .collect();
+        for frame in pathological.clone().chunks_mut(480) {
+            gate.process(frame);
+        }
+        let rate = gate.transitions_per_second();
+        assert!(rate > 40.0, "Oscillating gate should report a high rate: {}", rate);
+        // One excursion, one warning
+        assert_eq!(warnings.load(Ordering::Relaxed), 1);
+
+        // Older transitions drop out of the window
+        gate.process(&mut vec![0.0; 96000]);
+        assert_eq!(gate.transitions_per_second(), 0.0);
+    }
+
+    #[test]
+    fn test_stereo_gate_closes_channels_together() {
+        // Left sits above the close threshold, right below it
+        let left_in = make_sine(440.0, 0.018, 48000.0, 48000); // ≈ -38 dBFS RMS
+        let right_in = make_sine(440.0, 0.007, 48000.0, 48000); // ≈ -46 dBFS RMS
+
+        // Independent gates pull the image to the left
+        let mut left_gate = NoiseGate::with_thresholds(-40.0, -44.0).unwrap();
+        let mut right_gate = NoiseGate::with_thresholds(-40.0, -44.0).unwrap();
+        let (mut left, mut right) = (left_in.clone(), right_in.clone());
+        left_gate.process(&mut left);
+        right_gate.process(&mut right);
+        assert!(left_gate.is_open() && right_gate.is_closed(), "Unlinked gates should split");
+
+        // Linked: both stay open and every sample gets the same gain
+        let mut stereo = StereoNoiseGate::with_gate(NoiseGate::with_thresholds(-40.0, -44.0).unwrap(), StereoLink::Max);
+        let (mut left, mut right) = (left_in.clone(), right_in.clone());
+        stereo.process(&mut left, &mut right);
+        assert!(stereo.gate().is_open());
+        assert_eq!(left, left_in);
+        assert_eq!(right, right_in);
+
+        // When both fall silent they close together
+        let (mut left, mut right) = (vec![0.001; 48000], vec![0.0005; 48000]);
+        stereo.process(&mut left, &mut right);
+        assert!(stereo.gate().is_closed());
+        for (l, r) in left.iter().zip(&right) {
+            assert!((l / 0.001 - r / 0.0005).abs() < 1e-4, "Channels gained differently: {} vs {}", l, r);
+        }
+        assert_eq!(*left.last().unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_input_trim_lets_compressor_engage() {
+        // -43 dBFS RMS: 23 dB under the compressor's threshold
+        let run = |trim: bool| {
+            let mut proc = SystemAudioProcessor::new();
+            proc.set_input_trim_enabled(trim);
+            let mut info = FrameInfo::default();
+            for _ in 0..1000 {
+                info = proc.process_reporting(&mut make_sine(440.0, 0.01, 48000.0, 480));
+            }
+            info.gain_reduction_db
+        };
+        let without = run(false);
+        let with = run(true);
+        assert!(without < 0.1, "Compressor shouldn't engage on quiet input: {:.2} dB", without);
+        assert!(with > 2.0, "Trimmed input should be compressed: {:.2} dB", with);
+    }
+
+    #[test]
+    fn test_invert_polarity_negates_output() {
+        let input = make_sine(440.0, 0.05, 48000.0, 4800);
+        let mut plain = input.clone();
+        SystemAudioProcessor::new().process(&mut plain);
+
+        let mut proc = SystemAudioProcessor::new();
+        proc.set_invert_polarity(true);
+        let mut inverted = input.clone();
+        proc.process(&mut inverted);
+        for (i, p) in inverted.iter().zip(&plain) {
+            assert_eq!(*i, -*p);
+        }
+    }
+
+    #[test]
+    fn test_process_copy_matches_process() {
+        let input = make_sine(440.0, 0.05, 48000.0, 4800);
+        let mut copied = SystemAudioProcessor::new();
+        let output = copied.process_copy(&input);
+        assert_eq!(input, make_sine(440.0, 0.05, 48000.0, 4800));
+
+        let mut in_place = input.clone();
+        SystemAudioProcessor::new().process(&mut in_place);
+        assert_eq!(output, in_place);
+    }
+
+    #[test]
+    fn test_process_mid_side_compresses_centre_more_than_panned() {
+        // Centred 440 Hz "voice" in both channels, 1 kHz noise element hard left
+        let voice = make_sine(440.0, 0.45, 48000.0, 48000);
+        let panned = make_sine(1000.0, 0.45, 48000.0, 48000);
+        let input: Vec<f32> = voice.iter().zip(&panned).flat_map(|(&v, &p)| [v + p, v]).collect();
+
+        let mut proc = SystemAudioProcessor::new();
+        proc.set_normalizer_enabled(false);
+        proc.set_gate_enabled(false);
+        let mut output = input.clone();
+        for block in output.chunks_mut(960) {
+            proc.process_mid_side(block);
+        }
+
+        // Skip the first half second while the envelopes settle
+        let settled = |interleaved: &[f32], channel: usize| -> Vec<f32> {
+            interleaved.iter().skip(channel).step_by(2).skip(24000).copied().collect()
+        };
+        let mid = |interleaved: &[f32]| -> Vec<f32> {
+            let (left, right) = (settled(interleaved, 0), settled(interleaved, 1));
+            left.iter().zip(&right).map(|(l, r)| (l + r) * 0.5).collect()
+        };
+        let reduction_db = |before: f32, after: f32| 20.0 * (before / after).log10();
+        let voice_reduction = reduction_db(
+            tone_level(&mid(&input), 440.0, 48000.0),
+            tone_level(&mid(&output), 440.0, 48000.0),
+        );
+        let panned_reduction = reduction_db(
+            tone_level(&settled(&input, 0), 1000.0, 48000.0),
+            tone_level(&settled(&output, 0), 1000.0, 48000.0),
+        );
+        assert!(voice_reduction > panned_reduction + 2.0,
+            "voice reduced {:.1} dB, panned {:.1} dB", voice_reduction, panned_reduction);
+        assert!(output.iter().all(|s| s.is_finite()));
+    }
+
+    #[test]
+    fn test_process_mid_side_polarity_and_bypass_cover_both_channels() {
+        // Loud hard-left tone: equal mid and side, both over the threshold
+        let tone = make_sine(440.0, 0.9, 48000.0, 48000);
+        let input: Vec<f32> = tone.iter().flat_map(|&l| [l, 0.0]).collect();
+        let run = |proc: &mut SystemAudioProcessor| {
+            let mut output = input.clone();
+            for block in output.chunks_mut(960) {
+                proc.process_mid_side(block);
+            }
+            output
+        };
+
+        let normal = run(&mut SystemAudioProcessor::new());
+        let mut inverting = SystemAudioProcessor::new();
+        inverting.set_invert_polarity(true);
+        let inverted = run(&mut inverting);
+        for (inv, out) in inverted.iter().zip(&normal) {
+            assert!((inv + out).abs() < 1e-6, "Inverted output should be negated: {} vs {}", inv, out);
+        }
+
+        let mut bypassed = SystemAudioProcessor::new();
+        bypassed.set_bypass(true);
+        assert_eq!(run(&mut bypassed), input, "Bypass should pass stereo through untouched");
+
+        // Compressor out of the order: neither channel is compressed
+        let mut uncompressed = SystemAudioProcessor::new();
+        uncompressed.set_order(&[StageKind::Normalizer, StageKind::Gate]).unwrap();
+        uncompressed.set_normalizer_enabled(false);
+        uncompressed.set_gate_enabled(false);
+        for (out, inp) in run(&mut uncompressed).iter().zip(&input) {
+            assert!((out - inp).abs() < 1e-6, "Side should skip the compressor too: {} vs {}", out, inp);
+        }
+    }
+
+    #[test]
+    fn test_process_mid_side_side_closes_with_gate() {
+        // Centred tone for 1s then silence, over steady anti-phase (side-only) noise
+        let mut voice = make_sine(440.0, 0.1, 48000.0, 48000);
+        voice.resize(96000, 0.0);
+        let noise = make_white_noise(0.01, 11, 96000);
+        let input: Vec<f32> = voice.iter().zip(&noise).flat_map(|(&v, &n)| [v + n, v - n]).collect();
+
+        let mut proc = SystemAudioProcessor::new();
+        let mut output = input.clone();
+        for block in output.chunks_mut(960) {
+            proc.process_mid_side(block);
+        }
+        assert!(!proc.gate().is_open(), "Gate should have closed on the silent mid");
+
+        let side = |interleaved: &[f32], range: std::ops::Range<usize>| -> Vec<f32> {
+            interleaved.chunks_exact(2).skip(range.start).take(range.len()).map(|p| (p[0] - p[1]) * 0.5).collect()
+        };
+        let gain = |range: std::ops::Range<usize>| rms(&side(&output, range.clone())) / rms(&side(&input, range));
+        let (open, closed) = (gain(24000..48000), gain(72000..96000));
+        assert!(closed < open * 0.5,
+            "Side should close with the gate: open gain {:.3}, closed gain {:.3}", open, closed);
+    }
+
+    #[test]
+    fn test_linked_agc_and_processor_settle() {
+        // -40 dBFS tone, 4s at -20 dBFS, back to -40 dBFS
+        let level = |i: usize| if (48000 * 3..48000 * 7).contains(&i) { 0.1 } else { 0.01 };
+        let input: Vec<f32> = (0..48000 * 12)
+            .map(|i| level(i) * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin())
+            .collect();
+        // Combined AGC × normalizer gain in dB after each 10ms block
+        let combined_gain = |linked: bool| {
+            let mut agc = AutoGainControl::new();
+            let agc_gain = agc.gain_link();
+            let mut proc = SystemAudioProcessor::new();
+            if linked {
+                proc.set_upstream_gain(Some(agc_gain.clone()));
+            }
+            input
+                .chunks(480)
+                .map(|block| {
+                    let mut block = block.to_vec();
+                    agc.process(&mut block);
+                    proc.process(&mut block);
+                    20.0 * (agc_gain.get() * proc.normalizer.current_gain).log10()
+                })
+                .collect::<Vec<f32>>()
+        };
+        let unlinked = combined_gain(false);
+        let linked = combined_gain(true);
+        let settled = linked[linked.len() - 1];
+        assert!((unlinked[unlinked.len() - 1] - settled).abs() < 0.1);
+
+        // The loud onset is caught just as fast
+        assert!(linked[301] <= unlinked[301] + 0.1, "linked={:.2} unlinked={:.2}", linked[301], unlinked[301]);
+
+        // Recovery: unlinked, both stages raise their gain and overshoot;
+        // linked, the product climbs once and settles
+        let recovery = 700..linked.len();
+        let overshoot = |db: &[f32]| db.iter().fold(f32::MIN, |m, &g| m.max(g)) - settled;
+        assert!(overshoot(&unlinked[recovery.clone()]) > 1.0,
+            "Unlinked should overshoot: {:.2} dB", overshoot(&unlinked[recovery.clone()]));
+        assert!(overshoot(&linked[recovery.clone()]) < 0.1,
+            "Linked overshoot {:.2} dB", overshoot(&linked[recovery.clone()]));
+        assert!(linked[recovery].windows(2).all(|pair| pair[1] >= pair[0] - 0.01),
+            "Linked gain should rise monotonically");
+    }
+
+    #[test]
+    fn test_frozen_processor_holds_gains() {
+        let mut proc = SystemAudioProcessor::new();
+        for _ in 0..200 {
+            proc.process(&mut make_sine(440.0, 0.05, 48000.0, 480));
+        }
+        let compressor_gain = proc.compressor.gain_smooth;
+        let normalizer_gain = proc.normalizer.current_gain;
+        let gate_state = proc.gate.state;
+
+        // A loud notification while frozen gets the held gains, no more
+        proc.set_frozen(true);
+        let held = compressor_gain * normalizer_gain;
+        for _ in 0..50 {
+            let input = make_sine(1000.0, 0.02, 48000.0, 480);
+            let mut burst = make_sine(1000.0, 0.02, 48000.0, 480);
+            proc.process(&mut burst);
+            for (out, inp) in burst.iter().zip(&input) {
+                assert!((out - inp * held).abs() < 1e-5);
+            }
+        }
+        for _ in 0..50 {
+            proc.process(&mut make_sine(1000.0, 0.9, 48000.0, 480));
+            proc.process(&mut [0.0; 480]);
+        }
+        proc.set_frozen(false);
+        assert_eq!(proc.compressor.gain_smooth, compressor_gain);
+        assert_eq!(proc.normalizer.current_gain, normalizer_gain);
+        assert_eq!(proc.gate.state, gate_state);
+
+        // Unfrozen, adaptation picks up again
+        proc.process(&mut make_sine(1000.0, 0.9, 48000.0, 480));
+        assert!(proc.compressor.gain_smooth < compressor_gain);
+    }
+
+    #[test]
+    fn test_noise_floor_shared_by_normalizer_and_gate() {
+        // -45 dBFS RMS hum after speech: above the gate's default close
+        // threshold and the normalizer's default floor
+        let hum = 10.0f32.powf(-45.0 / 20.0) * 2.0f32.sqrt();
+        let run = |floor_db: Option<f32>| {
+            let mut proc = SystemAudioProcessor::new();
+            if let Some(db) = floor_db {
+                proc.set_noise_floor(db).unwrap();
+            }
+            for _ in 0..200 {
+                proc.process(&mut make_sine(440.0, 0.2, 48000.0, 480));
+            }
+            // Let the detector windows clear the speech first
+            proc.process(&mut make_sine(440.0, hum, 48000.0, 960));
+            let gain_before = proc.normalizer.current_gain;
+            for _ in 0..100 {
+                proc.process(&mut make_sine(440.0, hum, 48000.0, 480));
+            }
+            (proc.normalizer.current_gain / gain_before, proc.gate.is_closed())
+        };
+
+        // Defaults: the normalizer treats the hum as signal and the gate passes it
+        let (drift, closed) = run(None);
+        assert!(drift > 1.01 && !closed, "drift={} closed={}", drift, closed);
+
+        // Floor above the hum: both call it silence
+        let (drift, closed) = run(Some(-40.0));
+        assert_eq!(drift, 1.0, "Normalizer should hold its gain");
+        assert!(closed, "Gate should close");
+        assert!(SystemAudioProcessor::new().set_noise_floor(3.0).is_err());
+    }
+
+    #[test]
+    fn test_adaptive_bypass_leaves_leveled_input_alone() {
+        // -16 dBFS RMS noise with a spike every 10ms: crest ≈ 4
+        let level = 10.0f32.powf(-16.0 / 20.0);
+        let mut leveled = make_white_noise(level * 3.0f32.sqrt(), 31, 48000 * 4);
+        for s in leveled.iter_mut().step_by(480) {
+            *s = 4.0 * level;
+        }
+        let crest = crest_factor(&leveled);
+        assert!((3.5..4.5).contains(&crest), "crest {:.2}", crest);
+
+        let change_db = |adaptive: bool| {
+            let mut proc = SystemAudioProcessor::new();
+            proc.set_adaptive_bypass_enabled(adaptive);
+            let mut output = leveled.clone();
+            proc.process_blocked(&mut output, 480);
+            let tail = 48000 * 3..;
+            let diff: Vec<f32> =
+                output[tail.clone()].iter().zip(&leveled[tail.clone()]).map(|(o, i)| o - i).collect();
+            (20.0 * (rms(&diff) / rms(&leveled[tail])).log10(), proc.adaptive_bypass_mix())
+        };
+        let (processed_db, _) = change_db(false);
+        let (bypassed_db, mix) = change_db(true);
+        assert!(mix > 0.95, "Leveled input should fade to passthrough: mix={:.3}", mix);
+        assert!(bypassed_db < -30.0 && bypassed_db < processed_db - 15.0,
+            "Output should barely differ from the input: {:.1} dB (fully processed {:.1} dB)",
+            bypassed_db, processed_db);
+
+        // A quiet, peaky signal is processed exactly as without the option
+        let quiet = make_impulse_train(0.3, 480, 48000 * 2);
+        let mut plain = SystemAudioProcessor::new();
+        let mut adaptive = SystemAudioProcessor::new();
+        adaptive.set_adaptive_bypass_enabled(true);
+        let mut expected = quiet.clone();
+        let mut output = quiet.clone();
+        plain.process_blocked(&mut expected, 480);
+        adaptive.process_blocked(&mut output, 480);
+        assert_eq!(adaptive.adaptive_bypass_mix(), 0.0);
+        assert_eq!(output, expected);
+
+        // The band follows the normalizer's target, not the default one
+        let mut retargeted = SystemAudioProcessor::new();
+        retargeted.normalizer.set_target_rms(0.05).unwrap();
+        retargeted.set_adaptive_bypass_enabled(true);
+        retargeted.process_blocked(&mut leveled.clone(), 480);
+        assert_eq!(retargeted.adaptive_bypass_mix(), 0.0, "-16 dBFS is 10 dB over a -26 dBFS target");
+
+        // -16 dBFS is 4 dB over a -20 dBFS target: outside the default
+        // band, inside a 6 dB normalizer band
+        let mix_with_band = |tolerance_db: Option<f32>| {
+            let mut proc = SystemAudioProcessor::new();
+            proc.normalizer.set_target_rms(0.1).unwrap();
+            proc.set_target_tolerance_db(tolerance_db).unwrap();
+            proc.set_adaptive_bypass_enabled(true);
+            proc.process_blocked(&mut leveled.clone(), 480);
+            proc.adaptive_bypass_mix()
+        };
+        assert_eq!(mix_with_band(None), 0.0);
+        assert!(mix_with_band(Some(6.0)) > 0.95, "The normalizer's band should replace the default");
+    }
 }
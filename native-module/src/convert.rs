@@ -0,0 +1,91 @@
+// Canonical i16 ↔ f32 sample conversion
+//
+// i16 → f32 divides by 32768 so the full i16 range maps into [-1.0, 1.0).
+// f32 → i16 scales by 32767, rounds to nearest and saturates, so +1.0 maps
+// to 32767 and out-of-range floats clamp instead of wrapping.
+//
+// The `_into` variants write into caller-provided buffers for use on the
+// real-time path; they convert `min(input.len(), output.len())` samples.
+
+/// i16 → f32 divisor
+const I16_TO_F32: f32 = 1.0 / 32768.0;
+/// f32 → i16 multiplier
+const F32_TO_I16: f32 = 32767.0;
+
+#[inline]
+fn sample_to_i16(sample: f32) -> i16 {
+    // `as` saturates on overflow and maps NaN to 0
+    (sample * F32_TO_I16).round() as i16
+}
+
+pub fn i16_to_f32(input: &[i16]) -> Vec<f32> {
+    input.iter().map(|&s| s as f32 * I16_TO_F32).collect()
+}
+
+pub fn f32_to_i16(input: &[f32]) -> Vec<i16> {
+    input.iter().map(|&s| sample_to_i16(s)).collect()
+}
+
+pub fn i16_to_f32_into(input: &[i16], output: &mut [f32]) {
+    for (dst, &s) in output.iter_mut().zip(input) {
+        *dst = s as f32 * I16_TO_F32;
+    }
+}
+
+pub fn f32_to_i16_into(input: &[f32], output: &mut [i16]) {
+    for (dst, &s) in output.iter_mut().zip(input) {
+        *dst = sample_to_i16(s);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_f32_to_i16_boundaries() {
+        assert_eq!(f32_to_i16(&[1.0, -1.0, 0.0]), vec![32767, -32767, 0]);
+        // Slightly over full scale saturates instead of wrapping
+        assert_eq!(f32_to_i16(&[1.0001, -1.0001, 2.0, -2.0]), vec![32767, -32768, 32767, -32768]);
+        assert_eq!(f32_to_i16(&[f32::INFINITY, f32::NEG_INFINITY, f32::NAN]), vec![32767, -32768, 0]);
+    }
+
+    #[test]
+    fn test_f32_to_i16_rounds() {
+        // 0.6 LSB rounds up, 0.4 LSB rounds down
+        assert_eq!(f32_to_i16(&[0.6 / 32767.0, 0.4 / 32767.0, -0.6 / 32767.0]), vec![1, 0, -1]);
+    }
+
+    #[test]
+    fn test_i16_to_f32_boundaries() {
+        assert_eq!(i16_to_f32(&[i16::MIN, 0, 16384]), vec![-1.0, 0.0, 0.5]);
+        let max = i16_to_f32(&[i16::MAX])[0];
+        assert!(max < 1.0 && max > 0.9999);
+    }
+
+    #[test]
+    fn test_round_trip_within_one_lsb() {
+        let original: Vec<i16> = (i16::MIN..=i16::MAX).step_by(7).collect();
+        let round_trip = f32_to_i16(&i16_to_f32(&original));
+        for (&a, &b) in original.iter().zip(&round_trip) {
+            assert!((a as i32 - b as i32).abs() <= 1, "{} -> {}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_into_variants_match() {
+        let input = [0.25f32, -0.5, 1.5];
+        let mut out = [0i16; 3];
+        f32_to_i16_into(&input, &mut out);
+        assert_eq!(out.to_vec(), f32_to_i16(&input));
+
+        let mut back = [0.0f32; 3];
+        i16_to_f32_into(&out, &mut back);
+        assert_eq!(back.to_vec(), i16_to_f32(&out));
+
+        // Shorter output buffer: converts what fits, no panic
+        let mut short = [0i16; 2];
+        f32_to_i16_into(&input, &mut short);
+        assert_eq!(short, [8192, -16384]);
+    }
+}
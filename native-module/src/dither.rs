@@ -0,0 +1,237 @@
+// Dithered f32 → i16 conversion
+//
+// Plain truncation of the float pipeline output leaves quantization error
+// that is correlated with the signal. On quiet speech (a few LSBs) that
+// error is heard as harmonic distortion and shows up to the recognizer as
+// structured noise. Adding a small random offset before rounding
+// decorrelates the error, turning it into a constant white noise floor.
+//
+//   - Rectangular (RPDF): ±0.5 LSB uniform. Removes distortion but the
+//     noise power still depends on the signal.
+//   - Triangular (TPDF):  ±1 LSB triangular (sum of two uniforms). Noise
+//     power is independent of the signal — the usual choice.
+//
+// Optional first-order noise shaping feeds the previous quantization error
+// back, pushing the noise floor towards high frequencies and away from the
+// speech band.
+
+use crate::rng::Pcg32;
+
+/// Full-scale multiplier, matching the resampler's f32 → i16 conversion
+const I16_SCALE: f32 = 32767.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dither {
+    /// Truncate without dither (legacy behaviour)
+    Off,
+    /// Uniform ±0.5 LSB
+    Rectangular,
+    /// Triangular ±1 LSB (TPDF)
+    Triangular,
+}
+
+pub struct Ditherer {
+    mode: Dither,
+    noise_shaping: bool,
+    /// Quantization error of the previous sample, in LSBs (noise shaping)
+    error_feedback: f32,
+    rng: Pcg32,
+}
+
+impl Ditherer {
+    pub fn new(mode: Dither) -> Self {
+        Self {
+            mode,
+            noise_shaping: false,
+            error_feedback: 0.0,
+            rng: Pcg32::from_entropy(),
+        }
+    }
+
+    /// Ditherer with a fixed RNG seed, so the output is reproducible
+    /// (e.g. in tests or golden-file comparisons).
+    pub fn with_seed(mode: Dither, seed: u64) -> Self {
+        Self {
+            rng: Pcg32::new(seed),
+            ..Self::new(mode)
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: Dither) {
+        self.mode = mode;
+    }
+
+    /// Enable first-order error-feedback noise shaping. Ignored when the
+    /// mode is `Off`.
+    pub fn set_noise_shaping(&mut self, enabled: bool) {
+        self.noise_shaping = enabled;
+        self.error_feedback = 0.0;
+    }
+
+    /// Allocating counterpart of `to_i16_dithered`, matching
+    /// `convert::f32_to_i16` for callers that want a new buffer.
+    pub fn f32_to_i16_dithered(&mut self, samples: &[f32]) -> Vec<i16> {
+        let mut out = vec![0i16; samples.len()];
+        self.to_i16_dithered(samples, &mut out);
+        out
+    }
+
+    /// Convert f32 samples in [-1.0, 1.0] to i16. `out` must be at least as
+    /// long as `samples`; only the first `samples.len()` entries are written.
+    pub fn to_i16_dithered(&mut self, samples: &[f32], out: &mut [i16]) {
+        for (sample, dst) in samples.iter().zip(out.iter_mut()) {
+            let scaled = *sample * I16_SCALE;
+
+            if self.mode == Dither::Off {
+                *dst = scaled.clamp(-32768.0, 32767.0) as i16;
+                continue;
+            }
+
+            let target = if self.noise_shaping {
+                scaled - self.error_feedback
+            } else {
+                scaled
+            };
+
+            let noise = match self.mode {
+                Dither::Rectangular => self.rng.next_f32() - 0.5,
+                Dither::Triangular => self.rng.next_f32() - self.rng.next_f32(),
+                Dither::Off => 0.0,
+            };
+
+            let quantized = (target + noise).round().clamp(-32768.0, 32767.0);
+            if self.noise_shaping {
+                self.error_feedback = quantized - target;
+            }
+            *dst = quantized as i16;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Spectral flatness (geometric mean / arithmetic mean of the power
+    /// spectrum). 1.0 = white, → 0 for tonal/correlated signals.
+    fn spectral_flatness(signal: &[f32]) -> f32 {
+        let n = signal.len();
+        let mut powers = Vec::with_capacity(n / 2);
+        for k in 1..n / 2 {
+            let (mut re, mut im) = (0.0f64, 0.0f64);
+            for (i, &x) in signal.iter().enumerate() {
+                let phase = -2.0 * std::f64::consts::PI * (k * i) as f64 / n as f64;
+                re += x as f64 * phase.cos();
+                im += x as f64 * phase.sin();
+            }
+            powers.push((re * re + im * im).max(1e-20));
+        }
+        let log_mean = powers.iter().map(|p| p.ln()).sum::<f64>() / powers.len() as f64;
+        let mean = powers.iter().sum::<f64>() / powers.len() as f64;
+        (log_mean.exp() / mean) as f32
+    }
+
+    /// Quantization error in LSBs
+    fn quantization_error(input: &[f32], output: &[i16]) -> Vec<f32> {
+        input.iter().zip(output).map(|(&x, &y)| y as f32 - x * I16_SCALE).collect()
+    }
+
+    #[test]
+    fn test_tpdf_flattens_error_spectrum() {
+        // Very quiet ramp: -3 LSB to +3 LSB
+        let n = 1024;
+        let ramp: Vec<f32> = (0..n)
+            .map(|i| (-3.0 + 6.0 * i as f32 / n as f32) / I16_SCALE)
+            .collect();
+
+        let mut plain = Ditherer::new(Dither::Off);
+        let mut plain_out = vec![0i16; n];
+        plain.to_i16_dithered(&ramp, &mut plain_out);
+
+        let mut tpdf = Ditherer::new(Dither::Triangular);
+        let mut tpdf_out = vec![0i16; n];
+        tpdf.to_i16_dithered(&ramp, &mut tpdf_out);
+
+        let plain_flatness = spectral_flatness(&quantization_error(&ramp, &plain_out));
+        let tpdf_flatness = spectral_flatness(&quantization_error(&ramp, &tpdf_out));
+
+        assert!(tpdf_flatness > plain_flatness * 2.0,
+            "TPDF error should be flatter: plain={:.3}, tpdf={:.3}", plain_flatness, tpdf_flatness);
+    }
+
+    #[test]
+    fn test_seeded_fade_out_is_reproducible_and_flatter() {
+        // 440 Hz fading from 4 LSB to nothing
+        let n = 1024;
+        let fade: Vec<f32> = (0..n)
+            .map(|i| {
+                let env = 4.0 * (1.0 - i as f32 / n as f32) / I16_SCALE;
+                env * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin()
+            })
+            .collect();
+
+        let dithered = Ditherer::with_seed(Dither::Triangular, 7).f32_to_i16_dithered(&fade);
+        assert_eq!(dithered, Ditherer::with_seed(Dither::Triangular, 7).f32_to_i16_dithered(&fade));
+        assert_ne!(dithered, Ditherer::with_seed(Dither::Triangular, 8).f32_to_i16_dithered(&fade));
+
+        let truncated = Ditherer::new(Dither::Off).f32_to_i16_dithered(&fade);
+        let truncated_flatness = spectral_flatness(&quantization_error(&fade, &truncated));
+        let dithered_flatness = spectral_flatness(&quantization_error(&fade, &dithered));
+        assert!(dithered_flatness > truncated_flatness * 2.0,
+            "Dithered error should be flatter: truncated={:.3}, dithered={:.3}", truncated_flatness, dithered_flatness);
+    }
+
+    #[test]
+    fn test_dither_error_bounded() {
+        let mut ditherer = Ditherer::new(Dither::Triangular);
+        let input: Vec<f32> = (0..1000).map(|i| (i as f32 / 1000.0) * 0.5).collect();
+        let mut out = vec![0i16; input.len()];
+        ditherer.to_i16_dithered(&input, &mut out);
+        for (&x, &y) in input.iter().zip(&out) {
+            assert!((y as f32 - x * I16_SCALE).abs() <= 1.5, "TPDF error must stay within ±1.5 LSB");
+        }
+    }
+
+    #[test]
+    fn test_noise_shaping_moves_noise_up() {
+        // Shaped error should have less low-frequency energy than unshaped
+        let n = 1024;
+        let input = vec![0.3 / I16_SCALE; n];
+        let low_band_energy = |err: &[f32]| -> f64 {
+            (1..n / 16)
+                .map(|k| {
+                    let (mut re, mut im) = (0.0f64, 0.0f64);
+                    for (i, &x) in err.iter().enumerate() {
+                        let phase = -2.0 * std::f64::consts::PI * (k * i) as f64 / n as f64;
+                        re += x as f64 * phase.cos();
+                        im += x as f64 * phase.sin();
+                    }
+                    re * re + im * im
+                })
+                .sum()
+        };
+
+        let mut flat = Ditherer::new(Dither::Triangular);
+        let mut flat_out = vec![0i16; n];
+        flat.to_i16_dithered(&input, &mut flat_out);
+
+        let mut shaped = Ditherer::new(Dither::Triangular);
+        shaped.set_noise_shaping(true);
+        let mut shaped_out = vec![0i16; n];
+        shaped.to_i16_dithered(&input, &mut shaped_out);
+
+        let flat_low = low_band_energy(&quantization_error(&input, &flat_out));
+        let shaped_low = low_band_energy(&quantization_error(&input, &shaped_out));
+        assert!(shaped_low < flat_low,
+            "Noise shaping should reduce low-band error: flat={:.1}, shaped={:.1}", flat_low, shaped_low);
+    }
+
+    #[test]
+    fn test_off_matches_truncation() {
+        let mut ditherer = Ditherer::new(Dither::Off);
+        let input = [0.5f32, -0.5, 1.2, -1.2, 0.00002];
+        let mut out = [0i16; 5];
+        ditherer.to_i16_dithered(&input, &mut out);
+        assert_eq!(out, [16383, -16383, 32767, -32768, 0]);
+    }
+}
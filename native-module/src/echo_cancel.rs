@@ -2,8 +2,12 @@ use std::collections::VecDeque;
 use std::sync::{Arc, Mutex, OnceLock};
 
 use aec_rs::{Aec, AecConfig};
+use anyhow::{bail, ensure, Result};
 
-/// Max reference buffer capacity: 1 second at 16kHz
+use crate::convert::{f32_to_i16, f32_to_i16_into, i16_to_f32};
+use crate::resampler::Resampler;
+
+/// Default reference buffer capacity: 1 second at 16kHz
 const REF_BUFFER_CAPACITY: usize = 16_000;
 
 /// AEC frame size (10ms sub-frames for best convergence)
@@ -15,72 +19,563 @@ const AEC_FILTER_LENGTH: usize = 3200;
 /// Sample rate for all AEC processing
 const AEC_SAMPLE_RATE: u32 = 16_000;
 
-static AEC_REFERENCE: OnceLock<Arc<Mutex<VecDeque<i16>>>> = OnceLock::new();
+/// Typical Geigel double-talk threshold for `set_double_talk_detection`:
+/// the mic peak may reach this fraction of the recent far-end peak before
+/// it's treated as near-end speech. 0.5 assumes at least 6 dB of acoustic
+/// echo loss (speaker → mic).
+pub const DOUBLE_TALK_THRESHOLD: f32 = 0.5;
+
+/// Far-end peak below which the reference counts as silent (~-50 dBFS);
+/// with no far-end there is nothing to double-talk against.
+const DOUBLE_TALK_REF_FLOOR: u16 = 100;
+
+/// Sub-frames double-talk is held after the last detection (50ms), so the flag
+/// doesn't drop in the gaps between syllables.
+const DOUBLE_TALK_HANGOVER: usize = 5;
+
+/// Output limiter release time constant. Attack is instant so no sample
+/// ever exceeds the ceiling.
+const OUTPUT_LIMITER_RELEASE_MS: f32 = 50.0;
+
+/// Microseconds per second, for converting `push_reference_us` timestamps
+const MICROS_PER_SECOND: f64 = 1_000_000.0;
 
-fn get_ref_buffer() -> &'static Arc<Mutex<VecDeque<i16>>> {
-    AEC_REFERENCE.get_or_init(|| Arc::new(Mutex::new(VecDeque::with_capacity(REF_BUFFER_CAPACITY))))
+/// What `push_reference` does when a frame won't fit in the buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Trim the oldest buffered samples to make room
+    #[default]
+    DropOldest,
+    /// Keep the buffered samples and discard the tail of the new frame
+    DropNewest,
+    /// Reject the whole frame and return an error
+    Error,
+}
+
+/// Reference buffer configuration, applied with `configure_reference`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReferenceConfig {
+    /// Maximum buffered reference samples (16kHz)
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+    /// Negate pushed samples, for a far-end tap that delivers
+    /// phase-inverted audio relative to what reaches the mic
+    pub invert_polarity: bool,
+}
+
+impl Default for ReferenceConfig {
+    fn default() -> Self {
+        Self {
+            capacity: REF_BUFFER_CAPACITY,
+            overflow: OverflowPolicy::default(),
+            invert_polarity: false,
+        }
+    }
+}
+
+impl ReferenceConfig {
+    pub fn validate(&self) -> Result<()> {
+        ensure!(self.capacity > 0, "reference capacity must be > 0");
+        Ok(())
+    }
+}
+
+/// A frame pushed with `push_reference_us`
+struct TimedFrame {
+    /// Wall-clock time (µs) its first sample played
+    timestamp_us: u64,
+    samples: Vec<i16>,
+}
+
+struct ReferenceBuffer {
+    samples: VecDeque<i16>,
+    config: ReferenceConfig,
+    /// Samples discarded on overflow since the last clear
+    dropped: u64,
+    /// Timestamp just past the newest buffered sample, once a frame has
+    /// been pushed with `push_reference_ts` (`None` = untimed)
+    end_ts: Option<u64>,
+    /// Frames pushed with `push_reference_us`, sorted by timestamp; kept
+    /// apart from `samples` so late or out-of-order frames still land in
+    /// the right place
+    timed: Vec<TimedFrame>,
+    /// Total samples held in `timed`
+    timed_len: usize,
+    /// Sample buffers of used-up timed frames, reused by the next pushes so
+    /// the realtime threads don't allocate under the lock once warmed up
+    timed_pool: Vec<Vec<i16>>,
+}
+
+impl ReferenceBuffer {
+    /// Buffer `frame` according to the overflow policy and return how many
+    /// of its samples were kept.
+    fn push(&mut self, frame: &[i16]) -> Result<usize> {
+        let capacity = self.config.capacity;
+        let free = capacity.saturating_sub(self.samples.len());
+        let invert = self.config.invert_polarity;
+        let polarity = move |&s: &i16| if invert { s.saturating_neg() } else { s };
+        match self.config.overflow {
+            OverflowPolicy::DropOldest => {
+                self.samples.extend(frame.iter().map(polarity));
+                let excess = self.samples.len().saturating_sub(capacity);
+                self.samples.drain(..excess);
+                self.dropped += excess as u64;
+                Ok(frame.len())
+            }
+            OverflowPolicy::DropNewest => {
+                let kept = frame.len().min(free);
+                self.samples.extend(frame[..kept].iter().map(polarity));
+                self.dropped += (frame.len() - kept) as u64;
+                Ok(kept)
+            }
+            OverflowPolicy::Error => {
+                if frame.len() > free {
+                    self.dropped += frame.len() as u64;
+                    bail!(
+                        "reference buffer overflow: {} samples pushed, {} free of {}",
+                        frame.len(), free, capacity
+                    );
+                }
+                self.samples.extend(frame.iter().map(polarity));
+                Ok(frame.len())
+            }
+        }
+    }
+
+    /// Timestamp of the oldest buffered sample, if timed.
+    fn front_ts(&self) -> Option<i64> {
+        self.end_ts.map(|end| end as i64 - self.samples.len() as i64)
+    }
+
+    /// Store a frame by its play time, dropping the oldest frames once the
+    /// capacity is exceeded. A frame repeating a timestamp replaces the
+    /// earlier one.
+    fn push_timed(&mut self, frame: &[i16], timestamp_us: u64) {
+        let invert = self.config.invert_polarity;
+        let mut samples = self.timed_pool.pop().unwrap_or_default();
+        samples.clear();
+        samples.extend(frame.iter().map(|&s| if invert { s.saturating_neg() } else { s }));
+        self.timed_len += samples.len();
+        match self.timed.binary_search_by_key(&timestamp_us, |f| f.timestamp_us) {
+            Ok(i) => {
+                let replaced = std::mem::replace(&mut self.timed[i].samples, samples);
+                self.timed_len -= replaced.len();
+                self.timed_pool.push(replaced);
+            }
+            Err(i) => self.timed.insert(i, TimedFrame { timestamp_us, samples }),
+        }
+        while self.timed_len > self.config.capacity && !self.timed.is_empty() {
+            let oldest = self.timed.remove(0).samples;
+            self.timed_len -= oldest.len();
+            self.dropped += oldest.len() as u64;
+            self.timed_pool.push(oldest);
+        }
+    }
+
+    /// Fill `window` with the reference starting at `start_us` from the
+    /// timed frames, returning how many leading samples are covered by
+    /// real reference (gaps and the rest are zero). Frames that end within
+    /// the window are used up and their buffers pooled.
+    fn select_timed(&mut self, start_us: i64, window: &mut [i16]) -> usize {
+        let size = window.len();
+        window.fill(0);
+        // Frames are in start order, so the covered prefix only grows
+        // while each frame starts inside it
+        let mut valid = 0;
+        for frame in &self.timed {
+            let offset = micros_to_samples(frame.timestamp_us as i64 - start_us);
+            if offset >= size as i64 {
+                break;
+            }
+            // Window position the frame starts at, and how much of the
+            // frame lies before the window
+            let (start, skip) = (offset.max(0) as usize, (-offset).max(0) as usize);
+            if skip >= frame.samples.len() {
+                continue;
+            }
+            let len = (frame.samples.len() - skip).min(size - start);
+            window[start..start + len].copy_from_slice(&frame.samples[skip..skip + len]);
+            if start <= valid {
+                valid = valid.max(start + len);
+            }
+        }
+
+        let window_end = start_us + samples_to_micros(size);
+        let mut i = 0;
+        while i < self.timed.len() {
+            let frame = &self.timed[i];
+            if frame.timestamp_us as i64 + samples_to_micros(frame.samples.len()) > window_end {
+                i += 1;
+                continue;
+            }
+            let used = self.timed.remove(i).samples;
+            self.timed_len -= used.len();
+            self.timed_pool.push(used);
+        }
+        valid
+    }
+
+    /// Drop every timed frame, keeping their buffers for reuse.
+    fn clear_timed(&mut self) {
+        self.timed_pool.extend(self.timed.drain(..).map(|frame| frame.samples));
+        self.timed_len = 0;
+    }
+}
+
+/// Convert a duration in µs to (rounded) samples at the AEC rate.
+fn micros_to_samples(micros: i64) -> i64 {
+    (micros as f64 * AEC_SAMPLE_RATE as f64 / MICROS_PER_SECOND).round() as i64
+}
+
+/// Duration of `samples` at the AEC rate, in whole µs.
+fn samples_to_micros(samples: usize) -> i64 {
+    (samples as f64 * MICROS_PER_SECOND / AEC_SAMPLE_RATE as f64) as i64
+}
+
+static AEC_REFERENCE: OnceLock<Arc<Mutex<ReferenceBuffer>>> = OnceLock::new();
+
+/// Streaming resampler for reference audio pushed at another rate, keyed by
+/// its source rate. Persistent so fractional remainders carry across frames.
+static REF_RESAMPLER: OnceLock<Mutex<Option<(u32, Resampler)>>> = OnceLock::new();
+
+fn get_ref_buffer() -> &'static Arc<Mutex<ReferenceBuffer>> {
+    AEC_REFERENCE.get_or_init(|| {
+        Arc::new(Mutex::new(ReferenceBuffer {
+            samples: VecDeque::with_capacity(REF_BUFFER_CAPACITY),
+            config: ReferenceConfig::default(),
+            dropped: 0,
+            end_ts: None,
+            timed: Vec::new(),
+            timed_len: 0,
+            timed_pool: Vec::new(),
+        }))
+    })
+}
+
+/// Set the reference buffer capacity and overflow policy. If the buffer
+/// already holds more than the new capacity, the oldest samples are
+/// trimmed (and counted as dropped).
+pub fn configure_reference(config: ReferenceConfig) -> Result<()> {
+    config.validate()?;
+    if let Ok(mut guard) = get_ref_buffer().lock() {
+        guard.config = config;
+        let excess = guard.samples.len().saturating_sub(config.capacity);
+        guard.samples.drain(..excess);
+        guard.dropped += excess as u64;
+    }
+    Ok(())
+}
+
+/// Number of reference samples currently buffered.
+pub fn reference_len() -> usize {
+    get_ref_buffer().lock().map(|g| g.samples.len()).unwrap_or(0)
+}
+
+/// Reference samples discarded on overflow since the last `clear_reference`.
+/// A steadily climbing count means nothing is pulling — usually a stalled
+/// mic path.
+pub fn dropped_reference_samples() -> u64 {
+    get_ref_buffer().lock().map(|g| g.dropped).unwrap_or(0)
 }
 
 /// Push reference audio from the system audio DSP thread.
-/// Called after resampling each frame. Trims oldest samples on overflow.
-pub fn push_reference(frame: &[i16]) {
+/// Called after resampling each frame. Overflow is handled according to the
+/// configured `OverflowPolicy`; only `OverflowPolicy::Error` returns an error.
+pub fn push_reference(frame: &[i16]) -> Result<()> {
     let buf = get_ref_buffer();
     if let Ok(mut guard) = buf.lock() {
-        guard.extend(frame.iter().copied());
-        while guard.len() > REF_BUFFER_CAPACITY {
-            guard.pop_front();
+        let kept = guard.push(frame)?;
+        // Untimed frames are assumed to follow on directly
+        if let Some(end) = guard.end_ts.as_mut() {
+            *end += kept as u64;
         }
     }
+    Ok(())
 }
 
-/// Pull reference samples for AEC. Returns zeros if buffer has insufficient data.
-pub fn pull_reference(size: usize) -> Vec<i16> {
+/// Push reference audio stamped with when its first sample was played, in
+/// mic samples (16kHz) on the mic capture clock — e.g. the mic stream's
+/// running sample count at the moment the system-audio frame was captured.
+///
+/// With timestamps the `EchoCanceller` can tell when the two clocks drift
+/// apart and re-aligns the reference by occasionally dropping or repeating
+/// a sample, instead of assuming both devices run at exactly 16kHz. The
+/// timestamps only need to be consistent with each other; the offset
+/// between the streams is learned on the first pull.
+pub fn push_reference_ts(frame: &[i16], ts_samples: u64) -> Result<()> {
     let buf = get_ref_buffer();
     if let Ok(mut guard) = buf.lock() {
-        if guard.len() >= size {
-            guard.drain(..size).collect()
-        } else {
-            // Not enough reference data — return zeros (AEC becomes passthrough)
-            vec![0i16; size]
+        let kept = guard.push(frame)?;
+        guard.end_ts = Some(ts_samples + kept as u64);
+    }
+    Ok(())
+}
+
+/// Push reference audio (16kHz) stamped with the wall-clock time its first
+/// sample was played, in µs, for `EchoCanceller::process_ts`. Frames may
+/// arrive late or out of order: each is placed by its timestamp rather
+/// than appended. Unlike `push_reference_ts` (mic-sample timestamps, drift
+/// correction for the draining `process`) these frames are only read by
+/// `process_ts`. When more than the configured capacity is held, the
+/// oldest frames are dropped whatever the overflow policy.
+pub fn push_reference_us(frame: &[i16], timestamp_us: u64) {
+    if let Ok(mut guard) = get_ref_buffer().lock() {
+        guard.push_timed(frame, timestamp_us);
+    }
+}
+
+/// Push f32 reference audio (16kHz) so a float chain doesn't have to
+/// convert first.
+pub fn push_reference_f32(frame: &[f32]) -> Result<()> {
+    push_reference(&f32_to_i16(frame))
+}
+
+/// Push reference audio captured at `from_rate`, resampling it to the AEC
+/// rate (16kHz) before buffering. Keeps resampler state between calls, so
+/// always push one continuous stream through here; changing `from_rate`
+/// starts a fresh resampler. Errors if `from_rate` is 0.
+pub fn push_reference_resampled(frame: &[i16], from_rate: u32) -> Result<()> {
+    if from_rate == AEC_SAMPLE_RATE {
+        return push_reference(frame);
+    }
+    let resampler = REF_RESAMPLER.get_or_init(|| Mutex::new(None));
+    if let Ok(mut guard) = resampler.lock() {
+        if guard.as_ref().map(|(rate, _)| *rate) != Some(from_rate) {
+            *guard = Some((from_rate, Resampler::new(from_rate as f64, AEC_SAMPLE_RATE as f64)?));
         }
-    } else {
-        vec![0i16; size]
+        if let Some((_, r)) = guard.as_mut() {
+            return push_reference(&f32_to_i16(&r.process(&i16_to_f32(frame))));
+        }
+    }
+    Ok(())
+}
+
+/// Pull reference samples for AEC. Whatever is buffered (up to `size`) is
+/// returned, zero-padded to `size` if the buffer ran short.
+pub fn pull_reference(size: usize) -> Vec<i16> {
+    pull_reference_counted(size).0
+}
+
+/// Like `pull_reference`, also returning how many leading samples are real
+/// reference data (the rest is zero padding).
+fn pull_reference_counted(size: usize) -> (Vec<i16>, usize) {
+    let buf = get_ref_buffer();
+    let mut samples = Vec::with_capacity(size);
+    if let Ok(mut guard) = buf.lock() {
+        let available = guard.samples.len().min(size);
+        samples.extend(guard.samples.drain(..available));
     }
+    let valid = samples.len();
+    samples.resize(size, 0);
+    (samples, valid)
 }
 
-/// Clear the reference buffer. Call when capture starts/stops to prevent stale data.
+/// Clear the reference buffer and its dropped-sample count. Call when
+/// capture starts/stops to prevent stale data. The configuration is kept.
 pub fn clear_reference() {
     let buf = get_ref_buffer();
     if let Ok(mut guard) = buf.lock() {
-        guard.clear();
+        guard.samples.clear();
+        guard.dropped = 0;
+        guard.end_ts = None;
+        guard.clear_timed();
+    }
+    if let Some(resampler) = REF_RESAMPLER.get() {
+        if let Ok(mut guard) = resampler.lock() {
+            *guard = None;
+        }
+    }
+}
+
+/// Keeps the reference pull aligned with the mic when reference frames
+/// carry timestamps (see `push_reference_ts`). Counts the mic samples
+/// pulled against, and compares that with the timestamp of the reference
+/// sample about to be pulled: the difference is the stream offset, which
+/// should stay constant. When the clocks drift it creeps, and each pull
+/// corrects it by at most one sample — dropping a reference sample if the
+/// reference has fallen behind, repeating one if it has run ahead.
+struct DriftTracker {
+    /// Mic samples pulled against so far
+    mic_position: u64,
+    /// Offset (reference front timestamp − mic position) learned on the
+    /// first timed pull
+    offset: Option<i64>,
+    /// Samples dropped or repeated so far
+    corrections: u64,
+}
+
+impl DriftTracker {
+    fn new() -> Self {
+        Self {
+            mic_position: 0,
+            offset: None,
+            corrections: 0,
+        }
+    }
+
+    /// `pull_reference_counted`, re-aligned by up to one sample first.
+    fn pull(&mut self, size: usize) -> (Vec<i16>, usize) {
+        let mut samples = Vec::with_capacity(size);
+        if let Ok(mut guard) = get_ref_buffer().lock() {
+            match guard.front_ts() {
+                // Untimed (or cleared): nothing to align against
+                None => self.offset = None,
+                Some(front) => {
+                    let offset = front - self.mic_position as i64;
+                    let drift = offset - *self.offset.get_or_insert(offset);
+                    if drift < 0 && guard.samples.len() > 1 {
+                        // Reference buffered too far back: skip a sample
+                        guard.samples.pop_front();
+                        self.corrections += 1;
+                    } else if drift > 0 {
+                        // Reference running ahead: repeat the oldest sample
+                        if let Some(&first) = guard.samples.front() {
+                            guard.samples.push_front(first);
+                            self.corrections += 1;
+                        }
+                    }
+                }
+            }
+            let available = guard.samples.len().min(size);
+            samples.extend(guard.samples.drain(..available));
+        }
+        self.mic_position += size as u64;
+        let valid = samples.len();
+        samples.resize(size, 0);
+        (samples, valid)
+    }
+}
+
+/// Geigel double-talk detector: flags sub-frames where the mic is louder
+/// than any echo of the recent far end could be, i.e. the near end is
+/// talking over it.
+struct DoubleTalkDetector {
+    /// Fraction of the far-end peak the mic peak may reach
+    threshold: f32,
+    /// Per-sub-frame far-end peaks covering the echo tail, newest last
+    ref_peaks: VecDeque<u16>,
+    hangover: usize,
+}
+
+impl DoubleTalkDetector {
+    fn new(threshold: f32) -> Self {
+        let tail_frames = AEC_FILTER_LENGTH / AEC_FRAME_SIZE;
+        Self {
+            threshold,
+            ref_peaks: VecDeque::from(vec![0; tail_frames]),
+            hangover: 0,
+        }
+    }
+
+    /// Feed one sub-frame pair and return whether double-talk is active.
+    fn update(&mut self, mic: &[i16], reference: &[i16]) -> bool {
+        let peak = |s: &[i16]| s.iter().map(|x| x.unsigned_abs()).max().unwrap_or(0);
+        self.ref_peaks.pop_front();
+        self.ref_peaks.push_back(peak(reference));
+
+        let far_peak = self.ref_peaks.iter().copied().max().unwrap_or(0);
+        let detected = far_peak >= DOUBLE_TALK_REF_FLOOR
+            && peak(mic) as f32 > self.threshold * far_peak as f32;
+        if detected {
+            self.hangover = DOUBLE_TALK_HANGOVER;
+        } else {
+            self.hangover = self.hangover.saturating_sub(1);
+        }
+        self.is_active()
+    }
+
+    fn is_active(&self) -> bool {
+        self.hangover > 0
+    }
+}
+
+/// Peak limiter on the canceller's output: a safety net against residual
+/// echo bursts the filter hasn't caught (e.g. just after the echo path
+/// changes).
+struct OutputLimiter {
+    /// Ceiling in i16 units
+    ceiling: f32,
+    gain: f32,
+    release_coeff: f32,
+}
+
+impl OutputLimiter {
+    fn new(ceiling_db: f32) -> Self {
+        Self {
+            ceiling: (32767.0 * 10.0f32.powf(ceiling_db / 20.0)).floor(),
+            gain: 1.0,
+            release_coeff: 1.0 - (-1.0 / (OUTPUT_LIMITER_RELEASE_MS * 0.001 * AEC_SAMPLE_RATE as f32)).exp(),
+        }
+    }
+
+    fn process(&mut self, samples: &mut [i16]) {
+        for sample in samples.iter_mut() {
+            let level = (*sample as f32).abs();
+            // Recover towards unity, then clamp down instantly if needed
+            self.gain += (1.0 - self.gain) * self.release_coeff;
+            if level * self.gain > self.ceiling {
+                self.gain = self.ceiling / level;
+            }
+            *sample = (*sample as f32 * self.gain).round().clamp(-self.ceiling, self.ceiling) as i16;
+        }
     }
 }
 
 pub struct EchoCanceller {
     aec: Aec,
     frame_size: usize,
+    /// Reused i16 copy of the mic frame for `process_f32`
+    scratch: Vec<i16>,
+    /// Reused reference window for `process_ts`
+    ref_scratch: Vec<i16>,
+    /// `None` = no double-talk detection (default)
+    double_talk: Option<DoubleTalkDetector>,
+    drift: DriftTracker,
+    /// `None` = output unlimited (default)
+    limiter: Option<OutputLimiter>,
+    /// RMS of what the last `process` call removed (see
+    /// `last_echo_estimate_rms`)
+    last_echo_estimate_rms: f32,
+    /// Speaker-to-mic latency subtracted from mic timestamps in `process_ts`
+    hardware_delay_us: u64,
 }
 
 impl EchoCanceller {
     /// Create a new echo canceller. Returns None if initialization fails.
     pub fn new() -> Option<Self> {
+        Self::with_preprocess(true)
+    }
+
+    /// Create an echo canceller with the speex preprocessor (its own noise
+    /// suppression + AGC) switched on or off. Disable it when the mic path
+    /// runs its own DSP so the two don't fight; echo cancellation itself is
+    /// unaffected. aec-rs doesn't expose the suppression level, so there is
+    /// no finer control than on/off.
+    pub fn with_preprocess(enabled: bool) -> Option<Self> {
         let result = std::panic::catch_unwind(|| {
             let config = AecConfig {
                 frame_size: AEC_FRAME_SIZE,
                 filter_length: AEC_FILTER_LENGTH as i32,
                 sample_rate: AEC_SAMPLE_RATE,
-                enable_preprocess: true,
+                enable_preprocess: enabled,
             };
             Aec::new(&config)
         });
 
         match result {
             Ok(aec) => {
-                println!("[EchoCanceller] Initialized (frame={}, filter={}, rate={})",
-                    AEC_FRAME_SIZE, AEC_FILTER_LENGTH, AEC_SAMPLE_RATE);
+                println!("[EchoCanceller] Initialized (frame={}, filter={}, rate={}, preprocess={})",
+                    AEC_FRAME_SIZE, AEC_FILTER_LENGTH, AEC_SAMPLE_RATE, enabled);
                 Some(EchoCanceller {
                     aec,
                     frame_size: AEC_FRAME_SIZE,
+                    scratch: Vec::new(),
+                    ref_scratch: Vec::new(),
+                    double_talk: None,
+                    drift: DriftTracker::new(),
+                    limiter: None,
+                    last_echo_estimate_rms: 0.0,
+                    hardware_delay_us: 0,
                 })
             }
             Err(e) => {
@@ -92,37 +587,173 @@ impl EchoCanceller {
 
     /// Process a mic frame through AEC. The frame is split into sub-frames
     /// matching the AEC frame size for best convergence.
+    ///
+    /// Sub-frames not fully covered by real reference audio (the buffer ran
+    /// short and was zero-padded) pass through unchanged, so the adaptive
+    /// filter never adapts against padding.
+    ///
+    /// Double-talk detection (`set_double_talk_detection`) only reports;
+    /// aec-rs has no way to freeze adaptation, and withholding the
+    /// reference would let the echo through uncancelled.
+    ///
+    /// If the reference was pushed with `push_reference_ts`, the pull is
+    /// first re-aligned for clock drift (see `drift_corrections`).
     pub fn process(&mut self, mic_frame: &[i16]) -> Vec<i16> {
-        let ref_samples = pull_reference(mic_frame.len());
+        let (ref_samples, ref_valid) = self.drift.pull(mic_frame.len());
+        self.cancel(mic_frame, &ref_samples, ref_valid)
+    }
+
+    /// Like `process`, but with the reference picked by time instead of
+    /// drained in arrival order: the frame's first sample was captured at
+    /// `mic_timestamp_us`, so the echo in it was played
+    /// `set_hardware_delay_us` earlier, and the reference covering that
+    /// span is assembled from frames pushed with `push_reference_us`.
+    /// Samples with no reference (not yet arrived, or lost) are treated
+    /// like a short buffer in `process`. Uses the same clock as the
+    /// reference timestamps.
+    pub fn process_ts(&mut self, mic_frame: &[i16], mic_timestamp_us: u64) -> Vec<i16> {
+        let start_us = mic_timestamp_us as i64 - self.hardware_delay_us as i64;
+        let mut ref_samples = std::mem::take(&mut self.ref_scratch);
+        ref_samples.resize(mic_frame.len(), 0);
+        let ref_valid = match get_ref_buffer().lock() {
+            Ok(mut guard) => guard.select_timed(start_us, &mut ref_samples),
+            Err(_) => 0,
+        };
+        let output = self.cancel(mic_frame, &ref_samples, ref_valid);
+        self.ref_scratch = ref_samples;
+        output
+    }
+
+    /// Speaker-to-mic latency in µs (output buffering, DAC/ADC, air) that
+    /// `process_ts` allows for: the reference is taken from this long
+    /// before each mic timestamp. Default 0.
+    pub fn set_hardware_delay_us(&mut self, delay_us: u64) {
+        self.hardware_delay_us = delay_us;
+    }
+
+    /// Cancel `mic_frame` against `ref_samples`, of which the first
+    /// `ref_valid` are real reference.
+    fn cancel(&mut self, mic_frame: &[i16], ref_samples: &[i16], ref_valid: usize) -> Vec<i16> {
         let mut output = Vec::with_capacity(mic_frame.len());
+        let mut removed_energy = 0.0f64;
 
-        for (mic_chunk, ref_chunk) in mic_frame
+        for (i, (mic_chunk, ref_chunk)) in mic_frame
             .chunks(self.frame_size)
             .zip(ref_samples.chunks(self.frame_size))
+            .enumerate()
         {
-            if mic_chunk.len() == self.frame_size && ref_chunk.len() == self.frame_size {
+            let referenced = (i + 1) * self.frame_size <= ref_valid;
+            if let Some(detector) = self.double_talk.as_mut() {
+                detector.update(mic_chunk, ref_chunk);
+            }
+            if referenced && mic_chunk.len() == self.frame_size && ref_chunk.len() == self.frame_size {
                 let mut out_buf = vec![0i16; self.frame_size];
                 self.aec.cancel_echo(mic_chunk, ref_chunk, &mut out_buf);
+                removed_energy += mic_chunk.iter().zip(&out_buf)
+                    .map(|(&mic, &out)| (mic as f64 - out as f64).powi(2))
+                    .sum::<f64>();
                 output.extend_from_slice(&out_buf);
             } else {
-                // Partial sub-frame at the end — pass through unchanged
+                // Partial sub-frame at the end or no reference — pass through unchanged
                 output.extend_from_slice(mic_chunk);
             }
         }
 
+        self.last_echo_estimate_rms = if mic_frame.is_empty() {
+            0.0
+        } else {
+            ((removed_energy / mic_frame.len() as f64).sqrt() / 32768.0) as f32
+        };
+
+        if let Some(limiter) = self.limiter.as_mut() {
+            limiter.process(&mut output);
+        }
         output
     }
+
+    /// RMS (linear, full scale 1.0) of the signal the canceller subtracted
+    /// over the last `process`/`process_ts` call, i.e. its estimate of the echo removed,
+    /// for logging. aec-rs doesn't expose its echo estimate, so this is the
+    /// RMS of mic input minus cancelled output, before the output ceiling.
+    /// Pass-through sub-frames count as nothing removed. Unlike an ERLE
+    /// ratio it is an absolute level, so it reads 0 during silence.
+    pub fn last_echo_estimate_rms(&self) -> f32 {
+        self.last_echo_estimate_rms
+    }
+
+    /// Cap the output at `ceiling_db` (peak, dBFS, ≤ 0) with a fast
+    /// limiter, so residual echo spikes can't startle the listener.
+    /// `None` (default) leaves the output unlimited.
+    pub fn set_output_ceiling(&mut self, ceiling_db: Option<f32>) -> Result<()> {
+        if let Some(db) = ceiling_db {
+            ensure!(db.is_finite() && db <= 0.0, "output ceiling must be <= 0 dBFS, got {}", db);
+        }
+        self.limiter = ceiling_db.map(OutputLimiter::new);
+        Ok(())
+    }
+
+    /// Flag sub-frames where the mic peak exceeds `threshold` (> 0, e.g.
+    /// `DOUBLE_TALK_THRESHOLD`) times the recent far-end peak as
+    /// double-talk, for `is_double_talk`. Echo paths with less acoustic
+    /// loss than the threshold allows read as double-talk throughout.
+    /// `None` (default) turns detection off.
+    pub fn set_double_talk_detection(&mut self, threshold: Option<f32>) -> Result<()> {
+        if let Some(threshold) = threshold {
+            ensure!(threshold.is_finite() && threshold > 0.0,
+                "double-talk threshold must be finite and > 0, got {}", threshold);
+        }
+        self.double_talk = threshold.map(DoubleTalkDetector::new);
+        Ok(())
+    }
+
+    /// Whether near-end speech was detected over far-end audio in the most
+    /// recent sub-frames; always false with detection off.
+    pub fn is_double_talk(&self) -> bool {
+        self.double_talk.as_ref().is_some_and(DoubleTalkDetector::is_active)
+    }
+
+    /// Reference samples dropped or repeated to correct clock drift. Grows
+    /// at roughly the clocks' rate mismatch (100 ppm ≈ 1.6 per second).
+    pub fn drift_corrections(&self) -> u64 {
+        self.drift.corrections
+    }
+
+    /// f32 variant of `process`. aec-rs only works on i16, so the frame is
+    /// quantized through a reused scratch buffer and converted back.
+    pub fn process_f32(&mut self, mic_frame: &[f32]) -> Vec<f32> {
+        let mut scratch = std::mem::take(&mut self.scratch);
+        scratch.resize(mic_frame.len(), 0);
+        f32_to_i16_into(mic_frame, &mut scratch);
+        let output = self.process(&scratch);
+        self.scratch = scratch;
+        i16_to_f32(&output)
+    }
+}
+
+/// The reference buffer is process-global; tests that touch it (here or
+/// in other modules) take this lock so parallel test threads don't
+/// interleave pushes/pulls.
+#[cfg(test)]
+pub fn lock_reference() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_signals::{make_sine, make_white_noise};
+
+    fn rms_i16(samples: &[i16]) -> f32 {
+        (samples.iter().map(|&s| (s as f32).powi(2)).sum::<f32>() / samples.len().max(1) as f32).sqrt()
+    }
 
     #[test]
     fn test_push_pull_reference() {
+        let _guard = lock_reference();
         clear_reference();
         let frame = vec![100i16; 320];
-        push_reference(&frame);
+        push_reference(&frame).unwrap();
         let pulled = pull_reference(320);
         assert_eq!(pulled.len(), 320);
         assert_eq!(pulled[0], 100);
@@ -130,21 +761,209 @@ mod tests {
 
     #[test]
     fn test_pull_empty_returns_zeros() {
+        let _guard = lock_reference();
         clear_reference();
         let pulled = pull_reference(320);
         assert_eq!(pulled.len(), 320);
         assert!(pulled.iter().all(|&s| s == 0));
     }
 
+    #[test]
+    fn test_pull_partial_pads_with_zeros() {
+        let _guard = lock_reference();
+        clear_reference();
+        push_reference(&[7i16; 100]).unwrap();
+        let (pulled, valid) = pull_reference_counted(320);
+        assert_eq!(valid, 100);
+        assert_eq!(pulled.len(), 320);
+        assert!(pulled[..100].iter().all(|&s| s == 7));
+        assert!(pulled[100..].iter().all(|&s| s == 0));
+    }
+
+    #[test]
+    fn test_push_reference_resampled() {
+        let _guard = lock_reference();
+        clear_reference();
+        // 100ms at 48kHz in 10ms frames → ~1600 samples at 16kHz
+        let reference = f32_to_i16(&make_sine(440.0, 0.3, 48000.0, 4800));
+        for frame in reference.chunks(480) {
+            push_reference_resampled(frame, 48000).unwrap();
+        }
+        let buffered = reference_len();
+        // Short by at most the resampler's filter latency
+        assert!((1580..=1600).contains(&buffered), "Expected ~1600 samples at 16kHz, got {}", buffered);
+        assert!(push_reference_resampled(&reference[..480], 0).is_err());
+    }
+
     #[test]
     fn test_buffer_capacity_cap() {
+        let _guard = lock_reference();
         clear_reference();
         // Push more than capacity
         let big_frame = vec![42i16; REF_BUFFER_CAPACITY + 1000];
-        push_reference(&big_frame);
-        let buf = get_ref_buffer();
-        let guard = buf.lock().unwrap();
-        assert!(guard.len() <= REF_BUFFER_CAPACITY);
+        push_reference(&big_frame).unwrap();
+        assert_eq!(reference_len(), REF_BUFFER_CAPACITY);
+        assert_eq!(dropped_reference_samples(), 1000);
+    }
+
+    /// Run `f` with a small reference buffer, restoring the default after.
+    fn with_reference_config(overflow: OverflowPolicy, f: impl FnOnce()) {
+        clear_reference();
+        configure_reference(ReferenceConfig { capacity: 100, overflow, ..Default::default() }).unwrap();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+        configure_reference(ReferenceConfig::default()).unwrap();
+        clear_reference();
+        if let Err(e) = result {
+            std::panic::resume_unwind(e);
+        }
+    }
+
+    #[test]
+    fn test_overflow_drop_oldest() {
+        let _guard = lock_reference();
+        with_reference_config(OverflowPolicy::DropOldest, || {
+            push_reference(&[1i16; 80]).unwrap();
+            push_reference(&[2i16; 50]).unwrap();
+            assert_eq!(reference_len(), 100);
+            assert_eq!(dropped_reference_samples(), 30);
+            let pulled = pull_reference(100);
+            assert!(pulled[..50].iter().all(|&s| s == 1));
+            assert!(pulled[50..].iter().all(|&s| s == 2));
+        });
+    }
+
+    #[test]
+    fn test_overflow_drop_newest() {
+        let _guard = lock_reference();
+        with_reference_config(OverflowPolicy::DropNewest, || {
+            push_reference(&[1i16; 80]).unwrap();
+            push_reference(&[2i16; 50]).unwrap();
+            assert_eq!(reference_len(), 100);
+            assert_eq!(dropped_reference_samples(), 30);
+            let pulled = pull_reference(100);
+            assert!(pulled[..80].iter().all(|&s| s == 1));
+            assert!(pulled[80..].iter().all(|&s| s == 2));
+        });
+    }
+
+    #[test]
+    fn test_overflow_error() {
+        let _guard = lock_reference();
+        with_reference_config(OverflowPolicy::Error, || {
+            push_reference(&[1i16; 80]).unwrap();
+            assert!(push_reference(&[2i16; 50]).is_err());
+            // The rejected frame is dropped whole; what was buffered stays
+            assert_eq!(reference_len(), 80);
+            assert_eq!(dropped_reference_samples(), 50);
+            push_reference(&[3i16; 20]).unwrap();
+            assert_eq!(reference_len(), 100);
+        });
+    }
+
+    #[test]
+    fn test_configure_reference() {
+        let _guard = lock_reference();
+        clear_reference();
+        assert!(configure_reference(ReferenceConfig { capacity: 0, ..Default::default() }).is_err());
+        push_reference(&[5i16; 300]).unwrap();
+        // Shrinking below the buffered amount trims the oldest samples
+        configure_reference(ReferenceConfig { capacity: 100, ..Default::default() }).unwrap();
+        assert_eq!(reference_len(), 100);
+        assert_eq!(dropped_reference_samples(), 200);
+        configure_reference(ReferenceConfig::default()).unwrap();
+        clear_reference();
+        assert_eq!(dropped_reference_samples(), 0);
+    }
+
+    #[test]
+    fn test_timestamped_reference_tracks_drift() {
+        let _guard = lock_reference();
+        clear_reference();
+        const FRAME: usize = 160;
+        // The far-end device runs 200 ppm fast: it delivers more samples
+        // per mic second than the mic consumes
+        const RATE_RATIO: f64 = 1.0002;
+        // Each reference sample encodes the mic time it was played at, so
+        // the pulled value minus the mic position is the lag
+        let mic_time = |k: u64| (k as f64 / RATE_RATIO) as u64;
+
+        let mut tracker = DriftTracker::new();
+        let mut produced = 0u64;
+        let mut lags = Vec::new();
+        // 10 minutes of 10ms frames
+        for frame in 0..60_000u64 {
+            let mic_position = frame * FRAME as u64;
+            // Keep ~20ms of reference buffered ahead of the mic
+            while mic_time(produced) < mic_position + 2 * FRAME as u64 {
+                let samples: Vec<i16> = (produced..produced + FRAME as u64)
+                    .map(|k| (mic_time(k) % 30_000) as i16)
+                    .collect();
+                push_reference_ts(&samples, mic_time(produced)).unwrap();
+                produced += FRAME as u64;
+            }
+            let (pulled, valid) = tracker.pull(FRAME);
+            assert_eq!(valid, FRAME);
+            let lag = (pulled[0] as i64 - (mic_position % 30_000) as i64 + 15_000).rem_euclid(30_000) - 15_000;
+            lags.push(lag);
+        }
+
+        // Uncorrected the lag would creep by ~1900 samples; corrected it
+        // stays within a couple of samples and the buffer doesn't grow
+        let spread = lags.iter().max().unwrap() - lags.iter().min().unwrap();
+        assert!(spread <= 2, "Reference lag wandered by {} samples", spread);
+        assert!(tracker.corrections > 1500, "Expected ~1900 corrections, got {}", tracker.corrections);
+        assert!(reference_len() < 4 * FRAME, "Buffer grew to {}", reference_len());
+        clear_reference();
+    }
+
+    #[test]
+    fn test_timed_reference_selects_by_timestamp() {
+        let _guard = lock_reference();
+        clear_reference();
+        // 20ms frames, constant per frame so the source is easy to read,
+        // arriving out of order
+        const FRAME: usize = 320;
+        const FRAME_US: u64 = 20_000;
+        for ts in [2 * FRAME_US, 0, FRAME_US] {
+            push_reference_us(&vec![1000 + (ts / 10) as i16; FRAME], ts);
+        }
+        let mut window = [0i16; FRAME];
+        let valid = get_ref_buffer().lock().unwrap().select_timed(10_000, &mut window);
+        assert_eq!(valid, FRAME);
+        assert!(window[..FRAME / 2].iter().all(|&s| s == 1000), "first half from the frame at 0");
+        assert!(window[FRAME / 2..].iter().all(|&s| s == 3000), "second half from the frame at 20ms");
+        // The frame at 0 is used up; a window past the last frame is padded
+        let valid = get_ref_buffer().lock().unwrap().select_timed(50_000, &mut window);
+        assert_eq!(valid, FRAME / 2);
+        assert!(window[FRAME / 2..].iter().all(|&s| s == 0));
+        assert_eq!(get_ref_buffer().lock().unwrap().timed_len, 0);
+        // Used-up frames' buffers are pooled and taken by the next push
+        assert_eq!(get_ref_buffer().lock().unwrap().timed_pool.len(), 3);
+        push_reference_us(&[1; FRAME], 60_000);
+        assert_eq!(get_ref_buffer().lock().unwrap().timed_pool.len(), 2);
+        clear_reference();
+
+        // Pure echo 30ms behind the far end, reference frames arriving in
+        // swapped pairs: picking by timestamp still cancels it
+        const DELAY_US: u64 = 30_000;
+        let mut ec = EchoCanceller::with_preprocess(false).expect("should init");
+        ec.set_hardware_delay_us(DELAY_US);
+        let far = f32_to_i16(&make_white_noise(0.3, 9, FRAME * 200));
+        let mic: Vec<i16> = far.iter().map(|&s| s / 2).collect();
+        let frames: Vec<&[i16]> = far.chunks(FRAME).collect();
+        let mut output = Vec::new();
+        for (k, mic_frame) in mic.chunks(FRAME).enumerate() {
+            if k % 2 == 0 {
+                for j in [k + 1, k] {
+                    push_reference_us(frames[j], j as u64 * FRAME_US);
+                }
+            }
+            output.extend(ec.process_ts(mic_frame, k as u64 * FRAME_US + DELAY_US));
+        }
+        let tail = FRAME * 150..;
+        let residual = rms_i16(&output[tail.clone()]) / rms_i16(&mic[tail]);
+        assert!(residual < 0.5, "Echo should be cancelled: residual={:.3}", residual);
+        clear_reference();
     }
 
     #[test]
@@ -155,13 +974,232 @@ mod tests {
 
     #[test]
     fn test_echo_canceller_process() {
+        let _guard = lock_reference();
         clear_reference();
         let mut ec = EchoCanceller::new().expect("should init");
         // Push reference then process mic frame
         let ref_frame = vec![500i16; 320];
-        push_reference(&ref_frame);
+        push_reference(&ref_frame).unwrap();
         let mic_frame = vec![500i16; 320];
         let output = ec.process(&mic_frame);
         assert_eq!(output.len(), 320);
     }
+
+    #[test]
+    fn test_echo_estimate_high_for_pure_echo() {
+        let _guard = lock_reference();
+        clear_reference();
+        let mut ec = EchoCanceller::new().expect("should init");
+        // Mic hears exactly the reference
+        let far = f32_to_i16(&make_white_noise(0.3, 6, 320 * 100));
+        let mic = far.clone();
+        let mut estimate = 0.0;
+        for (mic_frame, far_frame) in mic.chunks(320).zip(far.chunks(320)) {
+            push_reference(far_frame).unwrap();
+            ec.process(mic_frame);
+            estimate = ec.last_echo_estimate_rms();
+        }
+        let mic_rms = rms_i16(&mic[320 * 99..]) / 32768.0;
+        assert!(estimate > 0.7 * mic_rms,
+            "Most of the mic should be estimated as echo: {:.4} of {:.4}", estimate, mic_rms);
+
+        // Nothing referenced, nothing removed
+        clear_reference();
+        ec.process(&mic[..320]);
+        assert_eq!(ec.last_echo_estimate_rms(), 0.0);
+    }
+
+    #[test]
+    fn test_unreferenced_subframes_pass_through() {
+        let _guard = lock_reference();
+        let mut ec = EchoCanceller::new().expect("should init");
+        clear_reference();
+        // Reference covers only the first of two sub-frames
+        push_reference(&f32_to_i16(&make_white_noise(0.3, 3, AEC_FRAME_SIZE))).unwrap();
+        let mic = f32_to_i16(&make_white_noise(0.2, 4, AEC_FRAME_SIZE * 2));
+        let output = ec.process(&mic);
+        assert_eq!(output.len(), mic.len());
+        assert_eq!(&output[AEC_FRAME_SIZE..], &mic[AEC_FRAME_SIZE..]);
+    }
+
+    #[test]
+    fn test_f32_path_matches_i16_path() {
+        let _guard = lock_reference();
+        let far = make_white_noise(0.3, 5, 320 * 20);
+        let mic: Vec<f32> = far.iter().map(|s| s * 0.5).collect();
+
+        let mut ec_i16 = EchoCanceller::new().expect("should init");
+        let mut ec_f32 = EchoCanceller::new().expect("should init");
+        clear_reference();
+        for (mic_frame, far_frame) in mic.chunks(320).zip(far.chunks(320)) {
+            push_reference(&f32_to_i16(far_frame)).unwrap();
+            let expected = i16_to_f32(&ec_i16.process(&f32_to_i16(mic_frame)));
+
+            push_reference_f32(far_frame).unwrap();
+            let actual = ec_f32.process_f32(mic_frame);
+
+            assert_eq!(actual.len(), mic_frame.len());
+            for (a, e) in actual.iter().zip(&expected) {
+                assert!((a - e).abs() <= 1.0 / 32768.0, "{} vs {}", a, e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_echo_canceller_without_preprocess() {
+        let _guard = lock_reference();
+        const FRAME: usize = 320;
+        const ECHO_DELAY: usize = 40;
+        let mut ec = EchoCanceller::with_preprocess(false).expect("should init");
+
+        // No reference: nothing to cancel, and with preprocess off nothing
+        // else (NS/AGC) should touch the level either
+        clear_reference();
+        let near = f32_to_i16(&make_white_noise(0.1, 1, FRAME * 50));
+        let mut passthrough = Vec::new();
+        for frame in near.chunks(FRAME) {
+            passthrough.extend(ec.process(frame));
+        }
+        let level = rms_i16(&passthrough[FRAME * 25..]) / rms_i16(&near[FRAME * 25..]);
+        assert!((level - 1.0).abs() < 0.1, "Passthrough level changed: {:.3}", level);
+
+        // Mic hears only a delayed, attenuated copy of the far end
+        let mut ec = EchoCanceller::with_preprocess(false).expect("should init");
+        clear_reference();
+        let far = f32_to_i16(&make_white_noise(0.3, 2, FRAME * 200 + ECHO_DELAY));
+        let mic: Vec<i16> = far[..FRAME * 200].iter().map(|&s| s / 2).collect();
+        let far = &far[ECHO_DELAY..];
+        let mut output = Vec::new();
+        for (mic_frame, far_frame) in mic.chunks(FRAME).zip(far.chunks(FRAME)) {
+            push_reference(far_frame).unwrap();
+            output.extend(ec.process(mic_frame));
+        }
+        let tail = FRAME * 150..;
+        let erle = rms_i16(&output[tail.clone()]) / rms_i16(&mic[tail]);
+        assert!(erle < 0.5, "Echo should still be cancelled: residual={:.3}", erle);
+    }
+
+    #[test]
+    fn test_double_talk_detection() {
+        let _guard = lock_reference();
+        const FRAME: usize = 320;
+        const ECHO_DELAY: usize = 40;
+        let far = f32_to_i16(&make_white_noise(0.3, 6, FRAME * 60 + ECHO_DELAY));
+        // Echo with 12 dB of acoustic loss
+        let echo: Vec<i16> = far[..FRAME * 60].iter().map(|&s| s / 4).collect();
+        let far = &far[ECHO_DELAY..];
+
+        // Far end only: the mic hears just the echo
+        let mut ec = EchoCanceller::with_preprocess(false).expect("should init");
+        assert!(ec.set_double_talk_detection(Some(0.0)).is_err());
+        assert!(ec.set_double_talk_detection(Some(f32::NAN)).is_err());
+        ec.set_double_talk_detection(Some(DOUBLE_TALK_THRESHOLD)).unwrap();
+        clear_reference();
+        for (mic_frame, far_frame) in echo.chunks(FRAME).zip(far.chunks(FRAME)) {
+            push_reference(far_frame).unwrap();
+            ec.process(mic_frame);
+            assert!(!ec.is_double_talk(), "Echo alone must not trigger double-talk");
+        }
+
+        // Near-end talker on top of the echo
+        let near = f32_to_i16(&make_white_noise(0.4, 7, FRAME * 60));
+        let mut ec = EchoCanceller::with_preprocess(false).expect("should init");
+        ec.set_double_talk_detection(Some(DOUBLE_TALK_THRESHOLD)).unwrap();
+        clear_reference();
+        let mut flagged = 0;
+        for ((echo_frame, near_frame), far_frame) in
+            echo.chunks(FRAME).zip(near.chunks(FRAME)).zip(far.chunks(FRAME))
+        {
+            let mic: Vec<i16> = echo_frame.iter().zip(near_frame).map(|(&e, &n)| e.saturating_add(n)).collect();
+            push_reference(far_frame).unwrap();
+            ec.process(&mic);
+            if ec.is_double_talk() {
+                flagged += 1;
+            }
+        }
+        assert!(flagged >= 55, "Double-talk should be detected: {} of 60 frames", flagged);
+    }
+
+    #[test]
+    fn test_zero_db_echo_path_cancelled_by_default() {
+        let _guard = lock_reference();
+        const FRAME: usize = 320;
+        const ECHO_DELAY: usize = 40;
+        let far = f32_to_i16(&make_white_noise(0.3, 8, FRAME * 200 + ECHO_DELAY));
+        // No acoustic loss: the mic hears the far end at full level
+        let mic = far[..FRAME * 200].to_vec();
+        let far = &far[ECHO_DELAY..];
+
+        let mut ec = EchoCanceller::with_preprocess(false).expect("should init");
+        clear_reference();
+        let mut output = Vec::new();
+        for (mic_frame, far_frame) in mic.chunks(FRAME).zip(far.chunks(FRAME)) {
+            push_reference(far_frame).unwrap();
+            output.extend(ec.process(mic_frame));
+            assert!(!ec.is_double_talk(), "Detection should be off by default");
+        }
+        let tail = FRAME * 150..;
+        let residual = rms_i16(&output[tail.clone()]) / rms_i16(&mic[tail]);
+        assert!(residual < 0.5, "0 dB echo should be cancelled: residual={:.3}", residual);
+    }
+
+    #[test]
+    fn test_output_ceiling_limits_residual_spike() {
+        let _guard = lock_reference();
+        let mut ec = EchoCanceller::with_preprocess(false).expect("should init");
+        assert!(ec.set_output_ceiling(Some(1.0)).is_err());
+        ec.set_output_ceiling(Some(-12.0)).unwrap();
+        let ceiling = (32767.0 * 10.0f32.powf(-12.0 / 20.0)) as i16;
+        clear_reference();
+
+        // Quiet residual passes untouched; a burst is held to the ceiling
+        let quiet = f32_to_i16(&make_white_noise(0.05, 8, 320));
+        assert_eq!(ec.process(&quiet), quiet);
+        let spike = f32_to_i16(&make_white_noise(0.9, 9, 320));
+        let output = ec.process(&spike);
+        let peak = output.iter().map(|s| s.unsigned_abs()).max().unwrap();
+        assert!(peak <= ceiling as u16, "Spike peak {} above ceiling {}", peak, ceiling);
+        assert!(peak as f32 > 0.9 * ceiling as f32, "Limiter should hold the spike near the ceiling");
+
+        // Once the spike has passed the gain recovers
+        for _ in 0..20 {
+            ec.process(&quiet);
+        }
+        let recovered = ec.process(&quiet);
+        assert!((rms_i16(&recovered) / rms_i16(&quiet) - 1.0).abs() < 0.01);
+
+        ec.set_output_ceiling(None).unwrap();
+        assert_eq!(ec.process(&spike), spike);
+    }
+
+    #[test]
+    fn test_inverted_reference_cancels_with_flag() {
+        let _guard = lock_reference();
+        const FRAME: usize = 320;
+        const ECHO_DELAY: usize = 40;
+        let far = f32_to_i16(&make_white_noise(0.3, 12, FRAME * 200 + ECHO_DELAY));
+        let mic: Vec<i16> = far[..FRAME * 200].iter().map(|&s| s / 2).collect();
+        let far = &far[ECHO_DELAY..];
+
+        let residual = |inverted: bool| {
+            configure_reference(ReferenceConfig { invert_polarity: inverted, ..Default::default() }).unwrap();
+            clear_reference();
+            let mut ec = EchoCanceller::with_preprocess(false).expect("should init");
+            let mut output = Vec::new();
+            for (mic_frame, far_frame) in mic.chunks(FRAME).zip(far.chunks(FRAME)) {
+                let pushed: Vec<i16> =
+                    far_frame.iter().map(|&s| if inverted { s.saturating_neg() } else { s }).collect();
+                push_reference(&pushed).unwrap();
+                output.extend(ec.process(mic_frame));
+            }
+            let tail = FRAME * 150..;
+            rms_i16(&output[tail.clone()]) / rms_i16(&mic[tail])
+        };
+        let plain = residual(false);
+        let inverted = residual(true);
+        configure_reference(ReferenceConfig::default()).unwrap();
+        assert!(plain < 0.5, "Echo should be cancelled: residual={:.3}", plain);
+        assert!((inverted - plain).abs() < 0.01,
+            "Inverted tap with the flag should cancel as well: {:.3} vs {:.3}", inverted, plain);
+    }
 }
@@ -0,0 +1,75 @@
+// Attack/release envelope follower
+//
+// Shared level detector for the dynamics stages. One sample in, one
+// envelope value out:
+//   - input above the envelope: one-pole rise with `attack_coeff`
+//     (1.0 = instant, the envelope jumps straight to the input)
+//   - input at or below it: exponential decay towards zero with
+//     `release_coeff`, independent of the input (peak-hold style, so short
+//     dips between peaks don't drag the envelope down faster)
+//
+// Coefficients are per-sample alphas: alpha = 1 - exp(-1 / (rate * time_s)).
+// Feed rectified (absolute) samples for a peak envelope.
+
+pub struct EnvelopeFollower {
+    attack_coeff: f32,
+    /// Per-sample decay multiplier, `1 - release_coeff`
+    release_decay: f32,
+    envelope: f32,
+}
+
+impl EnvelopeFollower {
+    pub fn new(attack_coeff: f32, release_coeff: f32) -> Self {
+        Self {
+            attack_coeff: attack_coeff.clamp(0.0, 1.0),
+            release_decay: 1.0 - release_coeff.clamp(0.0, 1.0),
+            envelope: 0.0,
+        }
+    }
+
+    #[inline]
+    pub fn process_sample(&mut self, input: f32) -> f32 {
+        if input > self.envelope {
+            if self.attack_coeff >= 1.0 {
+                self.envelope = input;
+            } else {
+                self.envelope += self.attack_coeff * (input - self.envelope);
+            }
+        } else {
+            self.envelope *= self.release_decay;
+        }
+        self.envelope
+    }
+
+    /// Current envelope value without advancing.
+    pub fn value(&self) -> f32 {
+        self.envelope
+    }
+
+    pub fn reset(&mut self) {
+        self.envelope = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finite_attack_rises_gradually() {
+        let mut env = EnvelopeFollower::new(0.1, 0.001);
+        let first = env.process_sample(1.0);
+        assert!((first - 0.1).abs() < 1e-6);
+        for _ in 0..200 {
+            env.process_sample(1.0);
+        }
+        assert!((env.value() - 1.0).abs() < 1e-3);
+
+        // Release ignores the input level and decays towards zero
+        let before = env.value();
+        let after = env.process_sample(0.5);
+        assert_eq!(after, before * (1.0 - 0.001));
+        env.reset();
+        assert_eq!(env.value(), 0.0);
+    }
+}
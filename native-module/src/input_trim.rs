@@ -0,0 +1,139 @@
+// Coarse input trim ahead of the compressor
+//
+// The compressor only acts above its threshold (-20 dBFS RMS by default).
+// A capture sitting at -45 dBFS never gets there, so the compressor does
+// nothing and the normalizer ends up supplying all the gain on its own,
+// noise included. This stage is the coarse half of a two-stage AGC: it
+// slowly lifts the long-term level into the compressor's working range
+// (a few dB above threshold) and leaves the fast, fine work to the
+// compressor and normalizer behind it.
+//
+//   long-term RMS of non-silent blocks (~200ms) → desired = target / level,
+//   clamped to 0..+30 dB; gain follows desired with a ~2s time constant
+//
+// Boost only: a hot input is the compressor's job, not the trim's. Blocks
+// below the silence floor neither feed the level nor move the gain, so
+// pauses don't wind it up.
+
+use crate::levels::NoiseFloor;
+use crate::stage::AudioStage;
+
+/// Long-term level the trim aims for: -14 dBFS RMS, 6 dB above the
+/// compressor's default threshold
+const TRIM_TARGET_RMS: f32 = 0.2;
+/// Maximum boost (+30 dB)
+const TRIM_MAX_GAIN: f32 = 31.6;
+/// Level averaging time constant: 200ms at 48kHz
+const TRIM_LEVEL_SAMPLES: f32 = 9600.0;
+/// Gain adaptation: ~2s at 48kHz
+/// alpha = 1 - exp(-1 / (48000 * 2)) ≈ 0.0000104
+const TRIM_GAIN_COEFF: f32 = 0.0000104;
+/// Default block RMS below which the trim holds (~-70 dBFS)
+const TRIM_SILENCE_FLOOR: f32 = 0.0003;
+
+pub struct InputTrim {
+    /// Mean-square of recent non-silent blocks (0 = none seen yet)
+    power: f32,
+    gain: f32,
+    /// Block RMS at or below which the trim holds
+    silence_floor: f32,
+}
+
+impl InputTrim {
+    /// Trim starting at unity gain.
+    pub fn new() -> Self {
+        Self { power: 0.0, gain: 1.0, silence_floor: TRIM_SILENCE_FLOOR }
+    }
+
+    /// Hold at and below the calibrated noise floor instead of ~-70 dBFS.
+    pub fn set_noise_floor(&mut self, floor: NoiseFloor) {
+        self.silence_floor = floor.rms();
+    }
+
+    /// Current trim gain (linear, ≥ 1).
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Trim in-place. The level is measured per call, so feed it regular
+    /// blocks (e.g. 10ms) rather than whole recordings.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        if samples.is_empty() {
+            return;
+        }
+        let block_power = samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32;
+        if block_power.sqrt() <= self.silence_floor {
+            samples.iter_mut().for_each(|s| *s *= self.gain);
+            return;
+        }
+
+        // First signal seeds the average, so there's no warm-up overshoot
+        if self.power == 0.0 {
+            self.power = block_power;
+        } else {
+            let coeff = 1.0 - (-(samples.len() as f32) / TRIM_LEVEL_SAMPLES).exp();
+            self.power += coeff * (block_power - self.power);
+        }
+        let desired = (TRIM_TARGET_RMS / self.power.sqrt()).clamp(1.0, TRIM_MAX_GAIN);
+        for sample in samples.iter_mut() {
+            self.gain += TRIM_GAIN_COEFF * (desired - self.gain);
+            *sample *= self.gain;
+        }
+    }
+
+    /// Apply the current gain without measuring or adapting.
+    pub fn apply_held_gain(&self, samples: &mut [f32]) {
+        samples.iter_mut().for_each(|s| *s *= self.gain);
+    }
+
+    pub fn reset(&mut self) {
+        self.power = 0.0;
+        self.gain = 1.0;
+    }
+}
+
+impl Default for InputTrim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioStage for InputTrim {
+    fn process(&mut self, samples: &mut [f32]) {
+        InputTrim::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        InputTrim::reset(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{make_sine, rms};
+
+    #[test]
+    fn test_trim_lifts_quiet_input_and_holds_in_silence() {
+        let mut trim = InputTrim::new();
+        let mut quiet = make_sine(440.0, 0.01, 48000.0, 48000 * 10);
+        for block in quiet.chunks_mut(480) {
+            trim.process(block);
+        }
+        let level_db = 20.0 * rms(&quiet[48000 * 9..]).log10();
+        assert!((-16.0..-13.0).contains(&level_db), "Trimmed level {:.1} dBFS", level_db);
+
+        let gain = trim.gain();
+        for _ in 0..500 {
+            trim.process(&mut [0.0; 480]);
+        }
+        assert_eq!(trim.gain(), gain, "Gain should hold through silence");
+
+        // Already loud input is left alone
+        let mut trim = InputTrim::new();
+        let input = make_sine(440.0, 0.5, 48000.0, 48000);
+        let mut loud = input.clone();
+        trim.process(&mut loud);
+        assert_eq!(loud, input);
+    }
+}
@@ -0,0 +1,403 @@
+// Level measurement and distribution analysis
+//
+// `LevelHistogram` collects per-frame RMS levels into 1 dB bins over a
+// calibration period so thresholds can be picked from the user's real
+// audio: a low percentile (e.g. p10) approximates the noise floor, a high
+// one (e.g. p90) the speech level. `NoiseFloor` carries such a
+// calibrated floor to every stage that makes a silence decision.
+// `SlidingRms` is the running detector the dynamics stages share;
+// `SubWindowRms` splits the same window into sub-blocks and reports their
+// median or maximum, for detection that an isolated click can't swing;
+// `PeakMeter` is a purely observational peak-hold for VU-style display.
+
+use std::cmp::Ordering;
+
+use anyhow::{ensure, Result};
+use num_traits::{AsPrimitive, Float};
+
+/// Lowest level tracked; anything quieter (including digital silence)
+/// lands in the bottom bin
+const HISTOGRAM_MIN_DB: f32 = -100.0;
+/// Bin width in dB
+const HISTOGRAM_BIN_DB: f32 = 1.0;
+/// Bins from `HISTOGRAM_MIN_DB` up to 0 dBFS
+const HISTOGRAM_BINS: usize = 100;
+
+/// Root-mean-square level (0.0 for an empty buffer)
+pub fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+/// Sample precision the detector and the compressor/normalizer can run at:
+/// `f32` for real-time capture, `f64` for offline processing of long
+/// recordings, where the f32 running sum's rounding error builds up.
+pub trait Sample: Float + AsPrimitive<f32> + From<f32> + Send + 'static {}
+
+impl Sample for f32 {}
+impl Sample for f64 {}
+
+/// Sliding-window RMS detector, as used by the compressor, normalizer,
+/// gate and AGC. Until the window has filled, the mean is taken over the samples
+/// seen so far rather than the zero-padded window, so the first estimates
+/// after construction or a reset aren't biased low.
+///
+/// The window sum is updated incrementally, so every push adds a little
+/// rounding error that never leaves it. At f32 this shows up over long
+/// runs as a small bias in the reading (and can push the sum below zero on
+/// silence, hence the clamp); `SlidingRms<f64>` keeps it negligible.
+pub struct SlidingRms<T: Sample = f32> {
+    /// Circular buffer of squared samples; its length is the window
+    buffer: Vec<T>,
+    index: usize,
+    sum: T,
+    /// Samples pushed since the last reset, saturating at the window length
+    samples_seen: usize,
+}
+
+impl<T: Sample> SlidingRms<T> {
+    /// Detector over the last `window` samples (at least 1).
+    pub fn new(window: usize) -> Self {
+        Self {
+            buffer: vec![T::zero(); window.max(1)],
+            index: 0,
+            sum: T::zero(),
+            samples_seen: 0,
+        }
+    }
+
+    /// Add one sample and return the current RMS.
+    #[inline]
+    pub fn push(&mut self, input: T) -> T {
+        let sq = input * input;
+        self.sum = self.sum - self.buffer[self.index];
+        self.buffer[self.index] = sq;
+        self.sum = self.sum + sq;
+        self.index = (self.index + 1) % self.buffer.len();
+        self.samples_seen = (self.samples_seen + 1).min(self.buffer.len());
+        // Rounding can leave the sum slightly negative on silence
+        (self.sum.max(T::zero()) / (self.samples_seen as f32).into()).sqrt()
+    }
+
+    /// Window length in samples.
+    pub fn window(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill(T::zero());
+        self.index = 0;
+        self.sum = T::zero();
+        self.samples_seen = 0;
+    }
+}
+
+/// How a level detector combines its window.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Detection {
+    /// Mean square over the whole window (plain sliding RMS)
+    #[default]
+    Mean,
+    /// Median sub-window RMS: a click filling one sub-window is ignored
+    Median,
+    /// Loudest sub-window RMS: reacts to anything, like a short window
+    Max,
+}
+
+/// Level detector over `count` consecutive sub-windows, reporting the
+/// median or maximum of their RMS levels (or the mean, which matches
+/// `SlidingRms` at sub-window resolution). The reading updates each time
+/// a sub-window completes and holds in between; until the first one
+/// completes it is the RMS of the partial sub-window.
+pub struct SubWindowRms<T: Sample = f32> {
+    /// Mean squares of the most recent completed sub-windows (ring)
+    powers: Vec<T>,
+    index: usize,
+    filled: usize,
+    /// Sort scratch for the median
+    scratch: Vec<T>,
+    sub_len: usize,
+    /// Running sum and count of the sub-window in progress
+    sum: T,
+    count: usize,
+    statistic: Detection,
+    level: T,
+}
+
+impl<T: Sample> SubWindowRms<T> {
+    /// Split `window` samples into `count` sub-windows (each at least one
+    /// sample) and report `statistic` over them.
+    pub fn new(window: usize, count: usize, statistic: Detection) -> Self {
+        let count = count.max(1);
+        Self {
+            powers: vec![T::zero(); count],
+            index: 0,
+            filled: 0,
+            scratch: Vec::with_capacity(count),
+            sub_len: (window / count).max(1),
+            sum: T::zero(),
+            count: 0,
+            statistic,
+            level: T::zero(),
+        }
+    }
+
+    /// Add one sample and return the current level.
+    #[inline]
+    pub fn push(&mut self, input: T) -> T {
+        self.sum = self.sum + input * input;
+        self.count += 1;
+        if self.count < self.sub_len {
+            if self.filled == 0 {
+                return (self.sum / (self.count as f32).into()).sqrt();
+            }
+            return self.level;
+        }
+
+        self.powers[self.index] = self.sum / (self.sub_len as f32).into();
+        self.index = (self.index + 1) % self.powers.len();
+        self.filled = (self.filled + 1).min(self.powers.len());
+        self.sum = T::zero();
+        self.count = 0;
+        self.level = self.combine().sqrt();
+        self.level
+    }
+
+    /// Combine the completed sub-window powers.
+    fn combine(&mut self) -> T {
+        let powers = &self.powers[..self.filled];
+        match self.statistic {
+            Detection::Mean => powers.iter().fold(T::zero(), |acc, &p| acc + p) / (self.filled as f32).into(),
+            Detection::Max => powers.iter().fold(T::zero(), |acc, &p| acc.max(p)),
+            Detection::Median => {
+                self.scratch.clear();
+                self.scratch.extend_from_slice(powers);
+                let mid = self.scratch.len() / 2;
+                *self.scratch
+                    .select_nth_unstable_by(mid, |a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+                    .1
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.powers.fill(T::zero());
+        self.index = 0;
+        self.filled = 0;
+        self.sum = T::zero();
+        self.count = 0;
+        self.level = T::zero();
+    }
+}
+
+/// Peak-hold meter: jumps to new peaks instantly, then falls at a fixed
+/// rate in dB. Observational only; never touches the audio.
+pub struct PeakMeter {
+    peak: f32,
+    /// Per-sample multiplier for the configured fall rate
+    decay: f32,
+}
+
+impl PeakMeter {
+    /// Meter falling by `decay_per_ms` dB per millisecond (e.g. 0.02 for a
+    /// 20 dB/s fall) at `sample_rate`.
+    pub fn new(decay_per_ms: f32, sample_rate: f32) -> Self {
+        let samples_per_ms = sample_rate / 1000.0;
+        Self {
+            peak: 0.0,
+            decay: 10.0f32.powf(-decay_per_ms.max(0.0) / (20.0 * samples_per_ms)),
+        }
+    }
+
+    pub fn process(&mut self, samples: &[f32]) {
+        for &s in samples {
+            self.peak = s.abs().max(self.peak * self.decay);
+        }
+    }
+
+    /// Held peak, linear.
+    pub fn peak(&self) -> f32 {
+        self.peak
+    }
+
+    /// Held peak in dBFS (-200 for silence).
+    pub fn peak_db(&self) -> f32 {
+        20.0 * self.peak.max(1e-10).log10()
+    }
+
+    pub fn reset(&mut self) {
+        self.peak = 0.0;
+    }
+}
+
+/// A calibrated noise floor (RMS, dBFS). The stages each default to their
+/// own silence threshold; configuring them from one `NoiseFloor` makes
+/// them agree on what counts as silence, so borderline audio can't be
+/// held as silence by one stage and passed as signal by the next.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoiseFloor {
+    db: f32,
+}
+
+impl NoiseFloor {
+    /// Floor at `db` dBFS RMS, e.g. a `LevelHistogram` low percentile.
+    /// Must be finite and below 0.
+    pub fn from_db(db: f32) -> Result<Self> {
+        ensure!(db.is_finite() && db < 0.0, "noise floor must be below 0 dBFS, got {}", db);
+        Ok(Self { db })
+    }
+
+    pub fn db(&self) -> f32 {
+        self.db
+    }
+
+    /// Linear RMS
+    pub fn rms(&self) -> f32 {
+        10.0f32.powf(self.db / 20.0)
+    }
+}
+
+pub struct LevelHistogram {
+    bins: [u64; HISTOGRAM_BINS],
+    frames: u64,
+}
+
+impl LevelHistogram {
+    pub fn new() -> Self {
+        Self {
+            bins: [0; HISTOGRAM_BINS],
+            frames: 0,
+        }
+    }
+
+    /// Record one frame's RMS level. Empty frames are ignored.
+    pub fn observe(&mut self, frame: &[f32]) {
+        if frame.is_empty() {
+            return;
+        }
+        let db = 20.0 * rms(frame).max(1e-10).log10();
+        let bin = ((db - HISTOGRAM_MIN_DB) / HISTOGRAM_BIN_DB).floor();
+        self.bins[(bin.max(0.0) as usize).min(HISTOGRAM_BINS - 1)] += 1;
+        self.frames += 1;
+    }
+
+    /// Level in dBFS below which `percentile` (0–100) of observed frames
+    /// fall, at bin resolution (the bin's centre). `None` before any frame.
+    pub fn percentile(&self, percentile: f32) -> Option<f32> {
+        if self.frames == 0 {
+            return None;
+        }
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0) * self.frames as f32).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (i, &count) in self.bins.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(HISTOGRAM_MIN_DB + (i as f32 + 0.5) * HISTOGRAM_BIN_DB);
+            }
+        }
+        None
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn reset(&mut self) {
+        self.bins = [0; HISTOGRAM_BINS];
+        self.frames = 0;
+    }
+}
+
+impl Default for LevelHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{make_sine, make_white_noise};
+
+    #[test]
+    fn test_bimodal_percentiles() {
+        let mut hist = LevelHistogram::new();
+        // Quiet floor ≈ -63 dBFS RMS, speech ≈ -23 dBFS RMS, alternating
+        let quiet = make_sine(440.0, 0.001, 48000.0, 480);
+        let loud = make_sine(440.0, 0.1, 48000.0, 480);
+        for i in 0..200 {
+            hist.observe(if i % 2 == 0 { &quiet } else { &loud });
+        }
+        assert_eq!(hist.frames(), 200);
+
+        let p10 = hist.percentile(10.0).unwrap();
+        let p90 = hist.percentile(90.0).unwrap();
+        assert!((p10 + 63.0).abs() < 1.5, "p10 should sit on the quiet mode: {:.1}", p10);
+        assert!((p90 + 23.0).abs() < 1.5, "p90 should sit on the loud mode: {:.1}", p90);
+
+        hist.reset();
+        assert_eq!(hist.percentile(50.0), None);
+    }
+
+    #[test]
+    fn test_sliding_rms_partial_window() {
+        const WINDOW: usize = 480;
+        let signal = make_white_noise(0.3, 21, 50);
+        let mut detector = SlidingRms::new(WINDOW);
+        let mut reported = 0.0;
+        for &s in &signal {
+            reported = detector.push(s);
+        }
+        let truth = rms(&signal);
+        assert!((reported - truth).abs() < 1e-5,
+            "RMS after 50 samples should be exact: reported={:.6}, true={:.6}", reported, truth);
+
+        // Once full it is the plain windowed RMS; after a reset it starts over
+        let long = make_white_noise(0.3, 22, WINDOW * 3);
+        for &s in &long {
+            reported = detector.push(s);
+        }
+        assert!((reported - rms(&long[WINDOW * 2..])).abs() < 1e-4);
+        detector.reset();
+        assert!((detector.push(0.5) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_sub_window_statistics() {
+        // Eight 10-sample sub-windows; one holds a click
+        let mut signal = vec![0.1f32; 80];
+        signal[35] = 0.9;
+        let level = |statistic| {
+            let mut detector = SubWindowRms::new(80, 8, statistic);
+            signal.iter().fold(0.0, |_, &s| detector.push(s))
+        };
+        assert!((level(Detection::Median) - 0.1).abs() < 1e-6, "Median ignores the click");
+        let click_rms = ((0.01 * 9.0 + 0.81) / 10.0f32).sqrt();
+        assert!((level(Detection::Max) - click_rms).abs() < 1e-6);
+        assert!((level(Detection::Mean) - rms(&signal)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_peak_meter_holds_and_decays() {
+        // 20 dB/s fall
+        let mut meter = PeakMeter::new(0.02, 48000.0);
+        meter.process(&[0.0; 480]);
+        assert_eq!(meter.peak(), 0.0);
+
+        let mut transient = vec![0.01f32; 480];
+        transient[479] = -0.8;
+        meter.process(&transient);
+        assert!((meter.peak() - 0.8).abs() < 1e-6, "Peak should jump to the transient: {}", meter.peak());
+        let start_db = meter.peak_db();
+
+        // 1s later it has fallen 20 dB
+        meter.process(&vec![0.0f32; 48000]);
+        let fallen = start_db - meter.peak_db();
+        assert!((fallen - 20.0).abs() < 0.1, "Expected a 20 dB fall after 1s, got {:.2}", fallen);
+
+        meter.reset();
+        assert_eq!(meter.peak(), 0.0);
+    }
+}
@@ -22,9 +22,29 @@ pub mod echo_cancel;
 pub mod agc;
 pub mod compressor;
 pub mod pre_emphasis;
-
-// Keep old resampler module for compatibility
 pub mod resampler;
+pub mod dither;
+pub mod convert;
+pub mod biquad;
+pub mod multiband;
+pub mod stream_processor;
+pub mod dual_stream;
+pub mod segmented;
+pub mod envelope;
+pub mod loudness;
+pub mod weighting;
+pub mod levels;
+pub mod stage;
+pub mod saturator;
+pub mod input_trim;
+pub mod offline;
+pub mod mid_side;
+pub mod metrics;
+pub mod rng;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_signals;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod benchmarks;
 
 use crate::streaming_resampler::StreamingResampler;
 use crate::audio_config::{FRAME_SAMPLES, DSP_POLL_MS};
@@ -132,8 +152,7 @@ impl SystemAudioCapture {
 
                 // 2. DSP pipeline on raw f32 samples, then resample
                 if !raw_batch.is_empty() {
-                    pre_emphasis.process(&mut raw_batch);
-                    processor.process(&mut raw_batch);
+                    processor.process_pre_emphasized(&mut pre_emphasis, &mut raw_batch);
                     let resampled = resampler.resample(&raw_batch);
                     frame_buffer.extend(resampled);
                     raw_batch.clear();
@@ -143,8 +162,9 @@ impl SystemAudioCapture {
                 while frame_buffer.len() >= FRAME_SAMPLES {
                     let frame: Vec<i16> = frame_buffer.drain(0..FRAME_SAMPLES).collect();
 
-                    // Push to AEC reference buffer for mic echo cancellation
-                    echo_cancel::push_reference(&frame);
+                    // Push to AEC reference buffer for mic echo cancellation.
+                    // Overflow is counted by dropped_reference_samples().
+                    let _ = echo_cancel::push_reference(&frame);
 
                     // Send every frame - no silence suppression on system audio
                     tsfn.call(frame, ThreadsafeFunctionCallMode::NonBlocking);
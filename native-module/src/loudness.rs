@@ -0,0 +1,196 @@
+// ITU-R BS.1770 loudness measurement (LUFS)
+//
+// RMS doesn't track perceived loudness: low-frequency energy counts as much
+// as the presence band. BS.1770 weights the signal with the "K" curve
+// (a +4 dB high shelf above ~1.5 kHz and a ~38 Hz high-pass) before
+// measuring mean-square power:
+//
+//   loudness = -0.691 + 10 * log10(mean square of K-weighted signal)
+//
+// Integrated loudness uses 400ms blocks with 75% overlap and two gates:
+//   - absolute: blocks quieter than -70 LUFS are ignored (silence)
+//   - relative: blocks more than 10 LU below the mean of the remaining
+//     blocks are ignored (pauses, breaths)
+//
+// Unlike a whole-programme BS.1770 measurement, "integrated" here covers
+// only the last 30s of blocks: a live call can run for hours, and the
+// reading has to keep following the talker rather than their first hour.
+//
+// Mono, 48kHz only: the filter coefficients are the ones tabulated in the
+// standard for 48kHz.
+
+use std::collections::VecDeque;
+
+use crate::biquad::Biquad;
+
+/// Samples per 100ms step at 48kHz (gating blocks advance by this much)
+const STEP_SAMPLES: usize = 4800;
+/// Steps per 400ms gating block
+const STEPS_PER_BLOCK: usize = 4;
+/// Offset that makes a 1kHz full-scale sine read -3.01 LUFS
+const LOUDNESS_OFFSET: f64 = -0.691;
+/// Absolute gate
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Relative gate, below the absolute-gated mean
+const RELATIVE_GATE_LU: f64 = 10.0;
+/// Blocks kept for the integrated reading: 30s of 100ms steps. Bounds the
+/// meter's memory and the per-step gating scan
+const INTEGRATION_BLOCKS: usize = 300;
+
+fn loudness(mean_square: f64) -> f64 {
+    LOUDNESS_OFFSET + 10.0 * mean_square.max(1e-20).log10()
+}
+
+pub struct LufsMeter {
+    /// K-weighting stage 1: high shelf
+    shelf: Biquad,
+    /// K-weighting stage 2: high-pass
+    highpass: Biquad,
+    /// Sum of squares in the current 100ms step
+    step_sum: f64,
+    step_len: usize,
+    /// Mean squares of the most recent steps (ring)
+    steps: [f64; STEPS_PER_BLOCK],
+    steps_seen: usize,
+    /// Mean square of the last `INTEGRATION_BLOCKS` completed 400ms blocks
+    blocks: VecDeque<f64>,
+    /// Cached gated result, refreshed per completed block
+    integrated: Option<f32>,
+}
+
+impl LufsMeter {
+    pub fn new() -> Self {
+        Self {
+            shelf: Biquad::from_coefficients(
+                1.535_124_9,
+                -2.691_696_2,
+                1.198_392_8,
+                -1.690_659_3,
+                0.732_480_8,
+            ),
+            highpass: Biquad::from_coefficients(1.0, -2.0, 1.0, -1.990_047_5, 0.990_072_25),
+            step_sum: 0.0,
+            step_len: 0,
+            steps: [0.0; STEPS_PER_BLOCK],
+            steps_seen: 0,
+            blocks: VecDeque::with_capacity(INTEGRATION_BLOCKS),
+            integrated: None,
+        }
+    }
+
+    /// Feed 48kHz mono audio.
+    pub fn process(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            self.process_sample(sample);
+        }
+    }
+
+    #[inline]
+    pub fn process_sample(&mut self, sample: f32) {
+        let weighted = self.highpass.process_sample(self.shelf.process_sample(sample)) as f64;
+        self.step_sum += weighted * weighted;
+        self.step_len += 1;
+        if self.step_len == STEP_SAMPLES {
+            self.finish_step();
+        }
+    }
+
+    fn finish_step(&mut self) {
+        self.steps[self.steps_seen % STEPS_PER_BLOCK] = self.step_sum / STEP_SAMPLES as f64;
+        self.steps_seen += 1;
+        self.step_sum = 0.0;
+        self.step_len = 0;
+        if self.steps_seen >= STEPS_PER_BLOCK {
+            if self.blocks.len() == INTEGRATION_BLOCKS {
+                self.blocks.pop_front();
+            }
+            self.blocks.push_back(self.steps.iter().sum::<f64>() / STEPS_PER_BLOCK as f64);
+            self.integrated = self.compute_integrated();
+        }
+    }
+
+    fn compute_integrated(&self) -> Option<f32> {
+        let gated_mean = |threshold: f64| {
+            let (sum, count) = self
+                .blocks
+                .iter()
+                .filter(|&&z| loudness(z) > threshold)
+                .fold((0.0, 0usize), |(s, c), &z| (s + z, c + 1));
+            (count > 0).then(|| sum / count as f64)
+        };
+        let relative_gate = loudness(gated_mean(ABSOLUTE_GATE_LUFS)?) - RELATIVE_GATE_LU;
+        gated_mean(relative_gate.max(ABSOLUTE_GATE_LUFS)).map(|z| loudness(z) as f32)
+    }
+
+    /// Loudness of the most recent 400ms block (`None` until one completes).
+    pub fn momentary(&self) -> Option<f32> {
+        self.blocks.back().map(|&z| loudness(z) as f32)
+    }
+
+    /// Gated loudness over the last 30s, or since the last reset if that's
+    /// sooner (`None` until a block in that span is above the absolute
+    /// gate).
+    pub fn integrated(&self) -> Option<f32> {
+        self.integrated
+    }
+
+    pub fn reset(&mut self) {
+        self.shelf.reset();
+        self.highpass.reset();
+        self.step_sum = 0.0;
+        self.step_len = 0;
+        self.steps = [0.0; STEPS_PER_BLOCK];
+        self.steps_seen = 0;
+        self.blocks.clear();
+        self.integrated = None;
+    }
+}
+
+impl Default for LufsMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::make_sine;
+
+    #[test]
+    fn test_1khz_sine_calibration() {
+        // BS.1770: a 1kHz sine at -20 dBFS peak reads -23.01 LUFS
+        let mut meter = LufsMeter::new();
+        meter.process(&make_sine(1000.0, 0.1, 48000.0, 48000 * 3));
+        let lufs = meter.integrated().expect("should have a reading");
+        assert!((lufs + 23.01).abs() < 0.5, "Expected -23.0 LUFS, got {:.2}", lufs);
+    }
+
+    #[test]
+    fn test_gating_ignores_silence() {
+        let mut meter = LufsMeter::new();
+        meter.process(&make_sine(1000.0, 0.1, 48000.0, 48000 * 2));
+        let before = meter.integrated().unwrap();
+        meter.process(&vec![0.0; 48000 * 4]);
+        let after = meter.integrated().unwrap();
+        // Only the few blocks straddling the signal's end pass the gates
+        assert!((before - after).abs() < 0.5, "Silence should be gated: {:.2} -> {:.2}", before, after);
+
+        meter.reset();
+        meter.process(&vec![0.0; 48000]);
+        assert_eq!(meter.integrated(), None);
+        assert!(meter.momentary().is_some());
+    }
+
+    #[test]
+    fn test_integration_window_is_bounded() {
+        // 10s at -23 LUFS, then 40s at -43 LUFS: the loud start has left
+        // the window, so the reading follows the quieter talker
+        let mut meter = LufsMeter::new();
+        meter.process(&make_sine(1000.0, 0.1, 48000.0, 48000 * 10));
+        meter.process(&make_sine(1000.0, 0.01, 48000.0, 48000 * 40));
+        assert_eq!(meter.blocks.len(), INTEGRATION_BLOCKS);
+        let lufs = meter.integrated().unwrap();
+        assert!((lufs + 43.01).abs() < 0.5, "Expected -43.0 LUFS, got {:.2}", lufs);
+    }
+}
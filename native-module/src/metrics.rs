@@ -0,0 +1,42 @@
+// Buffer-level signal metrics
+//
+// Cheap one-shot measurements over a whole buffer, for runtime decisions
+// such as whether a clip needs compression at all (a low crest factor
+// means it is already dense). All return 0.0 for empty or silent input.
+
+pub use crate::levels::rms;
+
+/// Absolute peak level
+pub fn peak(samples: &[f32]) -> f32 {
+    samples.iter().map(|s| s.abs()).fold(0.0f32, f32::max)
+}
+
+/// Peak / RMS, linear (0.0 for empty or silent input)
+pub fn crest_factor(samples: &[f32]) -> f32 {
+    let r = rms(samples);
+    if r > 0.0 { peak(samples) / r } else { 0.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{make_impulse_train, make_sine};
+
+    #[test]
+    fn test_crest_factor() {
+        // Whole number of 100 Hz cycles
+        let sine = make_sine(100.0, 0.5, 48000.0, 48000);
+        assert!((crest_factor(&sine) - std::f32::consts::SQRT_2).abs() < 1e-3,
+            "Sine crest factor should be √2, got {}", crest_factor(&sine));
+        assert!((rms(&sine) - 0.5 / std::f32::consts::SQRT_2).abs() < 1e-4);
+
+        assert_eq!(crest_factor(&[0.0; 480]), 0.0);
+        assert_eq!(crest_factor(&[]), 0.0);
+        assert_eq!(rms(&[]), 0.0);
+
+        // One click per 10ms: crest = √480
+        let impulses = make_impulse_train(0.5, 480, 4800);
+        assert!((crest_factor(&impulses) - 480.0f32.sqrt()).abs() < 1e-3,
+            "Impulse crest factor {}", crest_factor(&impulses));
+    }
+}
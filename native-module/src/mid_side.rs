@@ -0,0 +1,256 @@
+// Mid/side processing for stereo monitoring
+//
+// Compressing L and R separately (or linked) squashes the stereo image
+// along with the level. Encoding to mid/side first lets the centre (voice)
+// be compressed hard while the side, which carries the width, is treated
+// gently:
+//
+//   M = (L + R) / 2,  S = (L - R) / 2
+//   L = M + S,        R = M - S
+//
+// The helpers work in-place on interleaved L/R (or M/S) pairs; the
+// processor runs one `SystemAudioProcessor` per channel on top of them.
+// Leveling and gating are driven by the mid only: the side follows the
+// mid's level gain so quiet ambience isn't raised to the voice's level,
+// and closes with the mid's gate instead of chopping on its own schedule.
+
+use crate::compressor::SystemAudioProcessor;
+
+/// Side-channel compression ratio, gentler than the mid's 4:1 default so
+/// the width isn't squashed along with the level
+pub const SIDE_COMPRESSION_RATIO: f32 = 2.0;
+
+/// Encode interleaved L/R pairs to M/S in-place. A trailing unpaired
+/// sample is left untouched.
+pub fn lr_to_ms(samples: &mut [f32]) {
+    for pair in samples.chunks_exact_mut(2) {
+        let (left, right) = (pair[0], pair[1]);
+        pair[0] = (left + right) * 0.5;
+        pair[1] = (left - right) * 0.5;
+    }
+}
+
+/// Decode interleaved M/S pairs back to L/R in-place, the inverse of
+/// `lr_to_ms`. A trailing unpaired sample is left untouched.
+pub fn ms_to_lr(samples: &mut [f32]) {
+    for pair in samples.chunks_exact_mut(2) {
+        let (mid, side) = (pair[0], pair[1]);
+        pair[0] = mid + side;
+        pair[1] = mid - side;
+    }
+}
+
+/// Scale `samples` by a gain ramped linearly from `from` to `to`, so a
+/// gain sampled once per block doesn't step at block boundaries.
+pub fn apply_gain_ramp(samples: &mut [f32], from: f32, to: f32) {
+    let step = (to - from) / samples.len().max(1) as f32;
+    for (i, sample) in samples.iter_mut().enumerate() {
+        *sample *= from + step * (i + 1) as f32;
+    }
+}
+
+/// De-interleaved mid and side scratch, reused between calls.
+pub struct MidSideBuffers {
+    mid: Vec<f32>,
+    side: Vec<f32>,
+}
+
+impl MidSideBuffers {
+    pub fn new() -> Self {
+        Self { mid: Vec::new(), side: Vec::new() }
+    }
+
+    /// Encode interleaved L/R stereo to M/S, hand `f` the de-interleaved
+    /// mid and side to process in-place, then re-interleave and decode
+    /// back to L/R. Panics if the length is odd.
+    pub fn process(&mut self, interleaved: &mut [f32], f: impl FnOnce(&mut [f32], &mut [f32])) {
+        assert_eq!(interleaved.len() % 2, 0, "odd interleaved stereo length");
+        lr_to_ms(interleaved);
+        self.mid.clear();
+        self.side.clear();
+        for pair in interleaved.chunks_exact(2) {
+            self.mid.push(pair[0]);
+            self.side.push(pair[1]);
+        }
+        f(&mut self.mid, &mut self.side);
+        for (pair, (&mid, &side)) in interleaved.chunks_exact_mut(2).zip(self.mid.iter().zip(&self.side)) {
+            pair[0] = mid;
+            pair[1] = side;
+        }
+        ms_to_lr(interleaved);
+    }
+}
+
+impl Default for MidSideBuffers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct MidSideProcessor {
+    mid: SystemAudioProcessor,
+    side: SystemAudioProcessor,
+    buffers: MidSideBuffers,
+    /// Mid level gain at the end of the last block, ramped from on the side
+    linked_gain: f32,
+}
+
+impl MidSideProcessor {
+    /// Default chain on the mid; the side is compressed at 2:1 instead of
+    /// 4:1 with its normalizer and gate off, and follows the mid's level
+    /// gain instead.
+    pub fn new() -> Self {
+        let mut side = SystemAudioProcessor::new();
+        side.compressor_params().set_ratio(SIDE_COMPRESSION_RATIO);
+        side.set_normalizer_enabled(false);
+        side.set_gate_enabled(false);
+        Self::with_processors(SystemAudioProcessor::new(), side)
+    }
+
+    /// Pair of separately configured processors for mid and side. The side
+    /// is still scaled by the mid's level gain (`level_gain`) after its own
+    /// chain, so its normalizer and gate should normally be off.
+    pub fn with_processors(mid: SystemAudioProcessor, side: SystemAudioProcessor) -> Self {
+        Self { mid, side, buffers: MidSideBuffers::new(), linked_gain: 1.0 }
+    }
+
+    pub fn mid_mut(&mut self) -> &mut SystemAudioProcessor {
+        &mut self.mid
+    }
+
+    pub fn side_mut(&mut self) -> &mut SystemAudioProcessor {
+        &mut self.side
+    }
+
+    /// Process interleaved L/R stereo in-place: encode to M/S, run each
+    /// channel through its processor, scale the side by the mid's level
+    /// gain, decode back to L/R. Panics if the length is odd.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let (mid, side, linked_gain) = (&mut self.mid, &mut self.side, &mut self.linked_gain);
+        self.buffers.process(samples, |mid_buf, side_buf| {
+            mid.process(mid_buf);
+            side.process(side_buf);
+            let gain = mid.level_gain();
+            apply_gain_ramp(side_buf, *linked_gain, gain);
+            *linked_gain = gain;
+        });
+    }
+
+    /// Reset both channels' state, keeping settings.
+    pub fn reset(&mut self) {
+        self.mid.reset();
+        self.side.reset();
+        self.linked_gain = 1.0;
+    }
+}
+
+impl Default for MidSideProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::rms;
+    use crate::test_signals::{make_sine, make_white_noise, tone_level};
+
+    fn interleave(left: &[f32], right: &[f32]) -> Vec<f32> {
+        left.iter().zip(right).flat_map(|(&l, &r)| [l, r]).collect()
+    }
+
+    #[test]
+    fn test_ms_round_trip() {
+        let original = interleave(&make_white_noise(0.9, 5, 4800), &make_white_noise(0.9, 6, 4800));
+        let mut samples = original.clone();
+        lr_to_ms(&mut samples);
+        assert_ne!(samples, original);
+        ms_to_lr(&mut samples);
+        for (out, orig) in samples.iter().zip(&original) {
+            assert!((out - orig).abs() < 1e-6, "Round trip error: {} vs {}", out, orig);
+        }
+
+        // Identical channels are all mid, no side
+        let mut mono = interleave(&[0.5; 4], &[0.5; 4]);
+        lr_to_ms(&mut mono);
+        assert_eq!(mono, [0.5, 0.0, 0.5, 0.0, 0.5, 0.0, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn test_side_compressed_less_than_mid() {
+        // Equally loud mid (440 Hz) and side (1 kHz) tones
+        let mid = make_sine(440.0, 0.45, 48000.0, 48000);
+        let side = make_sine(1000.0, 0.45, 48000.0, 48000);
+        let mut samples = interleave(&mid, &side);
+        ms_to_lr(&mut samples);
+
+        let mut proc = MidSideProcessor::new();
+        proc.mid_mut().set_normalizer_enabled(false);
+        proc.mid_mut().set_gate_enabled(false);
+        for block in samples.chunks_mut(960) {
+            proc.process(block);
+        }
+
+        lr_to_ms(&mut samples);
+        let (out_mid, out_side): (Vec<f32>, Vec<f32>) = samples.chunks_exact(2).map(|p| (p[0], p[1])).unzip();
+        let reduction_db = |out: &[f32], input: &[f32], freq: f32| {
+            -20.0 * (tone_level(&out[24000..], freq, 48000.0) / tone_level(&input[24000..], freq, 48000.0)).log10()
+        };
+        let mid_reduction = reduction_db(&out_mid, &mid, 440.0);
+        let side_reduction = reduction_db(&out_side, &side, 1000.0);
+        assert!(mid_reduction > side_reduction + 2.0,
+            "Mid should be compressed harder: mid {:.1} dB, side {:.1} dB", mid_reduction, side_reduction);
+        assert!(side_reduction > 0.5, "Side should still be compressed: {:.1} dB", side_reduction);
+    }
+
+    fn split(samples: &[f32]) -> (Vec<f32>, Vec<f32>) {
+        let mut ms = samples.to_vec();
+        lr_to_ms(&mut ms);
+        ms.chunks_exact(2).map(|p| (p[0], p[1])).unzip()
+    }
+
+    #[test]
+    fn test_default_side_follows_mid_level() {
+        // -23 dBFS mid over a -43 dBFS side: the normalizer lifts the mid,
+        // and the side must move with it rather than be leveled on its own
+        let mid = make_sine(440.0, 0.1, 48000.0, 96000);
+        let side = make_sine(1000.0, 0.01, 48000.0, 96000);
+        let mut samples = interleave(&mid, &side);
+        ms_to_lr(&mut samples);
+
+        let mut proc = MidSideProcessor::new();
+        for block in samples.chunks_mut(480) {
+            proc.process(block);
+        }
+
+        let (out_mid, out_side) = split(&samples[96000..]);
+        let mid_level = tone_level(&out_mid, 440.0, 48000.0);
+        let side_level = tone_level(&out_side, 1000.0, 48000.0);
+        assert!(mid_level > 0.1, "Mid should be normalized up: {:.3}", mid_level);
+        let width_db = 20.0 * (side_level / mid_level).log10();
+        assert!((width_db + 20.0).abs() < 2.0, "Side should stay 20 dB under the mid: {:.1} dB", width_db);
+    }
+
+    #[test]
+    fn test_default_side_closes_with_mid_gate() {
+        // Mid tone for 1s then silence, steady side noise throughout
+        let mut mid = make_sine(440.0, 0.1, 48000.0, 48000);
+        mid.resize(96000, 0.0);
+        let side = make_white_noise(0.01, 7, 96000);
+        let mut samples = interleave(&mid, &side);
+        ms_to_lr(&mut samples);
+
+        let mut proc = MidSideProcessor::new();
+        for block in samples.chunks_mut(480) {
+            proc.process(block);
+        }
+
+        let (_, out_side) = split(&samples);
+        let open = rms(&out_side[24000..48000]) / rms(&side[24000..48000]);
+        let closed = rms(&out_side[72000..]) / rms(&side[72000..]);
+        assert!(!proc.mid_mut().gate().is_open(), "Mid gate should have closed");
+        assert!(closed < open * 0.5,
+            "Side should close with the mid gate: open gain {:.3}, closed gain {:.3}", open, closed);
+    }
+}
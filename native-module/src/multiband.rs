@@ -0,0 +1,254 @@
+// Multiband speech compressor
+//
+// A single-band compressor lets loud low-frequency energy (plosives, hum,
+// male fundamentals) pull the gain down for the whole spectrum, dulling the
+// consonants the recognizer relies on. Splitting into bands lets each one
+// be compressed on its own level.
+//
+// Bands are split with 4th-order Linkwitz-Riley crossovers (two cascaded
+// Butterworth biquads), which sum back flat in magnitude. For three bands
+// the low band is passed through an LR4 all-pass at the upper crossover so
+// all bands stay phase-aligned before summing.
+//
+// Each band runs an independent `SpeechCompressor`, configurable through
+// `band_mut`.
+
+use crate::biquad::{Biquad, BUTTERWORTH_Q};
+use crate::compressor::SpeechCompressor;
+use crate::stage::AudioStage;
+
+/// Capture rate the crossovers are designed for
+const SAMPLE_RATE: f32 = 48000.0;
+/// Default speech split: rumble/plosives | formants | sibilance/consonants
+const DEFAULT_LOW_CROSSOVER_HZ: f32 = 300.0;
+const DEFAULT_HIGH_CROSSOVER_HZ: f32 = 3000.0;
+
+/// 4th-order Linkwitz-Riley section (two identical Butterworth biquads)
+struct LinkwitzRiley {
+    first: Biquad,
+    second: Biquad,
+}
+
+impl LinkwitzRiley {
+    fn lowpass(cutoff_hz: f32) -> Self {
+        Self {
+            first: Biquad::lowpass(cutoff_hz, BUTTERWORTH_Q, SAMPLE_RATE),
+            second: Biquad::lowpass(cutoff_hz, BUTTERWORTH_Q, SAMPLE_RATE),
+        }
+    }
+
+    fn highpass(cutoff_hz: f32) -> Self {
+        Self {
+            first: Biquad::highpass(cutoff_hz, BUTTERWORTH_Q, SAMPLE_RATE),
+            second: Biquad::highpass(cutoff_hz, BUTTERWORTH_Q, SAMPLE_RATE),
+        }
+    }
+
+    #[inline]
+    fn process_sample(&mut self, x: f32) -> f32 {
+        self.second.process_sample(self.first.process_sample(x))
+    }
+
+    fn reset(&mut self) {
+        self.first.reset();
+        self.second.reset();
+    }
+}
+
+/// Complementary LR4 low/high split at one frequency
+struct Crossover {
+    lowpass: LinkwitzRiley,
+    highpass: LinkwitzRiley,
+}
+
+impl Crossover {
+    fn new(cutoff_hz: f32) -> Self {
+        Self {
+            lowpass: LinkwitzRiley::lowpass(cutoff_hz),
+            highpass: LinkwitzRiley::highpass(cutoff_hz),
+        }
+    }
+
+    #[inline]
+    fn split(&mut self, x: f32) -> (f32, f32) {
+        (self.lowpass.process_sample(x), self.highpass.process_sample(x))
+    }
+
+    /// LR4 low + high sums to an all-pass with the crossover's phase
+    #[inline]
+    fn allpass(&mut self, x: f32) -> f32 {
+        let (low, high) = self.split(x);
+        low + high
+    }
+
+    fn reset(&mut self) {
+        self.lowpass.reset();
+        self.highpass.reset();
+    }
+}
+
+pub struct MultibandCompressor {
+    /// Crossovers in ascending frequency order (bands - 1 of them)
+    crossovers: Vec<Crossover>,
+    /// Phase compensation for the low band in three-band mode
+    low_band_allpass: Option<Crossover>,
+    bands: Vec<SpeechCompressor>,
+    /// Per-band scratch buffers, reused across calls
+    band_buffers: Vec<Vec<f32>>,
+}
+
+impl MultibandCompressor {
+    /// Three-band compressor with speech-oriented crossovers (300 Hz, 3 kHz).
+    pub fn new() -> Self {
+        Self::three_band(DEFAULT_LOW_CROSSOVER_HZ, DEFAULT_HIGH_CROSSOVER_HZ)
+    }
+
+    pub fn two_band(crossover_hz: f32) -> Self {
+        Self::with_crossovers(vec![Crossover::new(crossover_hz)], None)
+    }
+
+    /// Crossover frequencies are sorted, so argument order doesn't matter.
+    pub fn three_band(low_crossover_hz: f32, high_crossover_hz: f32) -> Self {
+        let low = low_crossover_hz.min(high_crossover_hz);
+        let high = low_crossover_hz.max(high_crossover_hz);
+        Self::with_crossovers(
+            vec![Crossover::new(low), Crossover::new(high)],
+            Some(Crossover::new(high)),
+        )
+    }
+
+    fn with_crossovers(crossovers: Vec<Crossover>, low_band_allpass: Option<Crossover>) -> Self {
+        let num_bands = crossovers.len() + 1;
+        Self {
+            crossovers,
+            low_band_allpass,
+            bands: (0..num_bands).map(|_| SpeechCompressor::new()).collect(),
+            band_buffers: vec![Vec::new(); num_bands],
+        }
+    }
+
+    pub fn num_bands(&self) -> usize {
+        self.bands.len()
+    }
+
+    /// Per-band compressor, lowest band first, for individual configuration.
+    pub fn band_mut(&mut self, index: usize) -> Option<&mut SpeechCompressor> {
+        self.bands.get_mut(index)
+    }
+
+    /// Split → compress each band → recombine, in-place.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let n = samples.len();
+        for buffer in self.band_buffers.iter_mut() {
+            buffer.clear();
+            buffer.resize(n, 0.0);
+        }
+
+        // 1. Split
+        let last = self.bands.len() - 1;
+        for (i, &x) in samples.iter().enumerate() {
+            let mut rest = x;
+            for (band, crossover) in self.crossovers.iter_mut().enumerate() {
+                let (low, high) = crossover.split(rest);
+                let low = match (&mut self.low_band_allpass, band) {
+                    (Some(allpass), 0) => allpass.allpass(low),
+                    _ => low,
+                };
+                self.band_buffers[band][i] = low;
+                rest = high;
+            }
+            self.band_buffers[last][i] = rest;
+        }
+
+        // 2. Compress each band independently
+        for (compressor, buffer) in self.bands.iter_mut().zip(self.band_buffers.iter_mut()) {
+            compressor.process(buffer);
+        }
+
+        // 3. Recombine
+        for (i, sample) in samples.iter_mut().enumerate() {
+            *sample = self.band_buffers.iter().map(|b| b[i]).sum();
+        }
+    }
+
+    /// Clear filter and per-band compressor state, keeping settings.
+    pub fn reset(&mut self) {
+        for crossover in self.crossovers.iter_mut().chain(self.low_band_allpass.as_mut()) {
+            crossover.reset();
+        }
+        for band in self.bands.iter_mut() {
+            band.reset();
+        }
+    }
+}
+
+impl Default for MultibandCompressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioStage for MultibandCompressor {
+    fn process(&mut self, samples: &mut [f32]) {
+        MultibandCompressor::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        MultibandCompressor::reset(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{make_sine, rms, tone_level};
+
+    fn two_tone() -> Vec<f32> {
+        // Loud 150 Hz (above threshold) + quiet 4 kHz (well below)
+        make_sine(150.0, 0.5, SAMPLE_RATE, 48000)
+            .iter()
+            .zip(make_sine(4000.0, 0.02, SAMPLE_RATE, 48000))
+            .map(|(a, b)| a + b)
+            .collect()
+    }
+
+    #[test]
+    fn test_high_tone_preserved_vs_single_band() {
+        let input = two_tone();
+        let high_in = tone_level(&input[24000..], 4000.0, SAMPLE_RATE);
+
+        let mut single = input.clone();
+        SpeechCompressor::new().process(&mut single);
+        let single_ratio = tone_level(&single[24000..], 4000.0, SAMPLE_RATE) / high_in;
+
+        let mut multi = input.clone();
+        MultibandCompressor::new().process(&mut multi);
+        let multi_ratio = tone_level(&multi[24000..], 4000.0, SAMPLE_RATE) / high_in;
+
+        assert!(single_ratio < 0.7, "Single-band should duck the quiet tone: {:.3}", single_ratio);
+        assert!(multi_ratio > 0.9, "Multiband should leave the quiet tone alone: {:.3}", multi_ratio);
+    }
+
+    #[test]
+    fn test_crossovers_sum_flat() {
+        // Below threshold every band has unity gain → output ≈ input level
+        for bands in [MultibandCompressor::two_band(1000.0), MultibandCompressor::new()] {
+            let mut comp = bands;
+            for freq in [100.0, 300.0, 1000.0, 3000.0, 8000.0] {
+                let mut signal = make_sine(freq, 0.02, SAMPLE_RATE, 9600);
+                comp.process(&mut signal);
+                let level = rms(&signal[4800..]) / (0.02 / 2.0f32.sqrt());
+                assert!((level - 1.0).abs() < 0.05, "{}Hz should sum flat, got {:.3}", freq, level);
+            }
+        }
+    }
+
+    #[test]
+    fn test_band_config() {
+        let mut comp = MultibandCompressor::new();
+        assert_eq!(comp.num_bands(), 3);
+        assert!(comp.band_mut(2).is_some());
+        assert!(comp.band_mut(3).is_none());
+        assert_eq!(MultibandCompressor::two_band(500.0).num_bands(), 2);
+    }
+}
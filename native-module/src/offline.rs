@@ -0,0 +1,138 @@
+// Whole-file two-pass normalization for recorded calls
+//
+// The realtime `RmsNormalizer` only ever sees the last few ms, so its gain
+// has to move with the material: a quiet opening gets lifted, the loud
+// part that follows gets pulled back, and the transitions pump. A
+// recording on disk can be looked at in full first:
+//
+//   pass 1: integrated RMS over 10ms blocks, skipping blocks at or below
+//           the noise floor
+//   pass 2: one gain (target / integrated), then a look-ahead peak limiter
+//
+// The single gain keeps the file's own dynamics intact; the limiter only
+// touches the few peaks the gain pushes past the ceiling. Not for the
+// capture path: it needs the whole file up front.
+
+use anyhow::{ensure, Result};
+
+use crate::levels::{rms, NoiseFloor};
+
+/// Level measurement block: 10ms at 48kHz
+const MEASURE_BLOCK: usize = 480;
+/// Maximum gain (+32 dB), so a near-silent file isn't blown up into noise
+const MAX_GAIN: f32 = 40.0;
+/// Limiter ceiling: -1 dBFS
+const LIMITER_CEILING: f32 = 0.891;
+/// Limiter gain ramp ahead of a peak: ~2ms at 48kHz
+/// alpha = exp(-1 / (48000 * 0.002)) ≈ 0.9896
+const LIMITER_ATTACK: f32 = 0.9896;
+/// Limiter recovery after a peak: ~50ms at 48kHz
+/// alpha = exp(-1 / (48000 * 0.05)) ≈ 0.99958
+const LIMITER_RELEASE: f32 = 0.99958;
+
+/// Integrated RMS of `samples` over its 10ms blocks above `floor` (`None`
+/// if every block is at or below it).
+pub fn integrated_rms(samples: &[f32], floor: NoiseFloor) -> Option<f32> {
+    let floor = floor.rms();
+    let (power, blocks) = samples
+        .chunks(MEASURE_BLOCK)
+        .map(rms)
+        .filter(|&level| level > floor)
+        .fold((0.0f64, 0usize), |(power, blocks), level| (power + (level * level) as f64, blocks + 1));
+    (blocks > 0).then(|| (power / blocks as f64).sqrt() as f32)
+}
+
+/// Normalize a whole recording (48kHz mono) to `target_rms` (linear, e.g.
+/// 0.15 for -16 dBFS) with one gain, then limit peaks to -1 dBFS. Blocks
+/// at or below `floor` (e.g. from a `LevelHistogram`) don't count towards
+/// the level, and files with nothing above it are returned unchanged.
+/// The target must be in (0, 1]. Allocates the output; offline use only.
+pub fn normalize_file(samples: &[f32], target_rms: f32, floor: NoiseFloor) -> Result<Vec<f32>> {
+    ensure!(target_rms > 0.0 && target_rms <= 1.0, "target RMS must be in (0, 1], got {}", target_rms);
+    let Some(level) = integrated_rms(samples, floor) else {
+        return Ok(samples.to_vec());
+    };
+    let gain = (target_rms / level).min(MAX_GAIN);
+    let mut output: Vec<f32> = samples.iter().map(|s| s * gain).collect();
+    limit(&mut output);
+    Ok(output)
+}
+
+/// Look-ahead peak limiter: each sample's gain is at most what keeps it
+/// under the ceiling, smoothed backwards (so the gain is already down
+/// when the peak arrives) and forwards (release). Smoothing only ever
+/// lowers the gain, so no sample overshoots.
+fn limit(samples: &mut [f32]) {
+    let mut gains: Vec<f32> = samples.iter().map(|s| (LIMITER_CEILING / s.abs()).min(1.0)).collect();
+    let mut reduction = 0.0f32;
+    for gain in gains.iter_mut().rev() {
+        reduction = (1.0 - *gain).max(reduction * LIMITER_ATTACK);
+        *gain = 1.0 - reduction;
+    }
+    reduction = 0.0;
+    for gain in gains.iter_mut() {
+        reduction = (1.0 - *gain).max(reduction * LIMITER_RELEASE);
+        *gain = 1.0 - reduction;
+    }
+    // The clamp only catches the last bit of rounding in 1 - (1 - gain)
+    for (sample, gain) in samples.iter_mut().zip(gains) {
+        *sample = (*sample * gain).clamp(-LIMITER_CEILING, LIMITER_CEILING);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressor::RmsNormalizer;
+    use crate::test_signals::{make_sine, peak, tone_level};
+
+    fn floor() -> NoiseFloor {
+        NoiseFloor::from_db(-70.0).unwrap()
+    }
+
+    #[test]
+    fn test_two_pass_normalizes_without_pumping() {
+        // 2s quiet then 2s loud
+        let mut file = make_sine(440.0, 0.02, 48000.0, 96000);
+        file.extend(make_sine(440.0, 0.1, 48000.0, 96000));
+        let (quiet, loud) = (48000..96000, 144000..192000);
+        let half_gain = |output: &[f32], range: std::ops::Range<usize>| {
+            20.0 * (tone_level(&output[range.clone()], 440.0, 48000.0)
+                / tone_level(&file[range], 440.0, 48000.0)).log10()
+        };
+
+        let output = normalize_file(&file, 0.15, floor()).unwrap();
+        let level_db = 20.0 * (integrated_rms(&output, floor()).unwrap() / 0.15).log10();
+        assert!(level_db.abs() < 0.5, "Output should sit at the target: {:.2} dB off", level_db);
+        let drift = half_gain(&output, quiet.clone()) - half_gain(&output, loud.clone());
+        assert!(drift.abs() < 0.01, "One gain for the whole file: halves differ by {:.2} dB", drift);
+
+        // The streaming normalizer re-levels each half instead
+        let mut streamed = file.clone();
+        let mut normalizer = RmsNormalizer::new();
+        for block in streamed.chunks_mut(480) {
+            normalizer.process(block);
+        }
+        let streamed_drift = half_gain(&streamed, quiet) - half_gain(&streamed, loud);
+        assert!(streamed_drift > 6.0, "Streaming gain should move: {:.2} dB", streamed_drift);
+    }
+
+    #[test]
+    fn test_limiter_catches_boosted_peaks() {
+        let mut file = make_sine(440.0, 0.05, 48000.0, 48000);
+        file[24000] = 0.9;
+        let output = normalize_file(&file, 0.15, floor()).unwrap();
+        assert!(peak(&output) <= LIMITER_CEILING, "Peak {:.4} over the ceiling", peak(&output));
+
+        // Silence passes through untouched
+        assert_eq!(normalize_file(&[0.0; 4800], 0.15, floor()).unwrap(), vec![0.0; 4800]);
+
+        // Audio under a raised floor counts as silence too
+        let quiet_floor = NoiseFloor::from_db(-20.0).unwrap();
+        assert_eq!(normalize_file(&file[..4800], 0.15, quiet_floor).unwrap(), file[..4800].to_vec());
+
+        for target in [0.0, -0.15, 1.5, f32::NAN] {
+            assert!(normalize_file(&file, target, floor()).is_err(), "target {}", target);
+        }
+    }
+}
@@ -11,8 +11,24 @@
 // over-boosting codec artifacts near the 3.2 kHz bandwidth edge.
 //
 // Zero latency, negligible CPU: 1 multiply + 1 subtract per sample.
+//
+// `TiltFilter` is a high-shelf alternative for the same position: the FIR
+// keeps rising all the way to Nyquist, which on some codecs over-boosts
+// the noise near the band edge; the shelf boosts above its corner and then
+// flattens out.
+
+use anyhow::{ensure, Result};
+
+use crate::biquad::Biquad;
+use crate::stage::AudioStage;
 
 const PRE_EMPHASIS_COEFF: f32 = 0.65;
+/// Pipeline sample rate the tilt shelf is designed at
+const TILT_SAMPLE_RATE: f32 = 48_000.0;
+/// Default tilt corner: boost lands on the F2/F3 formant region
+const TILT_CORNER_HZ: f32 = 1000.0;
+/// Default tilt shelf gain, about what the FIR gives at 3 kHz
+const TILT_GAIN_DB: f32 = 6.0;
 
 pub struct PreEmphasis {
     prev_sample: f32,
@@ -26,16 +42,107 @@ impl PreEmphasis {
     /// Apply pre-emphasis filter in-place.
     pub fn process(&mut self, samples: &mut [f32]) {
         for sample in samples.iter_mut() {
-            let input = *sample;
-            *sample = input - PRE_EMPHASIS_COEFF * self.prev_sample;
-            self.prev_sample = input;
+            *sample = self.process_sample(*sample);
         }
     }
+
+    /// Filter one sample, for fusing into another stage's loop.
+    #[inline]
+    pub fn process_sample(&mut self, input: f32) -> f32 {
+        let output = input - PRE_EMPHASIS_COEFF * self.prev_sample;
+        self.prev_sample = input;
+        output
+    }
+
+    /// Filter one self-contained clip: the state is reset first, so the
+    /// result doesn't depend on what was processed before (e.g. a batch of
+    /// unrelated recordings). Use `process` for a continuous stream.
+    pub fn process_independent(&mut self, samples: &mut [f32]) {
+        self.reset();
+        self.process(samples);
+    }
+
+    pub fn reset(&mut self) {
+        self.prev_sample = 0.0;
+    }
+}
+
+impl AudioStage for PreEmphasis {
+    fn process(&mut self, samples: &mut [f32]) {
+        PreEmphasis::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        PreEmphasis::reset(self);
+    }
+}
+
+/// High-shelf spectral tilt: flat below the corner, a fixed boost (or cut)
+/// above it. Drop-in alternative to `PreEmphasis` ahead of the compressor.
+pub struct TiltFilter {
+    shelf: Biquad,
+}
+
+impl TiltFilter {
+    /// +6 dB above 1 kHz at 48kHz.
+    pub fn new() -> Self {
+        Self { shelf: Biquad::high_shelf(TILT_CORNER_HZ, TILT_GAIN_DB, TILT_SAMPLE_RATE) }
+    }
+
+    /// Shelf with `gain_db` above `corner_hz` (half of it at the corner).
+    /// The corner must be in (0, 24000) Hz and the gain finite.
+    pub fn with_shelf(corner_hz: f32, gain_db: f32) -> Result<Self> {
+        ensure!(corner_hz > 0.0 && corner_hz < TILT_SAMPLE_RATE / 2.0,
+            "tilt corner must be in (0, {}) Hz, got {}", TILT_SAMPLE_RATE / 2.0, corner_hz);
+        ensure!(gain_db.is_finite(), "tilt gain must be finite, got {}", gain_db);
+        Ok(Self { shelf: Biquad::high_shelf(corner_hz, gain_db, TILT_SAMPLE_RATE) })
+    }
+
+    /// Apply the tilt in-place.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        self.shelf.process(samples);
+    }
+
+    /// Filter one sample, for fusing into another stage's loop.
+    #[inline]
+    pub fn process_sample(&mut self, input: f32) -> f32 {
+        self.shelf.process_sample(input)
+    }
+
+    pub fn reset(&mut self) {
+        self.shelf.reset();
+    }
+}
+
+impl Default for TiltFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioStage for TiltFilter {
+    fn process(&mut self, samples: &mut [f32]) {
+        TiltFilter::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        TiltFilter::reset(self);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_signals::{make_sine, tone_level};
+
+    /// Steady-state gain in dB of `stage` for a sine at `freq`.
+    fn response_db(stage: &mut dyn AudioStage, freq: f32) -> f32 {
+        stage.reset();
+        let input = make_sine(freq, 0.25, 48000.0, 9600);
+        let mut output = input.clone();
+        stage.process(&mut output);
+        20.0 * (tone_level(&output[4800..], freq, 48000.0) / tone_level(&input[4800..], freq, 48000.0)).log10()
+    }
 
     #[test]
     fn test_dc_is_attenuated() {
@@ -75,6 +182,38 @@ mod tests {
         assert!((b[0] - 0.175).abs() < 1e-6, "State should carry across calls: got {}", b[0]);
     }
 
+    #[test]
+    fn test_process_independent_ignores_prior_calls() {
+        let mut filter = PreEmphasis::new();
+        let mut fresh = vec![0.5; 10];
+        filter.process_independent(&mut fresh);
+
+        filter.process(&mut [-0.8; 10]);
+        let mut after = vec![0.5; 10];
+        filter.process_independent(&mut after);
+        assert_eq!(after, fresh);
+        assert_eq!(after[0], 0.5);
+    }
+
+    #[test]
+    fn test_tilt_shelf_flattens_above_corner() {
+        let (mut fir, mut tilt) = (PreEmphasis::new(), TiltFilter::new());
+        // The FIR keeps rising towards Nyquist...
+        let fir_rise = response_db(&mut fir, 16000.0) - response_db(&mut fir, 8000.0);
+        assert!(fir_rise > 3.0, "FIR should keep rising: {:.2} dB", fir_rise);
+        // ...the shelf levels off at its gain above the corner
+        let tilt_rise = response_db(&mut tilt, 16000.0) - response_db(&mut tilt, 8000.0);
+        assert!(tilt_rise.abs() < 0.3, "Shelf should be flat: {:.2} dB", tilt_rise);
+        assert!((response_db(&mut tilt, 8000.0) - TILT_GAIN_DB).abs() < 0.3);
+        assert!(response_db(&mut tilt, 100.0).abs() < 0.3, "Shelf should leave the lows alone");
+
+        let mut gentle = TiltFilter::with_shelf(2000.0, 3.0).unwrap();
+        assert!((response_db(&mut gentle, 12000.0) - 3.0).abs() < 0.3);
+        assert!(TiltFilter::with_shelf(0.0, 6.0).is_err());
+        assert!(TiltFilter::with_shelf(30000.0, 6.0).is_err());
+        assert!(TiltFilter::with_shelf(1000.0, f32::NAN).is_err());
+    }
+
     #[test]
     fn test_empty_input() {
         let mut filter = PreEmphasis::new();
@@ -1,77 +1,294 @@
-use anyhow::Result;
-use rubato::{FftFixedIn, Resampler as RubatoResampler};
+// Streaming f32 → f32 resampler
+//
+// Lets the DSP pipeline convert CoreAudio's native rate (usually 48 kHz)
+// to the 16 kHz the AEC and STT expect while staying in f32, so the
+// conversion to i16 happens once at the very end.
+//
+// Two quality modes:
+//   - Linear:    2-tap interpolation, 1 sample lookahead, cheapest.
+//   - Polyphase: windowed-sinc FIR bank (Blackman window). The cutoff tracks
+//                the lower of the two Nyquist rates, so downsampling is
+//                properly anti-aliased. Latency is the filter half-length
+//                (~1ms at 48 kHz → 16 kHz).
+//
+// Fractional buffer boundaries are handled by keeping the input tail that
+// the next output sample still needs, plus the fractional read position.
+
+use anyhow::{ensure, Result};
+
+/// Number of fractional phases in the polyphase filter bank.
+/// Positions are rounded to the nearest phase; 128 keeps the phase error
+/// well below the 16-bit noise floor.
+const POLYPHASE_PHASES: usize = 128;
+
+/// Zero crossings of the sinc kernel on each side of centre.
+/// Higher = steeper anti-alias rolloff, more CPU and latency.
+const SINC_ZERO_CROSSINGS: usize = 8;
+
+/// Cutoff as a fraction of the target Nyquist — leaves a transition band
+/// so the stopband starts at Nyquist rather than ending there.
+const SINC_ROLLOFF: f64 = 0.92;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResamplerQuality {
+    /// Linear interpolation — zero added latency, no anti-alias filter
+    Linear,
+    /// Windowed-sinc polyphase filter — anti-aliased, small fixed latency
+    Polyphase,
+}
 
-/// High-quality resampler using rubato (polyphase FIR with sinc interpolation)
-/// Converts f32 audio from input sample rate to 16kHz i16 output
 pub struct Resampler {
-    resampler: FftFixedIn<f32>,
-    input_buffer: Vec<Vec<f32>>,
-    output_buffer: Vec<Vec<f32>>,
+    /// Input samples consumed per output sample (from_rate / to_rate)
+    ratio: f64,
+    quality: ResamplerQuality,
+    /// Taps on each side of the interpolation point (1 for linear)
+    half_taps: usize,
+    /// Filter bank: `POLYPHASE_PHASES + 1` rows of `2 * half_taps` taps.
+    /// Empty in linear mode.
+    bank: Vec<f32>,
+    /// Pending input: retained tail from previous calls + new input
+    buffer: Vec<f32>,
+    /// Read position of the next output sample within `buffer`
+    position: f64,
 }
 
 impl Resampler {
-    pub fn new(input_sample_rate: f64) -> Result<Self> {
-        let output_sample_rate = 16000.0;
-        
-        println!("[Resampler] Created: {}Hz -> {}Hz (high-quality rubato)", 
-                 input_sample_rate, output_sample_rate);
-        
-        // FftFixedIn: Fixed input chunk size, variable output size
-        // This is ideal for streaming from a microphone tap that delivers fixed-size buffers
-        let resampler = FftFixedIn::<f32>::new(
-            input_sample_rate as usize,
-            output_sample_rate as usize,
-            1024,  // chunk size (internal buffer)
-            2,     // sub-chunks for better quality
-            1,     // mono
-        ).map_err(|e| anyhow::anyhow!("Failed to create resampler: {}", e))?;
-        
-        Ok(Self {
-            resampler,
-            input_buffer: vec![Vec::new()],
-            output_buffer: vec![Vec::new()],
-        })
+    /// Create a polyphase resampler from `from_rate` to `to_rate` (Hz).
+    pub fn new(from_rate: f64, to_rate: f64) -> Result<Self> {
+        Self::with_quality(from_rate, to_rate, ResamplerQuality::Polyphase)
+    }
+
+    /// Create a resampler with an explicit quality mode. Both rates must be
+    /// finite and > 0.
+    pub fn with_quality(from_rate: f64, to_rate: f64, quality: ResamplerQuality) -> Result<Self> {
+        ensure!(from_rate.is_finite() && from_rate > 0.0 && to_rate.is_finite() && to_rate > 0.0,
+            "resampler rates must be finite and > 0 Hz, got {} -> {}", from_rate, to_rate);
+        let ratio = from_rate / to_rate;
+
+        let (half_taps, bank) = match quality {
+            ResamplerQuality::Linear => (1, Vec::new()),
+            ResamplerQuality::Polyphase => {
+                // Cutoff relative to the input rate (1.0 = input Nyquist)
+                let cutoff = (1.0 / ratio).min(1.0) * SINC_ROLLOFF;
+                let half_taps = (SINC_ZERO_CROSSINGS as f64 / cutoff).ceil() as usize;
+                (half_taps, Self::build_bank(half_taps, cutoff))
+            }
+        };
+
+        println!(
+            "[Resampler] Created: {}Hz -> {}Hz (ratio: {:.4}, {:?}, {} taps)",
+            from_rate, to_rate, ratio, quality, half_taps * 2
+        );
+
+        let mut resampler = Self {
+            ratio,
+            quality,
+            half_taps,
+            bank,
+            buffer: Vec::with_capacity(4096),
+            position: 0.0,
+        };
+        resampler.reset();
+        Ok(resampler)
+    }
+
+    /// Precompute the windowed-sinc kernel for each fractional phase.
+    /// Row `p` holds taps for offsets `-(half_taps-1) ..= half_taps`
+    /// evaluated at fractional position `p / POLYPHASE_PHASES`.
+    fn build_bank(half_taps: usize, cutoff: f64) -> Vec<f32> {
+        let taps = 2 * half_taps;
+        let mut bank = Vec::with_capacity((POLYPHASE_PHASES + 1) * taps);
+
+        for phase in 0..=POLYPHASE_PHASES {
+            let frac = phase as f64 / POLYPHASE_PHASES as f64;
+            let row_start = bank.len();
+            let mut sum = 0.0f64;
+
+            for k in 0..taps {
+                // Distance from the interpolation point to this tap
+                let x = (k as f64 - (half_taps as f64 - 1.0)) - frac;
+                let sinc = if x.abs() < 1e-9 {
+                    1.0
+                } else {
+                    let arg = std::f64::consts::PI * cutoff * x;
+                    arg.sin() / arg
+                };
+                // Blackman window over [-half_taps, half_taps]
+                let t = x / half_taps as f64;
+                let window = if t.abs() >= 1.0 {
+                    0.0
+                } else {
+                    let a = std::f64::consts::PI * t;
+                    0.42 + 0.5 * a.cos() + 0.08 * (2.0 * a).cos()
+                };
+                let h = sinc * window;
+                sum += h;
+                bank.push(h as f32);
+            }
+
+            // Normalise each phase to unity DC gain
+            for tap in &mut bank[row_start..] {
+                *tap = (*tap as f64 / sum) as f32;
+            }
+        }
+
+        bank
     }
 
-    /// Resample f32 audio data to i16 at 16kHz using high-quality algorithm
-    pub fn resample(&mut self, input_data: &[f32]) -> Result<Vec<i16>> {
-        if input_data.is_empty() {
-            return Ok(Vec::new());
+    /// Resample a chunk of f32 audio. Output length varies by ±1 between
+    /// calls; state carries across calls for seamless streaming.
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        if input.is_empty() {
+            return Vec::new();
         }
 
-        // Add new input to our buffer (mono, so channel 0)
-        self.input_buffer[0].extend_from_slice(input_data);
-        
-        let mut output_samples = Vec::new();
-        
-        // Process complete chunks
-        let frames_needed = self.resampler.input_frames_next();
-        
-        while self.input_buffer[0].len() >= frames_needed {
-            // Take exactly the frames we need
-            let chunk: Vec<f32> = self.input_buffer[0].drain(0..frames_needed).collect();
-            let input_chunk = vec![chunk];
-            
-            // Resize output buffer
-            let output_frames = self.resampler.output_frames_next();
-            self.output_buffer[0].resize(output_frames, 0.0);
-            
-            // Process
-            match self.resampler.process_into_buffer(&input_chunk, &mut self.output_buffer, None) {
-                Ok((_, out_len)) => {
-                    // Convert f32 [-1.0, 1.0] to i16
-                    for i in 0..out_len {
-                        let sample = self.output_buffer[0][i];
-                        let scaled = (sample * 32767.0).clamp(-32768.0, 32767.0);
-                        output_samples.push(scaled as i16);
-                    }
+        self.buffer.extend_from_slice(input);
+
+        let estimated_output = (input.len() as f64 / self.ratio) as usize + 2;
+        let mut output = Vec::with_capacity(estimated_output);
+
+        loop {
+            let idx = self.position.floor() as usize;
+            // Need taps up to idx + half_taps to be present
+            if idx + self.half_taps >= self.buffer.len() {
+                break;
+            }
+            let frac = self.position - idx as f64;
+
+            let sample = match self.quality {
+                ResamplerQuality::Linear => {
+                    let a = self.buffer[idx];
+                    let b = self.buffer[idx + 1];
+                    a + (frac as f32) * (b - a)
                 }
-                Err(e) => {
-                    println!("[Resampler] Process error: {}", e);
+                ResamplerQuality::Polyphase => {
+                    let taps = 2 * self.half_taps;
+                    let phase = (frac * POLYPHASE_PHASES as f64).round() as usize;
+                    let row = &self.bank[phase * taps..(phase + 1) * taps];
+                    let start = idx + 1 - self.half_taps;
+                    self.buffer[start..start + taps]
+                        .iter()
+                        .zip(row)
+                        .map(|(s, h)| s * h)
+                        .sum()
                 }
-            }
+            };
+
+            output.push(sample);
+            self.position += self.ratio;
+        }
+
+        // Drop input the next output sample no longer needs
+        let consumed = (self.position.floor() as usize + 1)
+            .saturating_sub(self.half_taps)
+            .min(self.buffer.len());
+        self.buffer.drain(..consumed);
+        self.position -= consumed as f64;
+
+        output
+    }
+
+    /// Reset all streaming state (e.g. when capture restarts).
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        // Prime with silence so the first output is centred on input sample 0
+        self.buffer.resize(self.half_taps - 1, 0.0);
+        self.position = (self.half_taps - 1) as f64;
+    }
+
+    /// Algorithmic latency in input samples.
+    pub fn latency(&self) -> usize {
+        self.half_taps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::make_sine;
+
+    /// Estimate frequency from rising zero crossings
+    fn estimate_freq(samples: &[f32], sample_rate: f32) -> f32 {
+        let crossings = samples
+            .windows(2)
+            .filter(|w| w[0] < 0.0 && w[1] >= 0.0)
+            .count();
+        crossings as f32 * sample_rate / samples.len() as f32
+    }
+
+    #[test]
+    fn test_sine_48k_to_16k() {
+        let mut resampler = Resampler::new(48000.0, 16000.0).unwrap();
+        let input = make_sine(1000.0, 0.5, 48000.0, 48000);
+
+        // Odd chunk size so chunk boundaries fall on fractional positions
+        let mut output = Vec::new();
+        for chunk in input.chunks(437) {
+            output.extend(resampler.process(chunk));
+        }
+
+        // ~1 second at 16kHz, minus the filter's lookahead
+        let expected = 16000.0;
+        assert!((output.len() as f32 - expected).abs() < 32.0,
+            "Output length should be ~{}, got {}", expected, output.len());
+
+        // Skip the filter's warmup, then check frequency and level
+        let steady = &output[200..];
+        let freq = estimate_freq(steady, 16000.0);
+        assert!((freq - 1000.0).abs() < 5.0, "Output should be 1kHz, got {:.1}Hz", freq);
+
+        let peak = steady.iter().map(|s| s.abs()).fold(0.0f32, f32::max);
+        assert!((peak - 0.5).abs() < 0.02, "Passband level should be preserved, peak={:.4}", peak);
+    }
+
+    #[test]
+    fn test_fractional_ratio_streaming() {
+        // 44.1k → 16k: non-integer ratio exercises the phase bank
+        let mut resampler = Resampler::new(44100.0, 16000.0).unwrap();
+        let input = make_sine(1000.0, 0.5, 44100.0, 44100);
+
+        let mut output = Vec::new();
+        for chunk in input.chunks(480) {
+            output.extend(resampler.process(chunk));
+        }
+
+        assert!((output.len() as f32 - 16000.0).abs() < 32.0, "got {} samples", output.len());
+        let freq = estimate_freq(&output[200..], 16000.0);
+        assert!((freq - 1000.0).abs() < 5.0, "Output should be 1kHz, got {:.1}Hz", freq);
+    }
+
+    #[test]
+    fn test_anti_aliasing() {
+        // 10kHz is above the 8kHz output Nyquist and must be rejected
+        let mut resampler = Resampler::new(48000.0, 16000.0).unwrap();
+        let input = make_sine(10000.0, 0.5, 48000.0, 9600);
+        let output = resampler.process(&input);
+        let peak = output[200..].iter().map(|s| s.abs()).fold(0.0f32, f32::max);
+        assert!(peak < 0.01, "Out-of-band tone should be filtered, peak={:.4}", peak);
+    }
+
+    #[test]
+    fn test_linear_matches_length() {
+        let mut resampler = Resampler::with_quality(48000.0, 16000.0, ResamplerQuality::Linear).unwrap();
+        let mut total = 0;
+        for _ in 0..100 {
+            total += resampler.process(&[0.25; 480]).len();
+        }
+        // 48000 in → 16000 out (minus one sample of lookahead)
+        assert!((total as i32 - 16000).abs() <= 1, "got {}", total);
+    }
+
+    #[test]
+    fn test_empty_input() {
+        let mut resampler = Resampler::new(48000.0, 16000.0).unwrap();
+        assert!(resampler.process(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_rejects_invalid_rates() {
+        for (from, to) in [(48000.0, 0.0), (0.0, 16000.0), (-48000.0, 16000.0), (48000.0, f64::INFINITY), (f64::NAN, 16000.0)] {
+            assert!(Resampler::new(from, to).is_err(), "{} -> {}", from, to);
+            assert!(Resampler::with_quality(from, to, ResamplerQuality::Linear).is_err(), "{} -> {}", from, to);
         }
-        
-        Ok(output_samples)
     }
 }
@@ -0,0 +1,147 @@
+// Soft saturation ("warmth") for the monitoring feed
+//
+// Clean compressed speech can sound clinical on headphones. A gentle
+// tube-style waveshaper rounds off peaks and adds low-order harmonics:
+//
+//   y = (tanh(k·(x + bias)) − tanh(k·bias)) / k,   k = 1 + drive
+//
+// The bias makes the curve asymmetric, so even harmonics (2nd, 4th) appear
+// alongside the odd ones a symmetric tanh produces. Subtracting
+// tanh(k·bias) keeps silence at zero, and a 20 Hz high-pass removes the
+// signal-dependent DC the asymmetry leaves behind.
+//
+// The slope of tanh never exceeds 1, so |y| ≤ |x|: output peaks never
+// exceed input peaks. Small signals lose a little level
+// (≈1 − tanh²(k·bias)), which the normalizer downstream makes up. Not
+// meant for the STT path: harmonics are noise to a recognizer.
+
+use anyhow::{ensure, Result};
+
+use crate::biquad::{Biquad, BUTTERWORTH_Q};
+use crate::stage::AudioStage;
+
+/// Capture rate the DC blocker is designed for
+const SAMPLE_RATE: f32 = 48000.0;
+/// Input offset that makes the curve asymmetric (even harmonics)
+const SATURATION_BIAS: f32 = 0.1;
+/// DC blocker cutoff: below speech, above the asymmetry's slow drift
+const DC_BLOCK_HZ: f32 = 20.0;
+
+pub struct Saturator {
+    /// 0 = off (exact passthrough)
+    drive: f32,
+    dc_block: Biquad,
+}
+
+impl Saturator {
+    /// Saturator with zero drive, i.e. passthrough until `set_drive`.
+    pub fn new() -> Self {
+        Self {
+            drive: 0.0,
+            dc_block: Biquad::highpass(DC_BLOCK_HZ, BUTTERWORTH_Q, SAMPLE_RATE),
+        }
+    }
+
+    pub fn with_drive(drive: f32) -> Result<Self> {
+        let mut saturator = Self::new();
+        saturator.set_drive(drive)?;
+        Ok(saturator)
+    }
+
+    /// Saturation amount: 0 disables the stage, 1 is a subtle warmth,
+    /// 4+ audibly drives it. Must be finite and ≥ 0.
+    pub fn set_drive(&mut self, drive: f32) -> Result<()> {
+        ensure!(drive.is_finite() && drive >= 0.0, "drive must be >= 0, got {}", drive);
+        if self.drive == 0.0 && drive > 0.0 {
+            self.dc_block.reset();
+        }
+        self.drive = drive;
+        Ok(())
+    }
+
+    pub fn drive(&self) -> f32 {
+        self.drive
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.drive > 0.0
+    }
+
+    #[inline]
+    fn shape(&self, x: f32) -> f32 {
+        let k = 1.0 + self.drive;
+        ((k * (x + SATURATION_BIAS)).tanh() - (k * SATURATION_BIAS).tanh()) / k
+    }
+
+    /// Saturate in-place. No-op at zero drive.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        if !self.is_enabled() {
+            return;
+        }
+        for sample in samples.iter_mut() {
+            *sample = self.dc_block.process_sample(self.shape(*sample));
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.dc_block.reset();
+    }
+}
+
+impl Default for Saturator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioStage for Saturator {
+    fn process(&mut self, samples: &mut [f32]) {
+        Saturator::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        Saturator::reset(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{make_sine, peak, tone_level};
+
+    #[test]
+    fn test_drive_adds_harmonics_with_bounded_peak() {
+        let input = make_sine(500.0, 0.8, SAMPLE_RATE, 48000);
+        let fundamental_in = tone_level(&input[24000..], 500.0, SAMPLE_RATE);
+        // A clean sine has (numerically) no harmonics
+        assert!(tone_level(&input[24000..], 1000.0, SAMPLE_RATE) < 1e-4 * fundamental_in);
+
+        let mut output = input.clone();
+        Saturator::with_drive(2.0).unwrap().process(&mut output);
+        let settled = &output[24000..];
+
+        let fundamental = tone_level(settled, 500.0, SAMPLE_RATE);
+        let second = tone_level(settled, 1000.0, SAMPLE_RATE) / fundamental;
+        let third = tone_level(settled, 1500.0, SAMPLE_RATE) / fundamental;
+        assert!(second > 0.01, "Bias should add even harmonics: H2={:.4}", second);
+        assert!(third > 0.01, "tanh should add odd harmonics: H3={:.4}", third);
+        assert!(peak(settled) <= peak(&input), "Peak grew: {:.3} > {:.3}", peak(settled), peak(&input));
+    }
+
+    #[test]
+    fn test_zero_drive_is_passthrough() {
+        let input = make_sine(500.0, 0.8, SAMPLE_RATE, 4800);
+        let mut output = input.clone();
+        let mut saturator = Saturator::new();
+        assert!(!saturator.is_enabled());
+        saturator.process(&mut output);
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_drive_validation() {
+        assert!(Saturator::with_drive(-1.0).is_err());
+        assert!(Saturator::with_drive(f32::NAN).is_err());
+        assert_eq!(Saturator::with_drive(1.5).unwrap().drive(), 1.5);
+    }
+}
@@ -0,0 +1,213 @@
+// Speech-segment output for transcription
+//
+// Streaming every sample to the recognizer wastes bandwidth on silence.
+// This wraps a `SystemAudioProcessor` and uses its noise gate as the speech
+// detector: processed audio is accumulated while the gate is open and handed
+// to a callback as one contiguous segment once it closes.
+//
+//   gate Open   → start segment (pre-roll + block)
+//   Hold/Release → keep appending (short pauses stay inside the segment)
+//   gate Closed → emit segment, back to filling the pre-roll
+//
+// The gate only opens once its detector has seen the onset, so the first
+// few ms of a word would be lost; a rolling pre-roll of the audio just
+// before the opening is prepended to every segment. The gate runs in
+// detection-only mode (see `new`) so the pre-roll holds real audio rather
+// than gated silence.
+
+use std::collections::VecDeque;
+
+use anyhow::{ensure, Result};
+
+use crate::compressor::SystemAudioProcessor;
+use crate::stage::AudioStage;
+
+/// Gate state is sampled every 10ms at 48kHz
+const SEGMENT_BLOCK: usize = 480;
+/// Default pre-roll: 100ms at 48kHz
+const DEFAULT_PRE_ROLL: usize = 4800;
+/// Samples per millisecond at the 48kHz capture rate
+const SAMPLES_PER_MS: f32 = 48.0;
+
+/// Receives each completed segment with its start and end (exclusive)
+/// position in samples since construction or `reset()`.
+pub type SegmentCallback = Box<dyn FnMut(Vec<f32>, u64, u64) + Send>;
+
+pub struct SegmentedProcessor {
+    processor: SystemAudioProcessor,
+    on_segment: SegmentCallback,
+    /// Most recent processed audio while no segment is open
+    pre_roll: VecDeque<f32>,
+    pre_roll_len: usize,
+    /// Segment being accumulated and its start position (`None` = silence)
+    segment: Option<(Vec<f32>, u64)>,
+    /// Samples processed since construction/reset
+    position: u64,
+}
+
+impl SegmentedProcessor {
+    /// Segment `processor`'s output, calling `on_segment` as each segment
+    /// completes. The processor's gate is switched to detection only: the
+    /// segments do the gating, and the audio inside them is left ungated.
+    pub fn new<F>(mut processor: SystemAudioProcessor, on_segment: F) -> Self
+    where
+        F: FnMut(Vec<f32>, u64, u64) + Send + 'static,
+    {
+        processor.set_gate_enabled(false);
+        Self {
+            processor,
+            on_segment: Box::new(on_segment),
+            pre_roll: VecDeque::with_capacity(DEFAULT_PRE_ROLL),
+            pre_roll_len: DEFAULT_PRE_ROLL,
+            segment: None,
+            position: 0,
+        }
+    }
+
+    /// Audio kept from before the gate opens (default 100ms). Must be
+    /// finite and ≥ 0.
+    pub fn set_pre_roll_ms(&mut self, pre_roll_ms: f32) -> Result<()> {
+        ensure!(pre_roll_ms.is_finite() && pre_roll_ms >= 0.0,
+            "pre-roll must be >= 0 ms, got {}", pre_roll_ms);
+        self.pre_roll_len = (pre_roll_ms * SAMPLES_PER_MS).round() as usize;
+        while self.pre_roll.len() > self.pre_roll_len {
+            self.pre_roll.pop_front();
+        }
+        Ok(())
+    }
+
+    pub fn processor_mut(&mut self) -> &mut SystemAudioProcessor {
+        &mut self.processor
+    }
+
+    /// True while a segment is being accumulated.
+    pub fn in_segment(&self) -> bool {
+        self.segment.is_some()
+    }
+
+    /// Process in-place (the continuous output stays available to the
+    /// caller) and emit any segments that complete within `samples`.
+    /// Boundaries are resolved to 10ms blocks of the stream, however the
+    /// caller slices its frames.
+    pub fn process(&mut self, mut samples: &mut [f32]) {
+        while !samples.is_empty() {
+            let until_boundary = SEGMENT_BLOCK - (self.position % SEGMENT_BLOCK as u64) as usize;
+            let (block, rest) = samples.split_at_mut(until_boundary.min(samples.len()));
+            samples = rest;
+            self.processor.process(block);
+            self.position += block.len() as u64;
+            let gate = self.processor.gate();
+
+            match self.segment.as_mut() {
+                Some((audio, _)) => {
+                    audio.extend_from_slice(block);
+                    if gate.is_closed() {
+                        self.emit();
+                    }
+                }
+                None if gate.is_open() => {
+                    let mut audio: Vec<f32> = self.pre_roll.drain(..).collect();
+                    audio.extend_from_slice(block);
+                    let start = self.position - audio.len() as u64;
+                    self.segment = Some((audio, start));
+                }
+                None => {
+                    self.pre_roll.extend(block.iter().copied());
+                    let excess = self.pre_roll.len().saturating_sub(self.pre_roll_len);
+                    self.pre_roll.drain(..excess);
+                }
+            }
+        }
+    }
+
+    /// Emit the segment in progress, if any, e.g. when the stream ends
+    /// mid-sentence.
+    pub fn flush(&mut self) {
+        self.emit();
+    }
+
+    fn emit(&mut self) {
+        if let Some((audio, start)) = self.segment.take() {
+            let end = start + audio.len() as u64;
+            (self.on_segment)(audio, start, end);
+        }
+    }
+
+    /// Drop any segment in progress (without emitting it) and the pre-roll,
+    /// reset the processor and restart positions at 0.
+    pub fn reset(&mut self) {
+        self.processor.reset();
+        self.pre_roll.clear();
+        self.segment = None;
+        self.position = 0;
+    }
+}
+
+impl AudioStage for SegmentedProcessor {
+    fn process(&mut self, samples: &mut [f32]) {
+        SegmentedProcessor::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        SegmentedProcessor::reset(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{make_sine, peak};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_speech_silence_speech_gives_two_segments() {
+        let segments = Arc::new(Mutex::new(Vec::new()));
+        let sink = segments.clone();
+        let mut segmented = SegmentedProcessor::new(SystemAudioProcessor::new(), move |audio, start, end| {
+            sink.lock().unwrap().push((audio, start, end));
+        });
+
+        // 500ms speech, 1s silence, 500ms speech, 500ms silence
+        let mut input = make_sine(440.0, 0.3, 48000.0, 24000);
+        input.extend(vec![0.0; 48000]);
+        input.extend(make_sine(440.0, 0.3, 48000.0, 24000));
+        input.extend(vec![0.0; 24000]);
+        for frame in input.chunks_mut(1024) {
+            segmented.process(frame);
+        }
+
+        let segments = segments.lock().unwrap();
+        assert_eq!(segments.len(), 2, "Expected two segments");
+        for (audio, start, end) in segments.iter() {
+            assert_eq!(audio.len() as u64, end - start);
+        }
+
+        // First starts with the stream; both end after hold + release,
+        // well before the next one starts
+        let (_, start, end) = &segments[0];
+        assert_eq!(*start, 0);
+        assert!((24000..30000).contains(end), "First segment end {}", end);
+
+        // Second opens at 1.5s with 100ms of pre-roll ahead of the onset
+        let (audio, start, end) = &segments[1];
+        assert_eq!(*start, 72000 - DEFAULT_PRE_ROLL as u64);
+        assert!((96000..102000).contains(end), "Second segment end {}", end);
+        assert!(peak(&audio[..DEFAULT_PRE_ROLL]) < 0.01, "Pre-roll should be the quiet lead-in");
+        assert!(peak(&audio[DEFAULT_PRE_ROLL..DEFAULT_PRE_ROLL + 480]) > 0.1, "Onset should follow the pre-roll");
+        assert!(!segmented.in_segment());
+    }
+
+    #[test]
+    fn test_flush_emits_open_segment() {
+        let count = Arc::new(Mutex::new(0));
+        let sink = count.clone();
+        let mut segmented = SegmentedProcessor::new(SystemAudioProcessor::new(), move |_, _, _| {
+            *sink.lock().unwrap() += 1;
+        });
+        segmented.process(&mut make_sine(440.0, 0.3, 48000.0, 4800));
+        assert!(segmented.in_segment());
+        segmented.flush();
+        assert_eq!(*count.lock().unwrap(), 1);
+        assert!(segmented.set_pre_roll_ms(-1.0).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+// Common interface for in-place f32 processing stages
+//
+// Every DSP stage works in-place on 48kHz mono f32. The trait lets callers
+// hold stages generically and gives each one the same convenience helpers.
+// `StageChain` runs boxed stages in order, so a pipeline can be assembled
+// at runtime from a config list.
+
+pub trait AudioStage {
+    /// Process `samples` in-place.
+    fn process(&mut self, samples: &mut [f32]);
+
+    /// Clear all runtime state (filters, envelopes, gains), keeping the
+    /// stage's configuration.
+    fn reset(&mut self);
+
+    /// Delay the stage adds to the signal, in samples.
+    fn latency_samples(&self) -> usize {
+        0
+    }
+
+    /// Process `input` into `output`, leaving `input` untouched, e.g. so the
+    /// raw stream can be recorded alongside the processed one. Doesn't
+    /// allocate. Panics if the lengths differ.
+    fn process_to(&mut self, input: &[f32], output: &mut [f32]) {
+        assert_eq!(input.len(), output.len(), "process_to: input and output lengths differ");
+        output.copy_from_slice(input);
+        self.process(output);
+    }
+
+    /// Process a copy of `input` and return it, leaving `input` untouched
+    /// (for dry/wet mixing, logging the raw stream, or offline processing
+    /// of an immutable buffer). Allocates, so keep it off the realtime path.
+    fn process_to_vec(&mut self, input: &[f32]) -> Vec<f32> {
+        let mut output = input.to_vec();
+        self.process(&mut output);
+        output
+    }
+}
+
+/// Stages run in order, itself usable as a single stage.
+#[derive(Default)]
+pub struct StageChain(Vec<Box<dyn AudioStage>>);
+
+impl StageChain {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Append a stage to the end of the chain.
+    pub fn push(&mut self, stage: Box<dyn AudioStage>) {
+        self.0.push(stage);
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Vec<Box<dyn AudioStage>>> for StageChain {
+    fn from(stages: Vec<Box<dyn AudioStage>>) -> Self {
+        Self(stages)
+    }
+}
+
+impl AudioStage for StageChain {
+    fn process(&mut self, samples: &mut [f32]) {
+        for stage in self.0.iter_mut() {
+            stage.process(samples);
+        }
+    }
+
+    fn reset(&mut self) {
+        for stage in self.0.iter_mut() {
+            stage.reset();
+        }
+    }
+
+    /// Sum of the stages' latencies.
+    fn latency_samples(&self) -> usize {
+        self.0.iter().map(|stage| stage.latency_samples()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agc::AutoGainControl;
+    use crate::compressor::{NoiseGate, SpeechCompressor, SystemAudioProcessor};
+    use crate::pre_emphasis::PreEmphasis;
+    use crate::stream_processor::StreamProcessor;
+    use crate::test_signals::make_sine;
+
+    fn check<S: AudioStage>(mut a: S, mut b: S) {
+        let input = make_sine(440.0, 0.05, 48000.0, 4800);
+        let original = input.clone();
+        let out = a.process_to_vec(&input);
+        assert_eq!(input, original, "process_to_vec must not modify its input");
+
+        let mut in_place = input.clone();
+        b.process(&mut in_place);
+        assert_eq!(out, in_place);
+    }
+
+    #[test]
+    fn test_process_to_vec_matches_in_place() {
+        check(SpeechCompressor::new(), SpeechCompressor::new());
+        check(NoiseGate::new(), NoiseGate::new());
+        check(SystemAudioProcessor::new(), SystemAudioProcessor::new());
+        check(AutoGainControl::new(), AutoGainControl::new());
+        check(PreEmphasis::new(), PreEmphasis::new());
+    }
+
+    #[test]
+    fn test_chain_matches_manual_sequencing() {
+        let input = make_sine(440.0, 0.3, 48000.0, 9600);
+
+        let mut manual = input.clone();
+        let mut pre_emphasis = PreEmphasis::new();
+        let mut compressor = SpeechCompressor::new();
+        for block in manual.chunks_mut(480) {
+            pre_emphasis.process(block);
+            compressor.process(block);
+        }
+
+        let mut chain = StageChain::from(vec![
+            Box::new(PreEmphasis::new()) as Box<dyn AudioStage>,
+            Box::new(SpeechCompressor::new()),
+        ]);
+        assert_eq!(chain.len(), 2);
+        let mut chained = input.clone();
+        for block in chained.chunks_mut(480) {
+            chain.process(block);
+        }
+        assert_eq!(chained, manual);
+
+        // After a reset the chain behaves like a fresh one
+        chain.reset();
+        let mut again = input.clone();
+        for block in again.chunks_mut(480) {
+            chain.process(block);
+        }
+        assert_eq!(again, manual);
+    }
+
+    #[test]
+    fn test_chain_latency_sums_stages() {
+        let mut chain = StageChain::new();
+        assert!(chain.is_empty());
+        chain.push(Box::new(PreEmphasis::new()));
+        chain.push(Box::new(StreamProcessor::with_block_size(SystemAudioProcessor::new(), 256)));
+        assert_eq!(chain.latency_samples(), 256);
+    }
+}
@@ -0,0 +1,155 @@
+// Fixed-block streaming wrapper for SystemAudioProcessor
+//
+// Capture callbacks deliver wildly varying frame sizes (64 … 4096). The
+// detectors behave slightly differently depending on how input is sliced,
+// so this wrapper decouples host frame size from DSP block size:
+//
+//   host frames → input FIFO → fixed blocks → processor → output FIFO → host
+//
+// Each call returns exactly as many samples as it was given. The output is
+// delayed by one block (primed with silence) so there is always a full
+// block's worth of processed audio ready. `flush()` drains the tail.
+
+use std::collections::VecDeque;
+
+use crate::compressor::SystemAudioProcessor;
+use crate::stage::AudioStage;
+
+/// Default internal block: 10ms at 48kHz
+const DEFAULT_BLOCK_SIZE: usize = 480;
+
+pub struct StreamProcessor {
+    processor: SystemAudioProcessor,
+    block_size: usize,
+    input: VecDeque<f32>,
+    output: VecDeque<f32>,
+    /// Scratch block handed to the processor
+    block: Vec<f32>,
+}
+
+impl StreamProcessor {
+    pub fn new(processor: SystemAudioProcessor) -> Self {
+        Self::with_block_size(processor, DEFAULT_BLOCK_SIZE)
+    }
+
+    pub fn with_block_size(processor: SystemAudioProcessor, block_size: usize) -> Self {
+        let block_size = block_size.max(1);
+        let mut output = VecDeque::with_capacity(block_size * 4);
+        output.resize(block_size, 0.0);
+        Self {
+            processor,
+            block_size,
+            input: VecDeque::with_capacity(block_size * 4),
+            output,
+            block: Vec::with_capacity(block_size),
+        }
+    }
+
+    /// Output delay in samples (one internal block).
+    pub fn latency(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn processor_mut(&mut self) -> &mut SystemAudioProcessor {
+        &mut self.processor
+    }
+
+    /// Process any number of samples in-place. The returned audio is the
+    /// processed stream delayed by `latency()` samples.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        self.input.extend(samples.iter().copied());
+
+        while self.input.len() >= self.block_size {
+            self.block.clear();
+            self.block.extend(self.input.drain(..self.block_size));
+            self.processor.process(&mut self.block);
+            self.output.extend(self.block.iter().copied());
+        }
+
+        // The one-block priming guarantees at least `samples.len()` are ready
+        let n = samples.len();
+        for (dst, src) in samples.iter_mut().zip(self.output.drain(..n)) {
+            *dst = src;
+        }
+    }
+
+    /// Process any partial block still queued and return all remaining
+    /// output. After a flush, total output = total input + `latency()`.
+    pub fn flush(&mut self) -> Vec<f32> {
+        if !self.input.is_empty() {
+            self.block.clear();
+            self.block.extend(self.input.drain(..));
+            self.processor.process(&mut self.block);
+            self.output.extend(self.block.iter().copied());
+        }
+        let tail = self.output.drain(..).collect();
+        // Re-prime so streaming can continue after a flush
+        self.output.resize(self.block_size, 0.0);
+        tail
+    }
+
+    /// Drop queued audio and reset the processor, re-priming the one-block
+    /// delay.
+    pub fn reset(&mut self) {
+        self.processor.reset();
+        self.input.clear();
+        self.output.clear();
+        self.output.resize(self.block_size, 0.0);
+    }
+}
+
+impl AudioStage for StreamProcessor {
+    fn process(&mut self, samples: &mut [f32]) {
+        StreamProcessor::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        StreamProcessor::reset(self);
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.latency()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::make_sine;
+
+    #[test]
+    fn test_irregular_chunks_preserve_length() {
+        let input = make_sine(440.0, 0.05, 48000.0, 48000);
+        let mut stream = StreamProcessor::new(SystemAudioProcessor::new());
+
+        let sizes = [64usize, 4096, 1, 333, 480, 2000, 17];
+        let mut output = Vec::new();
+        let mut pos = 0;
+        let mut i = 0;
+        while pos < input.len() {
+            let n = sizes[i % sizes.len()].min(input.len() - pos);
+            let mut chunk = input[pos..pos + n].to_vec();
+            stream.process(&mut chunk);
+            assert_eq!(chunk.len(), n);
+            output.extend(chunk);
+            pos += n;
+            i += 1;
+        }
+        assert_eq!(output.len(), input.len());
+
+        let tail = stream.flush();
+        assert_eq!(output.len() + tail.len(), input.len() + stream.latency());
+        output.extend(tail);
+
+        // Identical to processing fixed 480-sample blocks, delayed by one block
+        let mut reference = input.clone();
+        let mut proc = SystemAudioProcessor::new();
+        for block in reference.chunks_mut(480) {
+            proc.process(block);
+        }
+        assert!(output[..480].iter().all(|&s| s == 0.0));
+        for (a, b) in output[480..].iter().zip(&reference) {
+            assert!((a - b).abs() < 1e-6);
+        }
+    }
+}
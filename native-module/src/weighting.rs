@@ -0,0 +1,125 @@
+// A-weighting filter (IEC 61672) for level detectors
+//
+// Approximates the ear's reduced sensitivity at low and very high
+// frequencies, so a detector fed through it responds to speech-band energy
+// rather than rumble: 60 Hz is attenuated by ~27 dB, 1 kHz is 0 dB.
+//
+// The analog prototype's real poles (20.6 Hz ×2, 107.7 Hz, 737.9 Hz,
+// 12194 Hz ×2) are mapped with the bilinear transform into three biquads:
+//   high-pass 20.6/20.6 Hz → high-pass 107.7/737.9 Hz → low-pass 12194/12194 Hz
+// Without pre-warping the top pole lands a little low, so the response
+// above ~8 kHz falls off faster than the standard; irrelevant for speech
+// detection. Gain is normalised to exactly 0 dB at 1 kHz.
+
+use std::f64::consts::PI;
+
+use crate::biquad::Biquad;
+
+/// Analog prototype pole frequencies (Hz)
+const POLE_LOW_HZ: f64 = 20.598_997;
+const POLE_MID1_HZ: f64 = 107.652_65;
+const POLE_MID2_HZ: f64 = 737.862_23;
+const POLE_HIGH_HZ: f64 = 12_194.217;
+/// Normalisation frequency
+const REFERENCE_HZ: f64 = 1000.0;
+
+/// Normalised biquad coefficients `[b0, b1, b2, a1, a2]`
+type Coefficients = [f64; 5];
+
+/// Bilinear transform of s² / ((s + p1)(s + p2)) — two zeros at DC
+fn highpass_pair(f1: f64, f2: f64, sample_rate: f64) -> Coefficients {
+    let k = 2.0 * sample_rate;
+    let (p1, p2) = (2.0 * PI * f1, 2.0 * PI * f2);
+    let a0 = k * k + (p1 + p2) * k + p1 * p2;
+    [
+        k * k / a0,
+        -2.0 * k * k / a0,
+        k * k / a0,
+        (2.0 * p1 * p2 - 2.0 * k * k) / a0,
+        (k * k - (p1 + p2) * k + p1 * p2) / a0,
+    ]
+}
+
+/// Bilinear transform of p1·p2 / ((s + p1)(s + p2))
+fn lowpass_pair(f1: f64, f2: f64, sample_rate: f64) -> Coefficients {
+    let k = 2.0 * sample_rate;
+    let (p1, p2) = (2.0 * PI * f1, 2.0 * PI * f2);
+    let a0 = k * k + (p1 + p2) * k + p1 * p2;
+    [
+        p1 * p2 / a0,
+        2.0 * p1 * p2 / a0,
+        p1 * p2 / a0,
+        (2.0 * p1 * p2 - 2.0 * k * k) / a0,
+        (k * k - (p1 + p2) * k + p1 * p2) / a0,
+    ]
+}
+
+/// |H(e^jw)| of one section
+fn magnitude(c: &Coefficients, freq: f64, sample_rate: f64) -> f64 {
+    let w = 2.0 * PI * freq / sample_rate;
+    let (cos1, sin1, cos2, sin2) = (w.cos(), w.sin(), (2.0 * w).cos(), (2.0 * w).sin());
+    let num = ((c[0] + c[1] * cos1 + c[2] * cos2).powi(2) + (c[1] * sin1 + c[2] * sin2).powi(2)).sqrt();
+    let den = ((1.0 + c[3] * cos1 + c[4] * cos2).powi(2) + (c[3] * sin1 + c[4] * sin2).powi(2)).sqrt();
+    num / den
+}
+
+pub struct AWeighting {
+    stages: [Biquad; 3],
+}
+
+impl AWeighting {
+    pub fn new(sample_rate: f32) -> Self {
+        let sr = sample_rate as f64;
+        let mut sections = [
+            highpass_pair(POLE_LOW_HZ, POLE_LOW_HZ, sr),
+            highpass_pair(POLE_MID1_HZ, POLE_MID2_HZ, sr),
+            lowpass_pair(POLE_HIGH_HZ, POLE_HIGH_HZ, sr),
+        ];
+
+        // Fold the 1 kHz normalisation into the first section's numerator
+        let gain: f64 = sections.iter().map(|c| magnitude(c, REFERENCE_HZ, sr)).product();
+        for b in sections[0][..3].iter_mut() {
+            *b /= gain;
+        }
+
+        let stage = |c: Coefficients| {
+            Biquad::from_coefficients(c[0] as f32, c[1] as f32, c[2] as f32, c[3] as f32, c[4] as f32)
+        };
+        Self {
+            stages: sections.map(stage),
+        }
+    }
+
+    #[inline]
+    pub fn process_sample(&mut self, x: f32) -> f32 {
+        self.stages.iter_mut().fold(x, |acc, stage| stage.process_sample(acc))
+    }
+
+    pub fn reset(&mut self) {
+        for stage in self.stages.iter_mut() {
+            stage.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{make_sine, rms};
+
+    fn response_db(freq: f32) -> f32 {
+        let mut filter = AWeighting::new(48000.0);
+        let signal = make_sine(freq, 0.5, 48000.0, 48000);
+        let out: Vec<f32> = signal.iter().map(|&x| filter.process_sample(x)).collect();
+        20.0 * (rms(&out[24000..]) / rms(&signal[24000..])).log10()
+    }
+
+    #[test]
+    fn test_a_weighting_response() {
+        // IEC 61672 table values
+        for (freq, expected) in [(63.0, -26.2), (125.0, -16.1), (1000.0, 0.0), (4000.0, 1.0)] {
+            let db = response_db(freq);
+            assert!((db - expected).abs() < 0.5, "{}Hz: expected {:.1}dB, got {:.2}dB", freq, expected, db);
+        }
+    }
+}